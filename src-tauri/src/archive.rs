@@ -0,0 +1,232 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use rusqlite::{Connection, OpenFlags};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+use crate::models::{SearchResult, TimeBlock, TIME_BLOCK_COLUMNS};
+use crate::services::FileService;
+
+// Sits next to the live database; notes and attachment files stay where they are
+pub const ARCHIVE_FILE: &str = "archive.db";
+
+// Attachments and named notes sections are copied with their blocks; block_tags
+// aren't needed since time_blocks.tags keeps a JSON copy
+const ARCHIVED_TABLES: [&str; 5] = ["time_blocks", "attachments", "block_notes", "priorities", "brain_dumps"];
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveSummary {
+    pub time_blocks: usize,
+    pub attachments: usize,
+    pub note_sections: usize,
+    pub priorities: usize,
+    pub brain_dumps: usize,
+    pub block_ids: Vec<i64>,  // Blocks to drop from the search index
+}
+
+/// Move everything dated before `cutoff` (YYYY-MM-DD) into the archive database.
+pub fn archive_before(conn: &Connection, data_dir: &Path, cutoff: &str) -> Result<ArchiveSummary> {
+    NaiveDate::parse_from_str(cutoff, "%Y-%m-%d")
+        .map_err(|_| anyhow!("Invalid cutoff date: {}", cutoff))?;
+
+    // ATTACH can't run inside a transaction, so it wraps the move
+    let archive_path = data_dir.join(ARCHIVE_FILE);
+    conn.execute("ATTACH DATABASE ?1 AS archive", [archive_path.to_string_lossy()])?;
+    let moved = move_to_archive(conn, cutoff);
+    let detached = conn.execute("DETACH DATABASE archive", []);
+
+    let summary = moved?;
+    detached?;
+    Ok(summary)
+}
+
+fn move_to_archive(conn: &Connection, cutoff: &str) -> Result<ArchiveSummary> {
+    for table in ARCHIVED_TABLES {
+        prepare_archive_table(conn, table)?;
+    }
+
+    let tx = conn.unchecked_transaction()?;
+    let block_ids = {
+        let mut stmt = tx.prepare("SELECT id FROM main.time_blocks WHERE date < ?1")?;
+        let ids = stmt.query_map([cutoff], |row| row.get(0))?
+            .collect::<rusqlite::Result<Vec<i64>>>()?;
+        ids
+    };
+
+    let summary = ArchiveSummary {
+        attachments: copy_rows(&tx, "attachments", "time_block_id IN (SELECT id FROM main.time_blocks WHERE date < ?1)", cutoff)?,
+        note_sections: copy_rows(&tx, "block_notes", "time_block_id IN (SELECT id FROM main.time_blocks WHERE date < ?1)", cutoff)?,
+        time_blocks: copy_rows(&tx, "time_blocks", "date < ?1", cutoff)?,
+        priorities: copy_rows(&tx, "priorities", "date < ?1", cutoff)?,
+        brain_dumps: copy_rows(&tx, "brain_dumps", "date < ?1", cutoff)?,
+        block_ids,
+    };
+
+    // Attachment, block_notes and block_tags rows follow their blocks via ON DELETE CASCADE
+    tx.execute("DELETE FROM main.time_blocks WHERE date < ?1", [cutoff])?;
+    tx.execute("DELETE FROM main.priorities WHERE date < ?1", [cutoff])?;
+    tx.execute("DELETE FROM main.brain_dumps WHERE date < ?1", [cutoff])?;
+    tx.commit()?;
+
+    Ok(summary)
+}
+
+fn column_names(conn: &Connection, schema: &str, table: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(&format!("PRAGMA {}.table_info({})", schema, table))?;
+    let names = stmt.query_map([], |row| row.get::<_, String>(1))?
+        .collect::<rusqlite::Result<Vec<String>>>()?;
+    Ok(names)
+}
+
+// Create the archive copy of a table, adding any columns the live schema gained since
+fn prepare_archive_table(conn: &Connection, table: &str) -> Result<()> {
+    conn.execute(
+        &format!("CREATE TABLE IF NOT EXISTS archive.{0} AS SELECT * FROM main.{0} WHERE 0", table),
+        [],
+    )?;
+
+    let archived = column_names(conn, "archive", table)?;
+    for column in column_names(conn, "main", table)? {
+        if !archived.contains(&column) {
+            conn.execute(&format!("ALTER TABLE archive.{} ADD COLUMN {}", table, column), [])?;
+        }
+    }
+    Ok(())
+}
+
+fn copy_rows(conn: &Connection, table: &str, filter: &str, cutoff: &str) -> Result<usize> {
+    let columns = column_names(conn, "main", table)?.join(", ");
+    let copied = conn.execute(
+        &format!("INSERT INTO archive.{0} ({1}) SELECT {1} FROM main.{0} WHERE {2}", table, columns, filter),
+        [cutoff],
+    )?;
+    Ok(copied)
+}
+
+/// Case-insensitive match of every word against archived block titles, tags and notes.
+/// Returns newest first; archive results aren't ranked.
+pub fn search_archive(data_dir: &Path, files: &FileService, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+    let words: Vec<String> = query.split_whitespace().map(|word| word.to_lowercase()).collect();
+    if words.is_empty() {
+        return Err(anyhow!("Search query is empty"));
+    }
+
+    let archive_path = data_dir.join(ARCHIVE_FILE);
+    if !archive_path.exists() {
+        return Ok(Vec::new());
+    }
+    let conn = Connection::open_with_flags(&archive_path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+
+    // An archive last written by an older version lacks newer columns; read those as NULL
+    let archived = column_names(&conn, "main", "time_blocks")?;
+    let columns: Vec<String> = TIME_BLOCK_COLUMNS.split(", ")
+        .map(|column| match archived.iter().any(|name| name == column) {
+            true => column.to_string(),
+            false => format!("NULL AS {}", column),
+        })
+        .collect();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM time_blocks ORDER BY date DESC, start_minutes",
+        columns.join(", ")
+    ))?;
+    let blocks = stmt.query_map([], TimeBlock::from_row)?
+        .collect::<rusqlite::Result<Vec<TimeBlock>>>()?;
+
+    let mut results = Vec::new();
+    for block in blocks {
+        let notes = match &block.notes_file {
+            Some(notes_file) => files.load_notes(notes_file).unwrap_or_default(),
+            None => String::new(),
+        };
+        let haystack = format!("{}\n{}\n{}", block.title, block.tags.join(" "), notes).to_lowercase();
+        if !words.iter().all(|word| haystack.contains(word.as_str())) {
+            continue;
+        }
+
+        results.push(SearchResult {
+            id: block.id.unwrap_or_default(),
+            title: block.title,
+            content: notes,
+            date: block.date,
+            start_minutes: block.start_minutes,
+            duration_minutes: block.duration_minutes,
+            tags: block.tags,
+            score: 0.0,
+            highlights: Vec::new(),
+            section: None,
+            color: block.color,
+        });
+        if results.len() >= limit {
+            break;
+        }
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::load_time_blocks;
+    use tempfile::tempdir;
+
+    fn insert_day(conn: &Connection, date: &str, title: &str) {
+        conn.execute(
+            "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title, tags) VALUES (?1, 540, 30, ?2, '[\"work\"]')",
+            (date, title),
+        ).unwrap();
+        let block_id = conn.last_insert_rowid();
+        conn.execute(
+            "INSERT INTO attachments (time_block_id, file_path, file_name, file_type) VALUES (?1, 'attachments/a.png', 'a.png', 'image')",
+            [block_id],
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO block_notes (time_block_id, section_name, file_path) VALUES (?1, 'Agenda', 'notes/agenda.md')",
+            [block_id],
+        ).unwrap();
+        conn.execute("INSERT INTO priorities (date, content) VALUES (?1, 'Ship it')", [date]).unwrap();
+        conn.execute("INSERT INTO brain_dumps (date, content) VALUES (?1, 'Ideas')", [date]).unwrap();
+    }
+
+    #[test]
+    fn test_archive_moves_old_rows_and_is_searchable() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+
+        insert_day(&conn, "2024-01-15", "Quarterly planning");
+        insert_day(&conn, "2025-06-01", "Focus");
+
+        let summary = archive_before(&conn, dir.path(), "2025-01-01").unwrap();
+        assert_eq!(summary.time_blocks, 1);
+        assert_eq!(summary.attachments, 1);
+        assert_eq!(summary.note_sections, 1);
+        assert_eq!(summary.priorities, 1);
+        assert_eq!(summary.brain_dumps, 1);
+        assert_eq!(summary.block_ids.len(), 1);
+
+        assert!(load_time_blocks(&conn, "2024-01-15").unwrap().is_empty());
+        assert_eq!(load_time_blocks(&conn, "2025-06-01").unwrap().len(), 1);
+        let live_priorities: i64 = conn.query_row("SELECT COUNT(*) FROM priorities", [], |row| row.get(0)).unwrap();
+        assert_eq!(live_priorities, 1);
+
+        let results = search_archive(dir.path(), &files, "QUARTERLY", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, summary.block_ids[0]);
+        assert!(search_archive(dir.path(), &files, "focus", 10).unwrap().is_empty());
+
+        // Archiving again appends to the existing archive
+        let summary = archive_before(&conn, dir.path(), "2026-01-01").unwrap();
+        assert_eq!(summary.time_blocks, 1);
+        assert_eq!(search_archive(dir.path(), &files, "work", 10).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_invalid_cutoff_is_rejected() {
+        let dir = tempdir().unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+        assert!(archive_before(&conn, dir.path(), "last year").is_err());
+    }
+}
@@ -1,34 +1,326 @@
-use crate::models::{CalendarConnection, CalendarEvent};
-use crate::crypto::TokenEncryption;
+use crate::models::{Attendee, CalendarConnection, CalendarEvent, ConnectionParams, ConnectionSyncResult, ConnectionStatus, SecurityStatus, CALENDAR_CONNECTION_COLUMNS, CALENDAR_EVENT_COLUMNS};
+use crate::crypto::{key_path, looks_encrypted, KeyStorage, TokenEncryption};
+use crate::DbPool;
+use crate::error::AppError;
 use anyhow::{anyhow, Result};
-use reqwest::Client;
-use rusqlite::Connection;
+use log::{debug, error, info, warn};
+use futures::stream::{self, StreamExt};
+use reqwest::{Client, StatusCode};
+use rusqlite::OptionalExtension;
 use serde_json::Value;
-use std::sync::{Arc, Mutex};
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
+
+const DEFAULT_MAX_RETRIES: i32 = 5;
+const DEFAULT_SYNC_PARALLELISM: i32 = 2;
+const DEFAULT_SYNC_INTERVAL: i32 = 5;  // Minutes
+const DEFAULT_EVENT_RETENTION_DAYS: i32 = 90;
+const SYNC_WINDOW_DAYS: i64 = 30;
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(32);
+
+// When the next automatic sync is due, as RFC 3339
+fn next_sync_time(last_sync: &str, interval_minutes: i32) -> Option<String> {
+    let last_sync = chrono::DateTime::parse_from_rfc3339(last_sync).ok()?;
+    let next = last_sync + chrono::Duration::minutes(interval_minutes.max(1) as i64);
+    Some(next.with_timezone(&chrono::Utc).to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true))
+}
+
+pub enum EventQuery<'a> {
+    Window { time_min: &'a str, time_max: &'a str },
+    Changes { sync_token: &'a str },
+}
+
+#[derive(Debug, Default)]
+pub struct EventChanges {
+    pub events: Vec<CalendarEvent>,
+    pub cancelled: Vec<String>,  // External ids of deleted events
+    pub next_sync_token: Option<String>,
+    pub window_end: Option<String>,  // timeMax of a window query; changes queries keep the old one
+}
+
+// Google answers 410 Gone once a sync token expires; a full resync is needed
+#[derive(Debug)]
+struct SyncTokenExpired;
+
+impl std::fmt::Display for SyncTokenExpired {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Calendar sync token expired")
+    }
+}
+
+impl std::error::Error for SyncTokenExpired {}
+
+// Marks a sync as running; dropping it, even on an error or a cancelled future,
+// lets the next sync start
+struct SyncGuard<'a>(&'a AtomicBool);
+
+impl Drop for SyncGuard<'_> {
+    fn drop(&mut self) {
+        self.0.store(false, Ordering::Release);
+    }
+}
+
+// Google signals quota errors with 429, or 403 with a rate limit reason
+fn is_rate_limited(status: StatusCode, body: &str) -> bool {
+    if status == StatusCode::TOO_MANY_REQUESTS {
+        return true;
+    }
+    if status != StatusCode::FORBIDDEN {
+        return false;
+    }
+
+    let data: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+    data["error"]["errors"]
+        .as_array()
+        .map(|errors| errors.iter().any(|error| {
+            matches!(error["reason"].as_str(), Some("rateLimitExceeded") | Some("userRateLimitExceeded"))
+        }))
+        .unwrap_or(false)
+}
+
+// Exponential backoff from 1s, capped, plus up to the same again in random jitter
+fn retry_delay(attempt: u32, retry_after: Option<Duration>) -> Duration {
+    let base = Duration::from_secs(1u64 << attempt.min(5)).min(MAX_RETRY_DELAY);
+    let mut random = [0u8; 2];
+    let _ = ring::rand::SecureRandom::fill(&ring::rand::SystemRandom::new(), &mut random);
+    let jitter = base.mul_f64(u16::from_le_bytes(random) as f64 / u16::MAX as f64);
+    retry_after.unwrap_or_default().max(base + jitter)
+}
+
+// Interpret a tokeninfo response. Google reports expired and revoked access tokens
+// the same way, so an invalid token counts as revoked only when there's no refresh
+// token to fall back on.
+fn classify_token_check(status: StatusCode, body: &str, has_refresh_token: bool) -> (ConnectionStatus, Option<String>) {
+    if status.is_success() {
+        return (ConnectionStatus::Valid, None);
+    }
+
+    let data: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+    let reason = data["error_description"].as_str()
+        .or_else(|| data["error"].as_str())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("Token check failed with HTTP {}", status.as_u16()));
+
+    if has_refresh_token {
+        (ConnectionStatus::NeedsRefresh, Some(reason))
+    } else {
+        (ConnectionStatus::Revoked, Some(reason))
+    }
+}
+
+// Google's {"error": {"message": ...}} body, falling back to the raw text
+fn api_error_message(body: &str) -> String {
+    let data: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+    data["error"]["message"]
+        .as_str()
+        .map(str::to_string)
+        .unwrap_or_else(|| body.trim().to_string())
+}
+
+// Account name for a CalDAV login, e.g. "me@dav.example.com"
+fn caldav_account_name(url: &str, username: &str) -> Result<String> {
+    let parsed = reqwest::Url::parse(url)
+        .map_err(|e| AppError::Validation(format!("Invalid CalDAV URL '{}': {}", url, e)))?;
+    if !matches!(parsed.scheme(), "http" | "https") {
+        return Err(AppError::Validation(format!("CalDAV URL must use http or https: {}", url)).into());
+    }
+    let host = parsed.host_str()
+        .ok_or_else(|| AppError::Validation(format!("CalDAV URL has no host: {}", url)))?;
+    Ok(format!("{}@{}", username, host))
+}
+
+// A PROPFIND answers 207 Multi-Status when the URL and login are good
+fn check_caldav_response(status: StatusCode, url: &str) -> Result<()> {
+    match status.as_u16() {
+        200..=299 => Ok(()),
+        401 | 403 => Err(AppError::Auth("The CalDAV server rejected the username or password".to_string()).into()),
+        404 => Err(AppError::Validation(format!("No CalDAV calendar found at {}", url)).into()),
+        405 | 501 => Err(AppError::Validation(format!("{} doesn't look like a CalDAV server", url)).into()),
+        code => Err(anyhow!("CalDAV server returned HTTP {}", code)),
+    }
+}
+
+// Interpret a token endpoint response. OAuth errors come back as
+// {"error": ..., "error_description": ...} and are surfaced verbatim,
+// e.g. "invalid_grant: Bad Request".
+fn parse_token_response(status: reqwest::StatusCode, body: &str) -> Result<(String, Option<String>)> {
+    if !status.is_success() {
+        let data: Value = serde_json::from_str(body).unwrap_or(Value::Null);
+        return Err(match (data["error"].as_str(), data["error_description"].as_str()) {
+            (Some(error), Some(description)) => anyhow!("{}: {}", error, description),
+            (Some(error), None) => anyhow!("{}", error),
+            _ => anyhow!("Token request failed with HTTP {}: {}", status.as_u16(), body.trim()),
+        });
+    }
+
+    let data: Value = serde_json::from_str(body)
+        .map_err(|e| anyhow!("Invalid token response: {}", e))?;
+
+    let access_token = data["access_token"]
+        .as_str()
+        .ok_or_else(|| anyhow!("No access token in response"))?
+        .to_string();
+
+    let refresh_token = data["refresh_token"].as_str().map(|s| s.to_string());
+
+    Ok((access_token, refresh_token))
+}
 
 pub struct CalendarService {
     http_client: Client,
-    db: Arc<Mutex<Connection>>,
-    crypto: Option<TokenEncryption>,
+    db: DbPool,
+    crypto: RwLock<Option<TokenEncryption>>,  // None falls back to plaintext tokens
+    data_dir: PathBuf,
+    syncing: AtomicBool,  // Shared by every sync, summed or detailed
 }
 
 impl CalendarService {
-    pub fn new(db: Arc<Mutex<Connection>>, data_dir: PathBuf) -> Self {
+    pub fn new(db: DbPool, data_dir: PathBuf) -> Self {
         // Try to initialize encryption, but don't fail if it doesn't work
-        let crypto = TokenEncryption::new(&data_dir).ok();
+        let crypto = TokenEncryption::with_storage(&data_dir, Self::key_storage(&db), Self::has_encrypted_tokens(&db)).ok();
         
         if crypto.is_none() {
-            eprintln!("Warning: Token encryption not available. Tokens will be stored in plain text.");
+            warn!("Token encryption not available. Tokens will be stored in plain text.");
         }
         
         Self {
             http_client: Client::new(),
             db,
-            crypto,
+            crypto: RwLock::new(crypto),
+            data_dir,
+            syncing: AtomicBool::new(false),
         }
     }
 
+    // The encryption_key_storage setting; the key file unless set to keychain
+    fn key_storage(db: &DbPool) -> KeyStorage {
+        db.get().ok()
+            .and_then(|conn| crate::commands::read_setting(&conn, "encryption_key_storage").ok().flatten())
+            .and_then(|value| KeyStorage::parse(&value))
+            .unwrap_or(KeyStorage::File)
+    }
+
+    // Whether any stored token looks encrypted, in which case a lost key must not be
+    // replaced with a new one. Assumes so if the database can't be read.
+    fn has_encrypted_tokens(db: &DbPool) -> bool {
+        let tokens = db.get().map_err(anyhow::Error::from).and_then(|conn| {
+            let mut stmt = conn.prepare("SELECT access_token, refresh_token FROM calendar_connections")?;
+            let tokens = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            Ok(tokens)
+        });
+        match tokens {
+            Ok(tokens) => tokens.iter().any(|(access, refresh)| {
+                looks_encrypted(access) || refresh.as_deref().is_some_and(looks_encrypted)
+            }),
+            Err(_) => true,
+        }
+    }
+
+    fn crypto(&self) -> RwLockReadGuard<'_, Option<TokenEncryption>> {
+        self.crypto.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn encryption_active(&self) -> bool {
+        self.crypto().is_some()
+    }
+
+    fn is_encrypted(crypto: Option<&TokenEncryption>, token: &str) -> bool {
+        match crypto {
+            Some(crypto) => crypto.decrypt(token).is_ok(),
+            None => looks_encrypted(token),
+        }
+    }
+
+    // Whether tokens are protected at rest, for warning the user in settings
+    pub fn security_status(&self) -> Result<SecurityStatus> {
+        let crypto = self.crypto();
+        // Read before checking out a connection; key_storage takes one of its own
+        let key_storage = match crypto.as_ref() {
+            Some(crypto) => crypto.storage(),
+            None => Self::key_storage(&self.db),
+        };
+        let conn = self.db.get()?;
+        let mut stmt = conn.prepare("SELECT access_token, refresh_token FROM calendar_connections")?;
+        let tokens = stmt.query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+
+        let plaintext_connections = tokens.iter()
+            .filter(|(access, refresh)| {
+                std::iter::once(access).chain(refresh.iter())
+                    .any(|token| !Self::is_encrypted(crypto.as_ref(), token))
+            })
+            .count() as i64;
+
+        Ok(SecurityStatus {
+            encryption_active: crypto.is_some(),
+            key_file_path: key_path(&self.data_dir).to_string_lossy().into_owned(),
+            key_storage: key_storage.as_str().to_string(),
+            plaintext_connections,
+            has_plaintext_tokens: plaintext_connections > 0,
+        })
+    }
+
+    // Encrypt tokens that were stored while encryption was unavailable. Retries
+    // setting up encryption first; returns how many connections were updated.
+    pub fn reencrypt_plaintext_tokens(&self) -> Result<usize> {
+        if self.crypto().is_none() {
+            let crypto = TokenEncryption::with_storage(&self.data_dir, Self::key_storage(&self.db), Self::has_encrypted_tokens(&self.db))?;
+            *self.crypto.write().unwrap_or_else(|e| e.into_inner()) = Some(crypto);
+        }
+
+        let guard = self.crypto();
+        let crypto = guard.as_ref().ok_or_else(|| anyhow!("Token encryption is not available"))?;
+        let encrypt = |token: &str| -> Result<String> {
+            if crypto.decrypt(token).is_ok() {
+                Ok(token.to_string())
+            } else {
+                crypto.encrypt(token)
+            }
+        };
+
+        let conn = self.db.get()?;
+        let tx = conn.unchecked_transaction()?;
+        let rows = {
+            let mut stmt = tx.prepare("SELECT id, access_token, refresh_token FROM calendar_connections")?;
+            let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?)))?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+            rows
+        };
+
+        let mut updated = 0;
+        for (id, access, refresh) in rows {
+            let new_access = encrypt(&access)?;
+            let new_refresh = refresh.as_deref().map(|token| encrypt(token)).transpose()?;
+            if new_access != access || new_refresh != refresh {
+                tx.execute(
+                    "UPDATE calendar_connections SET access_token = ?1, refresh_token = ?2 WHERE id = ?3",
+                    (&new_access, &new_refresh, id),
+                )?;
+                updated += 1;
+            }
+        }
+        tx.commit()?;
+
+        Ok(updated)
+    }
+
+    // Move the key file into the OS keychain and keep using it from there. Stored
+    // tokens need no re-encryption since the key itself doesn't change.
+    pub fn migrate_key_to_keychain(&self) -> Result<()> {
+        crate::crypto::migrate_key_to_keychain(&self.data_dir)?;
+        self.db.get()?.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('encryption_key_storage', ?1)",
+            [KeyStorage::Keychain.as_str()],
+        )?;
+
+        let crypto = TokenEncryption::with_storage(&self.data_dir, KeyStorage::Keychain, true)?;
+        *self.crypto.write().unwrap_or_else(|e| e.into_inner()) = Some(crypto);
+        Ok(())
+    }
+
     // Google Calendar OAuth2 URL generation
     pub fn get_google_auth_url(&self, client_id: &str, redirect_uri: &str) -> String {
         let scope = "https://www.googleapis.com/auth/calendar.readonly";
@@ -40,21 +332,24 @@ impl CalendarService {
         )
     }
 
-    // Exchange authorization code for tokens
+    // Exchange authorization code for tokens. `code_verifier` is the PKCE verifier when
+    // the auth URL carried a code_challenge.
     pub async fn exchange_code_for_tokens(
         &self,
         code: &str,
         client_id: &str,
         client_secret: &str,
         redirect_uri: &str,
+        code_verifier: Option<&str>,
     ) -> Result<(String, Option<String>)> {
-        let params = [
+        let mut params = vec![
             ("code", code),
             ("client_id", client_id),
             ("client_secret", client_secret),
             ("redirect_uri", redirect_uri),
             ("grant_type", "authorization_code"),
         ];
+        params.extend(code_verifier.map(|verifier| ("code_verifier", verifier)));
 
         let response = self
             .http_client
@@ -63,16 +358,9 @@ impl CalendarService {
             .send()
             .await?;
 
-        let data: Value = response.json().await?;
-
-        let access_token = data["access_token"]
-            .as_str()
-            .ok_or_else(|| anyhow!("No access token in response"))?
-            .to_string();
-
-        let refresh_token = data["refresh_token"].as_str().map(|s| s.to_string());
-
-        Ok((access_token, refresh_token))
+        let status = response.status();
+        let body = response.text().await?;
+        parse_token_response(status, &body)
     }
 
     // Get user's Google Calendar account info
@@ -84,6 +372,11 @@ impl CalendarService {
             .send()
             .await?;
 
+        let status = response.status();
+        if !status.is_success() {
+            let body = response.text().await.unwrap_or_default();
+            return Err(AppError::Auth(format!("Google rejected the access token: {}", api_error_message(&body))).into());
+        }
         let data: Value = response.json().await?;
         let email = data["email"]
             .as_str()
@@ -92,16 +385,49 @@ impl CalendarService {
         Ok(email.to_string())
     }
 
+    // Try a provider's credentials with one small authenticated request, without
+    // saving anything. Returns the account name the connection would be saved under.
+    pub async fn test_connection(&self, provider: &str, params: &ConnectionParams) -> Result<String> {
+        let required = |value: &Option<String>, name: &str| -> Result<String> {
+            match value.as_deref().map(str::trim) {
+                Some(value) if !value.is_empty() => Ok(value.to_string()),
+                _ => Err(AppError::Validation(format!("{} is required for {} connections", name, provider)).into()),
+            }
+        };
+
+        match provider {
+            "google" => self.get_google_user_info(&required(&params.access_token, "An access token")?).await,
+            "caldav" => {
+                let url = required(&params.url, "A server URL")?;
+                let username = required(&params.username, "A username")?;
+                let account_name = caldav_account_name(&url, &username)?;
+
+                let response = self
+                    .http_client
+                    .request(reqwest::Method::from_bytes(b"PROPFIND")?, &url)
+                    .basic_auth(&username, params.password.as_deref())
+                    .header("Depth", "0")
+                    .header("Content-Type", "application/xml; charset=utf-8")
+                    .body(r#"<?xml version="1.0" encoding="utf-8"?><d:propfind xmlns:d="DAV:"><d:prop><d:current-user-principal/></d:prop></d:propfind>"#)
+                    .send()
+                    .await
+                    .map_err(|e| anyhow!("Couldn't reach {}: {}", url, e))?;
+                check_caldav_response(response.status(), &url)?;
+                Ok(account_name)
+            }
+            _ => Err(AppError::Validation(format!("Unsupported calendar provider: {}", provider)).into()),
+        }
+    }
+
     // Save calendar connection to database
     pub fn save_connection(&self, connection: &CalendarConnection) -> Result<i64> {
-        println!("🔥 CalendarService::save_connection called");
-        println!("🔥 Connection details: provider={}, account={}", 
-            connection.provider, connection.account_name);
+        debug!("Saving {} connection for {}", connection.provider, connection.account_name);
         
-        let conn = self.db.lock().unwrap();
+        let conn = self.db.get()?;
         
         // Encrypt tokens if encryption is available
-        let (encrypted_access_token, encrypted_refresh_token) = if let Some(ref crypto) = self.crypto {
+        let crypto = self.crypto();
+        let (encrypted_access_token, encrypted_refresh_token) = if let Some(crypto) = crypto.as_ref() {
             let access = crypto.encrypt(&connection.access_token)?;
             let refresh = connection.refresh_token.as_ref()
                 .map(|t| crypto.encrypt(t))
@@ -112,7 +438,6 @@ impl CalendarService {
         };
         
         let calendar_list_json = serde_json::to_string(&connection.calendar_list)?;
-        println!("🔥 Calendar list JSON: {}", calendar_list_json);
         
         let result = conn.execute(
             "INSERT INTO calendar_connections (provider, account_name, access_token, refresh_token, calendar_list, enabled)
@@ -128,26 +453,13 @@ impl CalendarService {
         );
 
         match result {
-            Ok(rows_affected) => {
+            Ok(_) => {
                 let id = conn.last_insert_rowid();
-                println!("🔥 Successfully saved connection to database! Rows affected: {}, ID: {}", rows_affected, id);
-                
-                // Verify it was actually saved
-                let verify: rusqlite::Result<i64> = conn.query_row(
-                    "SELECT COUNT(*) FROM calendar_connections WHERE id = ?1",
-                    [id],
-                    |row| row.get(0),
-                );
-                
-                match verify {
-                    Ok(count) => println!("🔥 Verification: Found {} connections with ID {}", count, id),
-                    Err(e) => println!("🔥 Verification failed: {}", e),
-                }
-                
+                info!("Saved {} connection {} for {}", connection.provider, id, connection.account_name);
                 Ok(id)
             }
             Err(e) => {
-                println!("🔥 ERROR saving connection to database: {}", e);
+                error!("Failed to save {} connection: {}", connection.provider, e);
                 Err(anyhow!("Failed to save connection: {}", e))
             }
         }
@@ -155,113 +467,234 @@ impl CalendarService {
 
     // Get all calendar connections
     pub fn get_connections(&self) -> Result<Vec<CalendarConnection>> {
-        println!("🔥 CalendarService::get_connections called");
-        let conn = self.db.lock().unwrap();
+        let crypto = self.crypto();
+        let conn = self.db.get()?;
         
-        // First check if there are ANY connections at all
-        let total_count: i64 = conn.query_row(
-            "SELECT COUNT(*) FROM calendar_connections",
-            [],
-            |row| row.get(0),
-        ).unwrap_or(0);
-        
-        println!("🔥 Total connections in database: {}", total_count);
-        
-        let mut stmt = conn.prepare(
-            "SELECT id, provider, account_name, access_token, refresh_token, calendar_list, last_sync, enabled, created_at 
-             FROM calendar_connections WHERE enabled = TRUE"
-        )?;
+        let sync_interval = crate::commands::read_setting_i32(&conn, "calendar_sync_interval", DEFAULT_SYNC_INTERVAL)?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM calendar_connections WHERE enabled = TRUE",
+            CALENDAR_CONNECTION_COLUMNS
+        ))?;
 
         let connections_iter = stmt.query_map([], |row| {
-            let calendar_list_str: String = row.get(5).unwrap_or_default();
-            let calendar_list: Vec<String> = if calendar_list_str.is_empty() {
-                Vec::new()
-            } else {
-                serde_json::from_str(&calendar_list_str).unwrap_or_default()
-            };
+            let mut connection = CalendarConnection::from_row(row)?;
 
-            let encrypted_access_token: String = row.get(3)?;
-            let encrypted_refresh_token: Option<String> = row.get(4)?;
-            
             // Decrypt tokens if encryption is available
-            let (access_token, refresh_token) = if let Some(ref crypto) = self.crypto {
-                let access = crypto.decrypt(&encrypted_access_token).unwrap_or(encrypted_access_token.clone());
-                let refresh = encrypted_refresh_token.as_ref()
+            if let Some(crypto) = crypto.as_ref() {
+                connection.access_token = crypto.decrypt(&connection.access_token)
+                    .unwrap_or(connection.access_token.clone());
+                connection.refresh_token = connection.refresh_token.as_ref()
                     .map(|t| crypto.decrypt(t).unwrap_or(t.clone()));
-                (access, refresh)
-            } else {
-                (encrypted_access_token, encrypted_refresh_token)
-            };
-
-            Ok(CalendarConnection {
-                id: Some(row.get(0)?),
-                provider: row.get(1)?,
-                account_name: row.get(2)?,
-                access_token,
-                refresh_token,
-                calendar_list,
-                last_sync: row.get(6)?,
-                enabled: row.get(7)?,
-                created_at: row.get(8)?,
-            })
+            }
+
+            connection.next_sync = connection.last_sync.as_deref()
+                .and_then(|last_sync| next_sync_time(last_sync, sync_interval));
+            Ok(connection)
         })?;
 
         let mut connections = Vec::new();
         for connection in connections_iter {
             match connection {
-                Ok(conn) => {
-                    println!("🔥 Found enabled connection: provider={}, account={}", 
-                        conn.provider, conn.account_name);
-                    connections.push(conn);
-                },
-                Err(e) => {
-                    println!("🔥 Error reading connection: {}", e);
-                }
+                Ok(conn) => connections.push(conn),
+                // One unreadable row shouldn't hide the other connections
+                Err(e) => warn!("Skipping unreadable calendar connection: {}", e),
             }
         }
 
-        println!("🔥 Returning {} enabled connections", connections.len());
+        debug!("Loaded {} enabled calendar connections", connections.len());
         Ok(connections)
     }
 
-    // Fetch events from Google Calendar
+    // Fetch a calendar's events, following pages. A window query is a full fetch;
+    // a changes query returns only what changed since the sync token was issued,
+    // including cancelled (deleted) events. Errors with SyncTokenExpired on 410.
     pub async fn fetch_google_events(
         &self,
         access_token: &str,
         calendar_id: &str,
-        time_min: &str,
-        time_max: &str,
-    ) -> Result<Vec<CalendarEvent>> {
+        query: EventQuery<'_>,
+    ) -> Result<EventChanges> {
         let url = format!(
-            "https://www.googleapis.com/calendar/v3/calendars/{}/events?timeMin={}&timeMax={}&singleEvents=true&orderBy=startTime",
-            urlencoding::encode(calendar_id),
-            urlencoding::encode(time_min),
-            urlencoding::encode(time_max)
+            "https://www.googleapis.com/calendar/v3/calendars/{}/events",
+            urlencoding::encode(calendar_id)
         );
+        // No orderBy: Google rejects it together with syncToken
+        let mut params = vec![("singleEvents", "true".to_string())];
+        let mut changes = EventChanges::default();
+        match query {
+            EventQuery::Window { time_min, time_max } => {
+                params.push(("timeMin", time_min.to_string()));
+                params.push(("timeMax", time_max.to_string()));
+                changes.window_end = Some(time_max.to_string());
+            }
+            EventQuery::Changes { sync_token } => params.push(("syncToken", sync_token.to_string())),
+        }
 
-        let response = self
-            .http_client
-            .get(&url)
-            .bearer_auth(access_token)
-            .send()
-            .await?;
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut page_params = params.clone();
+            if let Some(page_token) = &page_token {
+                page_params.push(("pageToken", page_token.clone()));
+            }
+            let data = self.fetch_events_page(access_token, &url, &page_params).await?;
+
+            for item in data["items"].as_array().into_iter().flatten() {
+                if item["status"].as_str() == Some("cancelled") {
+                    if let Some(external_id) = item["id"].as_str() {
+                        changes.cancelled.push(external_id.to_string());
+                    }
+                } else if let Ok(event) = self.parse_google_event(item, calendar_id, 0) {
+                    changes.events.push(event);
+                }
+            }
 
-        if !response.status().is_success() {
-            return Err(anyhow!("Failed to fetch calendar events: {}", response.status()));
+            // The sync token only comes with the last page
+            page_token = data["nextPageToken"].as_str().map(|token| token.to_string());
+            if page_token.is_none() {
+                changes.next_sync_token = data["nextSyncToken"].as_str().map(|token| token.to_string());
+                break;
+            }
         }
 
-        let data: Value = response.json().await?;
-        let empty_vec = vec![];
-        let items = data["items"].as_array().unwrap_or(&empty_vec);
+        Ok(changes)
+    }
 
-        let mut events = Vec::new();
-        for item in items {
-            if let Ok(event) = self.parse_google_event(item, calendar_id, 0) {
-                events.push(event);
+    // One events.list request, retrying rate limit errors with backoff
+    async fn fetch_events_page(&self, access_token: &str, url: &str, params: &[(&str, String)]) -> Result<Value> {
+        let max_retries = self.read_setting_i32("calendar_max_retries", DEFAULT_MAX_RETRIES).max(0) as u32;
+        let mut attempt = 0;
+        loop {
+            let response = self
+                .http_client
+                .get(url)
+                .query(params)
+                .bearer_auth(access_token)
+                .send()
+                .await?;
+
+            let status = response.status();
+            if status.is_success() {
+                return Ok(response.json().await?);
+            }
+            if status == StatusCode::GONE {
+                return Err(SyncTokenExpired.into());
             }
+
+            let retry_after = response.headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.parse().ok())
+                .map(Duration::from_secs);
+            let body = response.text().await.unwrap_or_default();
+
+            if !is_rate_limited(status, &body) {
+                return Err(anyhow!("Failed to fetch calendar events: HTTP {}: {}", status.as_u16(), api_error_message(&body)));
+            }
+            if attempt >= max_retries {
+                return Err(anyhow!(
+                    "Failed to fetch calendar events: rate limited after {} retries: {}",
+                    max_retries, api_error_message(&body)
+                ));
+            }
+
+            tokio::time::sleep(retry_delay(attempt, retry_after)).await;
+            attempt += 1;
         }
+    }
 
-        Ok(events)
+    // Incremental fetch when the calendar has a sync token, otherwise (or once
+    // Google expires the token) a full fetch of the window. Changes only cover the
+    // window the token was first issued for, so once that has fallen a day behind
+    // `time_max` the window is fetched again to pick up events coming into range.
+    async fn fetch_calendar_changes(
+        &self,
+        connection: &CalendarConnection,
+        calendar_id: &str,
+        time_min: &str,
+        time_max: &str,
+    ) -> Result<EventChanges> {
+        let connection_id = connection.id.unwrap_or(0);
+        let window_needed = chrono::DateTime::parse_from_rfc3339(time_max)
+            .map(|time_max| (time_max - chrono::Duration::days(1)).to_rfc3339_opts(chrono::SecondsFormat::Millis, true))?;
+        if let Some(sync_token) = self.sync_token(connection_id, calendar_id, &window_needed)? {
+            let query = EventQuery::Changes { sync_token: &sync_token };
+            match self.fetch_google_events(&connection.access_token, calendar_id, query).await {
+                Err(e) if e.is::<SyncTokenExpired>() => self.set_sync_token(connection_id, calendar_id, None, None)?,
+                result => return result,
+            }
+        }
+
+        let query = EventQuery::Window { time_min, time_max };
+        self.fetch_google_events(&connection.access_token, calendar_id, query).await
+    }
+
+    // The calendar's sync token, unless its window ends before `window_end`. Tokens
+    // saved before windows were tracked have none and count as expired.
+    fn sync_token(&self, connection_id: i64, calendar_id: &str, window_end: &str) -> Result<Option<String>> {
+        let conn = self.db.get()?;
+        let token = conn.query_row(
+            "SELECT sync_token FROM calendar_sync_tokens
+             WHERE connection_id = ?1 AND calendar_id = ?2 AND window_end >= ?3",
+            (connection_id, calendar_id, window_end),
+            |row| row.get(0),
+        ).optional()?;
+        Ok(token)
+    }
+
+    // A None `window_end` keeps the window the stored token was issued for
+    fn set_sync_token(&self, connection_id: i64, calendar_id: &str, sync_token: Option<&str>, window_end: Option<&str>) -> Result<()> {
+        let conn = self.db.get()?;
+        match sync_token {
+            Some(sync_token) => conn.execute(
+                "INSERT INTO calendar_sync_tokens (connection_id, calendar_id, sync_token, window_end) VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT(connection_id, calendar_id) DO UPDATE SET sync_token = excluded.sync_token,
+                     window_end = COALESCE(excluded.window_end, window_end)",
+                (connection_id, calendar_id, sync_token, window_end),
+            )?,
+            None => conn.execute(
+                "DELETE FROM calendar_sync_tokens WHERE connection_id = ?1 AND calendar_id = ?2",
+                (connection_id, calendar_id),
+            )?,
+        };
+        Ok(())
+    }
+
+    // Remove events Google reported as cancelled. Only the calendar's own copy goes;
+    // an event that has since moved to another calendar stays.
+    pub fn delete_cancelled_events(&self, connection_id: i64, calendar_id: &str, external_ids: &[String]) -> Result<usize> {
+        let conn = self.db.get()?;
+        let mut deleted = 0;
+        for external_id in external_ids {
+            deleted += conn.execute(
+                "DELETE FROM calendar_events WHERE connection_id = ?1 AND calendar_id = ?2 AND external_id = ?3",
+                (connection_id, calendar_id, external_id),
+            )?;
+        }
+        Ok(deleted)
+    }
+
+    // Delete a connection's events that ended more than calendar_event_retention_days
+    // ago (0 keeps them all). Blocks imported from events are copies, so they stay.
+    pub fn purge_old_events(&self, connection_id: i64) -> Result<usize> {
+        let retention_days = self.read_setting_i32("calendar_event_retention_days", DEFAULT_EVENT_RETENTION_DAYS);
+        if retention_days <= 0 {
+            return Ok(0);
+        }
+        let conn = self.db.get()?;
+        let purged = conn.execute(
+            "DELETE FROM calendar_events WHERE connection_id = ?1 AND datetime(end_time) < datetime('now', ?2)",
+            (connection_id, format!("-{} days", retention_days)),
+        )?;
+        Ok(purged)
+    }
+
+    // Purge old events for every connection, returning how many were deleted
+    pub fn purge_all_old_events(&self) -> Result<usize> {
+        let mut purged = 0;
+        for connection in self.get_connections()? {
+            purged += self.purge_old_events(connection.id.unwrap_or(0))?;
+        }
+        Ok(purged)
     }
 
     // Parse Google Calendar event JSON into our CalendarEvent struct
@@ -297,17 +730,24 @@ impl CalendarService {
         let description = item["description"].as_str().map(|s| s.to_string());
         let location = item["location"].as_str().map(|s| s.to_string());
         
-        let attendees: Vec<String> = item["attendees"]
+        let attendees: Vec<Attendee> = item["attendees"]
             .as_array()
             .map(|attendees| {
                 attendees
                     .iter()
-                    .filter_map(|a| a["email"].as_str())
-                    .map(|s| s.to_string())
+                    .filter_map(|a| {
+                        Some(Attendee {
+                            email: a["email"].as_str()?.to_string(),
+                            name: a["displayName"].as_str().map(|s| s.to_string()),
+                            response_status: a["responseStatus"].as_str().map(|s| s.to_string()),
+                        })
+                    })
                     .collect()
             })
             .unwrap_or_default();
 
+        let color_id = item["colorId"].as_str().map(|s| s.to_string());
+
         let last_updated = item["updated"]
             .as_str()
             .unwrap_or("")
@@ -326,20 +766,29 @@ impl CalendarService {
             is_all_day,
             attendees,
             last_updated,
+            color_id,
         })
     }
 
-    // Save events to database (upsert)
+    // Upsert events by (connection_id, external_id). The same event can show up under
+    // several calendars of one connection, e.g. after calendars are reorganized, and
+    // those fetches finish in any order; the most recently updated copy wins.
     pub fn save_events(&self, events: &[CalendarEvent]) -> Result<()> {
-        let conn = self.db.lock().unwrap();
+        let conn = self.db.get()?;
         
         for event in events {
             let attendees_json = serde_json::to_string(&event.attendees)?;
             
             conn.execute(
-                "INSERT OR REPLACE INTO calendar_events 
-                 (connection_id, external_id, calendar_id, title, start_time, end_time, description, location, is_all_day, attendees, last_updated)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                "INSERT INTO calendar_events 
+                 (connection_id, external_id, calendar_id, title, start_time, end_time, description, location, is_all_day, attendees, last_updated, color_id)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
+                 ON CONFLICT(connection_id, external_id) DO UPDATE SET
+                    calendar_id = excluded.calendar_id, title = excluded.title, start_time = excluded.start_time,
+                    end_time = excluded.end_time, description = excluded.description, location = excluded.location,
+                    is_all_day = excluded.is_all_day, attendees = excluded.attendees, last_updated = excluded.last_updated,
+                    color_id = excluded.color_id
+                 WHERE excluded.last_updated >= calendar_events.last_updated",
                 (
                     event.connection_id,
                     &event.external_id,
@@ -352,6 +801,7 @@ impl CalendarService {
                     event.is_all_day,
                     attendees_json,
                     &event.last_updated,
+                    &event.color_id,
                 ),
             )?;
         }
@@ -360,109 +810,498 @@ impl CalendarService {
     }
 
     // Get events for a specific date range
+    // Events overlapping the inclusive range of local dates, including ones that start
+    // before it (overnight and multi-day events). Stored times carry mixed offsets, so
+    // the query takes a day of slack each side and event_overlaps settles it.
     pub fn get_events_for_date_range(&self, start_date: &str, end_date: &str) -> Result<Vec<CalendarEvent>> {
-        let conn = self.db.lock().unwrap();
-        let mut stmt = conn.prepare(
-            "SELECT id, connection_id, external_id, calendar_id, title, start_time, end_time, description, location, is_all_day, attendees, last_updated
-             FROM calendar_events 
-             WHERE date(start_time) >= ?1 AND date(start_time) <= ?2
-             ORDER BY start_time"
-        )?;
+        let parse = |date: &str| chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map_err(|_| AppError::Validation(format!("Invalid date: {}", date)));
+        let (from, to) = (parse(start_date)?, parse(end_date)?);
+        let window_start = (from - chrono::Duration::days(1)).format("%Y-%m-%d").to_string();
+        let window_end = (to + chrono::Duration::days(2)).format("%Y-%m-%d").to_string();
 
-        let events_iter = stmt.query_map([start_date, end_date], |row| {
-            let attendees_str: String = row.get(10).unwrap_or_default();
-            let attendees: Vec<String> = if attendees_str.is_empty() {
-                Vec::new()
-            } else {
-                serde_json::from_str(&attendees_str).unwrap_or_default()
-            };
-
-            Ok(CalendarEvent {
-                id: Some(row.get(0)?),
-                connection_id: row.get(1)?,
-                external_id: row.get(2)?,
-                calendar_id: row.get(3)?,
-                title: row.get(4)?,
-                start_time: row.get(5)?,
-                end_time: row.get(6)?,
-                description: row.get(7)?,
-                location: row.get(8)?,
-                is_all_day: row.get(9)?,
-                attendees,
-                last_updated: row.get(11)?,
-            })
-        })?;
+        let conn = self.db.get()?;
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM calendar_events
+             WHERE start_time < ?2 AND end_time > ?1
+             ORDER BY start_time",
+            CALENDAR_EVENT_COLUMNS
+        ))?;
+
+        let events_iter = stmt.query_map([&window_start, &window_end], CalendarEvent::from_row)?;
 
         let mut events = Vec::new();
         for event in events_iter {
-            events.push(event?);
+            let event = event?;
+            if crate::scheduling::event_overlaps(&event, from, to) {
+                events.push(event);
+            }
         }
 
         Ok(events)
     }
 
-    // Sync all calendar connections
+    // Numeric setting, falling back to `default` if it can't be read
+    fn read_setting_i32(&self, key: &str, default: i32) -> i32 {
+        self.db.get()
+            .ok()
+            .and_then(|conn| crate::commands::read_setting_i32(&conn, key, default).ok())
+            .unwrap_or(default)
+    }
+
+    // Ask Google whether the connection's access token still works. Network
+    // failures are errors rather than a status, since they say nothing about the token.
+    pub async fn check_token(&self, connection: &CalendarConnection) -> Result<(ConnectionStatus, Option<String>)> {
+        let response = self
+            .http_client
+            .get("https://oauth2.googleapis.com/tokeninfo")
+            .query(&[("access_token", &connection.access_token)])
+            .send()
+            .await?;
+
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        Ok(classify_token_check(status, &body, connection.refresh_token.is_some()))
+    }
+
+    fn record_status(&self, connection_id: i64, status: ConnectionStatus, last_error: Option<&str>) -> Result<()> {
+        let conn = self.db.get()?;
+        conn.execute(
+            "UPDATE calendar_connections SET status = ?1, last_error = ?2 WHERE id = ?3",
+            (status.as_str(), last_error, connection_id),
+        )?;
+        Ok(())
+    }
+
+    pub async fn validate_connection(&self, connection_id: i64) -> Result<ConnectionStatus> {
+        let connection = self.get_connections()?
+            .into_iter()
+            .find(|connection| connection.id == Some(connection_id))
+            .ok_or_else(|| anyhow!("Calendar connection not found: {}", connection_id))?;
+
+        let (status, last_error) = self.check_token(&connection).await?;
+        self.record_status(connection_id, status, last_error.as_deref())?;
+        Ok(status)
+    }
+
+    // None if a sync is already running
+    fn begin_sync(&self) -> Option<SyncGuard<'_>> {
+        self.syncing.compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .ok()
+            .map(|_| SyncGuard(&self.syncing))
+    }
+
+    // Sync every calendar connection and report how each went. Connections whose
+    // token no longer works are skipped and marked so the UI can ask the user to
+    // reconnect. Only one sync runs at a time; a second call fails with AppError::Busy.
+    pub async fn sync_calendars_detailed(&self) -> Result<Vec<ConnectionSyncResult>> {
+        let _guard = self.begin_sync()
+            .ok_or_else(|| AppError::Busy("A calendar sync is already in progress".to_string()))?;
+
+        let mut results = Vec::new();
+        for connection in self.get_connections()? {
+            let started = Instant::now();
+            let outcome = self.sync_account(&connection).await;
+            let (synced, deleted) = outcome.as_ref().map_or((0, 0), |counts| *counts);
+            results.push(ConnectionSyncResult {
+                account_name: connection.account_name,
+                provider: connection.provider,
+                synced,
+                deleted,
+                error: outcome.err().map(|e| e.to_string()),
+                duration_ms: started.elapsed().as_millis() as u64,
+            });
+        }
+        Ok(results)
+    }
+
+    // Sync all calendar connections, returning the number of events saved. Fails only
+    // if every connection failed, so a partial sync still reports what it saved.
     pub async fn sync_all_calendars(&self) -> Result<i32> {
-        let connections = self.get_connections()?;
-        let mut total_synced = 0;
-
-        for connection in connections {
-            match self.sync_connection(&connection).await {
-                Ok(count) => {
-                    total_synced += count;
-                    // Update last sync time
-                    let conn = self.db.lock().unwrap();
+        let results = self.sync_calendars_detailed().await?;
+        let failures: Vec<String> = results.iter()
+            .filter_map(|result| result.error.as_ref().map(|e| format!("{}: {}", result.account_name, e)))
+            .collect();
+
+        if !failures.is_empty() && failures.len() == results.len() {
+            return Err(anyhow!(failures.join("; ")));
+        }
+        Ok(results.iter().map(|result| result.synced).sum())
+    }
+
+    // Check one connection's token, then sync it and purge its old events.
+    // Returns the events saved and deleted.
+    async fn sync_account(&self, connection: &CalendarConnection) -> Result<(i32, usize)> {
+        let connection_id = connection.id.unwrap_or(0);
+
+        let checked = match connection.status {
+            ConnectionStatus::Revoked => Ok((ConnectionStatus::Revoked, connection.last_error.clone())),
+            _ => self.check_token(connection).await,
+        };
+        match checked {
+            Ok((ConnectionStatus::Valid, _)) => {}
+            Ok((status, last_error)) => {
+                let reason = last_error.unwrap_or_else(|| "reconnect needed".to_string());
+                if let Err(e) = self.record_status(connection_id, status, Some(&reason)) {
+                    error!("Failed to record status for {}: {}", connection.account_name, e);
+                }
+                return Err(anyhow!("{} ({})", reason, status.as_str()));
+            }
+            // Couldn't reach Google to check; let the sync itself report the problem
+            Err(e) => error!("Failed to validate token for {}: {}", connection.account_name, e),
+        }
+
+        match self.sync_connection(connection).await {
+            Ok((synced, mut deleted)) => {
+                if let Ok(conn) = self.db.get() {
                     let _ = conn.execute(
-                        "UPDATE calendar_connections SET last_sync = CURRENT_TIMESTAMP WHERE id = ?1",
-                        [connection.id.unwrap_or(0)],
+                        "UPDATE calendar_connections SET last_sync = CURRENT_TIMESTAMP, status = 'valid', last_error = NULL WHERE id = ?1",
+                        [connection_id],
                     );
                 }
-                Err(e) => {
-                    eprintln!("Failed to sync calendar for {}: {}", connection.account_name, e);
+                match self.purge_old_events(connection_id) {
+                    Ok(0) => {}
+                    Ok(purged) => {
+                        info!("Purged {} old events for {}", purged, connection.account_name);
+                        deleted += purged;
+                    }
+                    Err(e) => warn!("Failed to purge old events for {}: {}", connection.account_name, e),
                 }
+                Ok((synced, deleted))
+            }
+            Err(e) => {
+                error!("Failed to sync calendar for {}: {}", connection.account_name, e);
+                if let Ok(conn) = self.db.get() {
+                    let _ = conn.execute(
+                        "UPDATE calendar_connections SET last_error = ?1 WHERE id = ?2",
+                        (e.to_string(), connection_id),
+                    );
+                }
+                Err(e)
             }
         }
-
-        Ok(total_synced)
     }
 
-    // Sync a single calendar connection
-    async fn sync_connection(&self, connection: &CalendarConnection) -> Result<i32> {
+    // Sync a single calendar connection. Returns the events saved and cancelled ones removed.
+    async fn sync_connection(&self, connection: &CalendarConnection) -> Result<(i32, usize)> {
         if connection.provider != "google" {
             return Err(anyhow!("Only Google Calendar is supported for now"));
         }
 
-        // Sync events for the next 30 days
+        // The first sync of a calendar fetches the next 30 days; later ones
+        // fetch only changes since then, refetching the window as it rolls forward
         let now = chrono::Utc::now();
         let time_min = now.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
-        let time_max = (now + chrono::Duration::days(30))
+        let time_max = (now + chrono::Duration::days(SYNC_WINDOW_DAYS))
             .format("%Y-%m-%dT%H:%M:%S%.3fZ")
             .to_string();
 
         let mut total_events = 0;
+        let mut total_deleted = 0;
+        let mut failures = Vec::new();
 
-        for calendar_id in &connection.calendar_list {
-            match self.fetch_google_events(
-                &connection.access_token,
-                calendar_id,
-                &time_min,
-                &time_max,
-            ).await {
-                Ok(mut events) => {
+        // Fetch a few calendars at a time so large accounts don't trip rate limits
+        let parallelism = self.read_setting_i32("calendar_sync_parallelism", DEFAULT_SYNC_PARALLELISM).max(1) as usize;
+        let (time_min, time_max) = (&time_min, &time_max);
+        let fetches: Vec<_> = stream::iter(&connection.calendar_list)
+            .map(|calendar_id| async move {
+                let result = self.fetch_calendar_changes(connection, calendar_id, time_min, time_max).await;
+                (calendar_id, result)
+            })
+            .buffer_unordered(parallelism)
+            .collect()
+            .await;
+
+        let connection_id = connection.id.unwrap_or(0);
+        for (calendar_id, result) in fetches {
+            match result {
+                Ok(mut changes) => {
                     // Set the connection_id for all events
-                    for event in &mut events {
-                        event.connection_id = connection.id.unwrap_or(0);
+                    for event in &mut changes.events {
+                        event.connection_id = connection_id;
                     }
                     
-                    self.save_events(&events)?;
-                    total_events += events.len();
+                    self.save_events(&changes.events)?;
+                    total_deleted += self.delete_cancelled_events(connection_id, calendar_id, &changes.cancelled)?;
+                    // Only stored once the changes are, so a failed save refetches them
+                    if let Some(sync_token) = &changes.next_sync_token {
+                        self.set_sync_token(connection_id, calendar_id, Some(sync_token), changes.window_end.as_deref())?;
+                    }
+                    total_events += changes.events.len();
                 }
                 Err(e) => {
-                    eprintln!("Failed to fetch events from calendar {}: {}", calendar_id, e);
+                    error!("Failed to fetch events from calendar {}: {}", calendar_id, e);
+                    failures.push(format!("{}: {}", calendar_id, e));
                 }
             }
         }
 
-        Ok(total_events as i32)
+        // Report why nothing came back instead of an empty sync
+        if !failures.is_empty() && failures.len() == connection.calendar_list.len() {
+            return Err(anyhow!(failures.join("; ")));
+        }
+        Ok((total_events as i32, total_deleted))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(calendar_id: &str, title: &str, last_updated: &str) -> CalendarEvent {
+        CalendarEvent {
+            id: None,
+            connection_id: 1,
+            external_id: "evt1".to_string(),
+            calendar_id: calendar_id.to_string(),
+            title: title.to_string(),
+            start_time: "2025-06-01T09:00:00Z".to_string(),
+            end_time: "2025-06-01T10:00:00Z".to_string(),
+            description: None,
+            location: None,
+            is_all_day: false,
+            attendees: vec![],
+            last_updated: last_updated.to_string(),
+            color_id: None,
+        }
+    }
+
+    fn setup() -> (CalendarService, DbPool, tempfile::TempDir) {
+        let dir = tempfile::tempdir().unwrap();
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .build(r2d2_sqlite::SqliteConnectionManager::memory())
+            .unwrap();
+        crate::init_database(&pool.get().unwrap()).unwrap();
+        let service = CalendarService::new(pool.clone(), dir.path().to_path_buf());
+        (service, pool, dir)
+    }
+
+    fn stored_events(pool: &DbPool) -> Vec<(String, String)> {
+        let conn = pool.get().unwrap();
+        let mut stmt = conn.prepare("SELECT calendar_id, title FROM calendar_events").unwrap();
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap();
+        rows.map(|row| row.unwrap()).collect()
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_save_events_keeps_newest_copy_across_calendars() {
+        let (service, pool, _dir) = setup();
+
+        service.save_events(&[event("work", "Standup", "2025-06-01T08:00:00Z")]).unwrap();
+        // An older copy from another calendar doesn't overwrite it
+        service.save_events(&[event("personal", "Old standup", "2025-05-30T08:00:00Z")]).unwrap();
+
+        assert_eq!(stored_events(&pool), vec![("work".to_string(), "Standup".to_string())]);
+
+        service.save_events(&[event("personal", "Moved standup", "2025-06-02T08:00:00Z")]).unwrap();
+        assert_eq!(stored_events(&pool), vec![("personal".to_string(), "Moved standup".to_string())]);
+    }
+
+    #[test]
+    fn test_date_range_includes_events_started_earlier() {
+        let (service, _pool, _dir) = setup();
+
+        let mut overnight = event("work", "Night shift", "2025-06-01T08:00:00Z");
+        overnight.start_time = "2025-06-01T22:00:00".to_string();
+        overnight.end_time = "2025-06-02T07:00:00".to_string();
+        let mut trip = event("work", "Conference", "2025-06-01T08:00:00Z");
+        trip.external_id = "evt2".to_string();
+        trip.is_all_day = true;
+        trip.start_time = "2025-05-31T00:00:00".to_string();
+        trip.end_time = "2025-06-03T23:59:59".to_string();
+        service.save_events(&[overnight, trip]).unwrap();
+
+        let titles = |from: &str, to: &str| -> Vec<String> {
+            service.get_events_for_date_range(from, to).unwrap().into_iter().map(|event| event.title).collect()
+        };
+        assert_eq!(titles("2025-06-02", "2025-06-02"), vec!["Conference", "Night shift"]);
+        assert_eq!(titles("2025-06-03", "2025-06-04"), Vec::<String>::new());
+        assert!(service.get_events_for_date_range("June 2", "2025-06-02").is_err());
+    }
+
+    #[test]
+    fn test_sync_tokens_and_cancelled_events() {
+        let (service, pool, _dir) = setup();
+        pool.get().unwrap().execute(
+            "INSERT INTO calendar_connections (id, provider, account_name, access_token) VALUES (1, 'google', 'me', 'token')",
+            [],
+        ).unwrap();
+
+        let window_end = "2025-06-30T00:00:00.000Z";
+        assert_eq!(service.sync_token(1, "work", window_end).unwrap(), None);
+        service.set_sync_token(1, "work", Some("abc"), Some(window_end)).unwrap();
+        // Incremental syncs keep the window the token was issued for
+        service.set_sync_token(1, "work", Some("def"), None).unwrap();
+        assert_eq!(service.sync_token(1, "work", window_end).unwrap().as_deref(), Some("def"));
+        // A window that no longer reaches far enough needs a full fetch
+        assert_eq!(service.sync_token(1, "work", "2025-07-01T00:00:00.000Z").unwrap(), None);
+        service.set_sync_token(1, "work", None, None).unwrap();
+        assert_eq!(service.sync_token(1, "work", window_end).unwrap(), None);
+
+        // Tokens saved before windows were tracked are refetched
+        service.set_sync_token(1, "work", Some("old"), None).unwrap();
+        assert_eq!(service.sync_token(1, "work", window_end).unwrap(), None);
+
+        service.save_events(&[event("work", "Standup", "2025-06-01T08:00:00Z")]).unwrap();
+        let cancelled = vec!["evt1".to_string()];
+        // Cancelled under another calendar leaves this calendar's copy alone
+        assert_eq!(service.delete_cancelled_events(1, "personal", &cancelled).unwrap(), 0);
+        assert_eq!(service.delete_cancelled_events(1, "work", &cancelled).unwrap(), 1);
+        assert!(stored_events(&pool).is_empty());
+    }
+
+    #[test]
+    fn test_purge_old_events_per_connection() {
+        let (service, pool, _dir) = setup();
+        {
+            let conn = pool.get().unwrap();
+            conn.execute_batch(
+                "INSERT INTO calendar_connections (id, provider, account_name, access_token) VALUES
+                     (1, 'google', 'me', 'token'), (2, 'google', 'work', 'token');
+                 UPDATE settings SET value = '30' WHERE key = 'calendar_event_retention_days';"
+            ).unwrap();
+            let recent = (chrono::Utc::now() - chrono::Duration::days(1)).format("%Y-%m-%dT%H:%M:%SZ").to_string();
+            for (connection_id, external_id, end_time) in [
+                (1, "old", "2020-01-01T10:00:00Z"),
+                (1, "all-day", "2020-01-02"),
+                (1, "recent", recent.as_str()),
+                (2, "other", "2020-01-01T10:00:00Z"),
+            ] {
+                conn.execute(
+                    "INSERT INTO calendar_events (connection_id, external_id, calendar_id, title, start_time, end_time, last_updated)
+                     VALUES (?1, ?2, 'primary', ?2, ?3, ?3, ?3)",
+                    (connection_id, external_id, end_time),
+                ).unwrap();
+            }
+        }
+
+        assert_eq!(service.purge_old_events(1).unwrap(), 2);
+        let remaining: Vec<String> = pool.get().unwrap()
+            .prepare("SELECT external_id FROM calendar_events ORDER BY external_id").unwrap()
+            .query_map([], |row| row.get(0)).unwrap()
+            .collect::<rusqlite::Result<_>>().unwrap();
+        assert_eq!(remaining, vec!["other", "recent"]);
+
+        pool.get().unwrap().execute("UPDATE settings SET value = '0' WHERE key = 'calendar_event_retention_days'", []).unwrap();
+        assert_eq!(service.purge_old_events(2).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_caldav_connection_checks() {
+        assert_eq!(caldav_account_name("https://dav.example.com/cal/me/", "me").unwrap(), "me@dav.example.com");
+        assert!(caldav_account_name("dav.example.com", "me").is_err());
+        assert!(caldav_account_name("ftp://dav.example.com", "me").is_err());
+
+        assert!(check_caldav_response(StatusCode::MULTI_STATUS, "u").is_ok());
+        let rejected = check_caldav_response(StatusCode::UNAUTHORIZED, "u").unwrap_err();
+        assert_eq!(AppError::from(rejected).code(), "auth");
+        let missing = check_caldav_response(StatusCode::NOT_FOUND, "u").unwrap_err();
+        assert_eq!(AppError::from(missing).code(), "validation");
+        assert!(check_caldav_response(StatusCode::INTERNAL_SERVER_ERROR, "u").is_err());
+    }
+
+    #[test]
+    fn test_detailed_sync_reports_each_connection() {
+        let (service, pool, _dir) = setup();
+        // Revoked connections are reported without contacting Google
+        pool.get().unwrap().execute(
+            "INSERT INTO calendar_connections (provider, account_name, access_token, status, last_error)
+             VALUES ('google', 'me@example.com', 'token', 'revoked', 'Token has been revoked')",
+            [],
+        ).unwrap();
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+
+        let results = runtime.block_on(service.sync_calendars_detailed()).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!((results[0].account_name.as_str(), results[0].provider.as_str()), ("me@example.com", "google"));
+        assert_eq!((results[0].synced, results[0].deleted), (0, 0));
+        assert_eq!(results[0].error.as_deref(), Some("Token has been revoked (revoked)"));
+
+        let error = runtime.block_on(service.sync_all_calendars()).unwrap_err();
+        assert!(error.to_string().starts_with("me@example.com: Token has been revoked"));
+    }
+
+    #[test]
+    fn test_next_sync_time() {
+        assert_eq!(next_sync_time("2025-06-01T09:58:00Z", 5).as_deref(), Some("2025-06-01T10:03:00Z"));
+        assert_eq!(next_sync_time("not a time", 5), None);
+    }
+
+    #[test]
+    fn test_classify_token_check() {
+        assert_eq!(classify_token_check(StatusCode::OK, r#"{"expires_in": "3000"}"#, false), (ConnectionStatus::Valid, None));
+
+        let invalid = r#"{"error": "invalid_token", "error_description": "Invalid Value"}"#;
+        assert_eq!(
+            classify_token_check(StatusCode::BAD_REQUEST, invalid, true),
+            (ConnectionStatus::NeedsRefresh, Some("Invalid Value".to_string()))
+        );
+        assert_eq!(
+            classify_token_check(StatusCode::BAD_REQUEST, invalid, false),
+            (ConnectionStatus::Revoked, Some("Invalid Value".to_string()))
+        );
+        assert_eq!(
+            classify_token_check(StatusCode::BAD_REQUEST, "", false).1.as_deref(),
+            Some("Token check failed with HTTP 400")
+        );
+    }
+
+    #[test]
+    fn test_is_rate_limited() {
+        assert!(is_rate_limited(StatusCode::TOO_MANY_REQUESTS, ""));
+        assert!(is_rate_limited(
+            StatusCode::FORBIDDEN,
+            r#"{"error": {"errors": [{"reason": "rateLimitExceeded"}], "code": 403, "message": "Rate Limit Exceeded"}}"#,
+        ));
+        assert!(!is_rate_limited(
+            StatusCode::FORBIDDEN,
+            r#"{"error": {"errors": [{"reason": "forbidden"}], "code": 403, "message": "Forbidden"}}"#,
+        ));
+        assert!(!is_rate_limited(StatusCode::INTERNAL_SERVER_ERROR, ""));
+    }
+
+    #[test]
+    fn test_retry_delay_backs_off_with_cap() {
+        for attempt in 0..8 {
+            let base = Duration::from_secs(1u64 << attempt.min(5)).min(MAX_RETRY_DELAY);
+            let delay = retry_delay(attempt, None);
+            assert!(delay >= base && delay <= base * 2);
+        }
+        assert_eq!(retry_delay(0, Some(Duration::from_secs(120))), Duration::from_secs(120));
+    }
+
+    #[test]
+    fn test_parse_token_response() {
+        let (access, refresh) = parse_token_response(
+            StatusCode::OK,
+            r#"{"access_token": "ya29.token", "refresh_token": "1//refresh", "expires_in": 3599}"#,
+        ).unwrap();
+        assert_eq!(access, "ya29.token");
+        assert_eq!(refresh.as_deref(), Some("1//refresh"));
+
+        let err = parse_token_response(
+            StatusCode::BAD_REQUEST,
+            r#"{"error": "invalid_grant", "error_description": "code already redeemed"}"#,
+        ).unwrap_err();
+        assert_eq!(err.to_string(), "invalid_grant: code already redeemed");
+
+        let err = parse_token_response(StatusCode::BAD_GATEWAY, "<html>Bad Gateway</html>").unwrap_err();
+        assert_eq!(err.to_string(), "Token request failed with HTTP 502: <html>Bad Gateway</html>");
+    }
+
+    #[test]
+    fn test_only_one_sync_at_a_time() {
+        let (service, _pool, _dir) = setup();
+
+        let running = service.begin_sync().unwrap();
+        assert!(service.begin_sync().is_none());
+        let error = tokio::runtime::Runtime::new().unwrap()
+            .block_on(service.sync_all_calendars())
+            .unwrap_err();
+        assert_eq!(AppError::from(error).code(), "busy");
+
+        drop(running);
+        assert!(service.begin_sync().is_some());
+    }
+}
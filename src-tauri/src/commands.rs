@@ -1,268 +1,3844 @@
-use tauri::State;
-use crate::{AppState, models::*};
+use chrono::Datelike;
+use rusqlite::{Connection, OptionalExtension, Row};
+use tauri::{AppHandle, Manager, State};
+use log::{error, warn};
+use crate::{db_conn, AppState, models::*};
+use crate::error::{AppError, AppResult};
+use crate::search::{AttachmentDocument, HighlightOptions, NoteSectionDocument, SearchSort};
+use crate::undo::{LoggedOperation, UndoResult};
+
+pub fn load_time_blocks(conn: &Connection, date: &str) -> rusqlite::Result<Vec<TimeBlock>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM time_blocks WHERE date = ?1 ORDER BY start_minutes",
+        TIME_BLOCK_COLUMNS
+    ))?;
+    
+    let blocks = stmt.query_map([date], TimeBlock::from_row)?
+        .collect::<rusqlite::Result<Vec<TimeBlock>>>();
+    blocks
+}
+
+// Inclusive date range, ordered by date then start time
+pub fn load_time_blocks_between(conn: &Connection, date_from: &str, date_to: &str) -> rusqlite::Result<Vec<TimeBlock>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM time_blocks WHERE date >= ?1 AND date <= ?2 ORDER BY date, start_minutes",
+        TIME_BLOCK_COLUMNS
+    ))?;
+    
+    let blocks = stmt.query_map([date_from, date_to], TimeBlock::from_row)?
+        .collect::<rusqlite::Result<Vec<TimeBlock>>>();
+    blocks
+}
+
+pub fn load_time_block(conn: &Connection, id: i64) -> rusqlite::Result<Option<TimeBlock>> {
+    conn.query_row(
+        &format!("SELECT {} FROM time_blocks WHERE id = ?1", TIME_BLOCK_COLUMNS),
+        [id],
+        TimeBlock::from_row
+    ).optional()
+}
+
+pub fn load_priorities(conn: &Connection, date: &str) -> rusqlite::Result<Vec<Priority>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, date, content, completed, priority_order FROM priorities 
+         WHERE date = ?1 ORDER BY priority_order"
+    )?;
+    
+    let priorities = stmt.query_map([date], |row| {
+        Ok(Priority {
+            id: Some(row.get(0)?),
+            date: row.get(1)?,
+            content: row.get(2)?,
+            completed: row.get(3)?,
+            priority_order: row.get(4).unwrap_or(0),
+            created_at: None,
+        })
+    })?.collect::<rusqlite::Result<Vec<Priority>>>();
+    priorities
+}
+
+// Dates are stored as YYYY-MM-DD text so they sort and range-compare correctly.
+// Accepts unpadded parts and '/' or '.' separators ("2025/6/1"); rejects dates that
+// don't exist, like 2025-02-30.
+pub fn normalize_date(input: &str) -> AppResult<String> {
+    let unified: String = input.trim().chars()
+        .map(|c| if c == '/' || c == '.' { '-' } else { c })
+        .collect();
+    match chrono::NaiveDate::parse_from_str(&unified, "%Y-%m-%d") {
+        Ok(day) if (1000..=9999).contains(&day.year()) => Ok(day.format("%Y-%m-%d").to_string()),
+        _ => Err(AppError::Validation(format!("Invalid date: {}", input))),
+    }
+}
 
 #[tauri::command]
-pub fn save_time_block(block: TimeBlock, notes_content: Option<String>, state: State<AppState>) -> Result<i64, String> {
-    let conn = state.db.lock().unwrap();
-    let tags_json = serde_json::to_string(&block.tags).unwrap_or_default();
+pub fn save_time_block(
+    block: TimeBlock,
+    notes_content: Option<String>,
+    app: AppHandle,
+    state: State<AppState>
+) -> AppResult<i64> {
+    let conn = db_conn(&state.db)?;
+    let tx = conn.unchecked_transaction()?;
+    let saved = write_time_block(&tx, &state.files, &block, notes_content.as_deref())?;
+    tx.commit()?;
     
-    let block_id = if let Some(id) = block.id {
-        // Update existing
-        conn.execute(
-            "UPDATE time_blocks SET start_minutes = ?1, duration_minutes = ?2, title = ?3, 
-             notes_file = ?4, color = ?5, tags = ?6, updated_at = CURRENT_TIMESTAMP
-             WHERE id = ?7",
-            (block.start_minutes, block.duration_minutes, &block.title, 
-             &block.notes_file, &block.color, tags_json, id),
-        ).map_err(|e| e.to_string())?;
-        id
-    } else {
-        // Insert new
-        conn.execute(
-            "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title, notes_file, color, tags)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
-            (&block.date, block.start_minutes, block.duration_minutes, 
-             &block.title, &block.notes_file, &block.color, tags_json),
-        ).map_err(|e| e.to_string())?;
-        conn.last_insert_rowid()
-    };
+    // Index for search
+    if let Some(content) = &notes_content {
+        if let Err(e) = state.search.index_time_block(&saved, content) {
+            error!("Failed to index time block: {}", e);
+        }
+    }
     
-    // Save notes file if provided
-    if let Some(content) = notes_content {
-        let mut updated_block = block.clone();
-        updated_block.id = Some(block_id);
-        let notes_path = state.files.save_notes(&updated_block, &content)
-            .map_err(|e| e.to_string())?;
-        
-        // Update notes_file path in database
-        conn.execute(
-            "UPDATE time_blocks SET notes_file = ?1 WHERE id = ?2",
-            (notes_path, block_id),
-        ).map_err(|e| e.to_string())?;
+    app.trigger_global("blocks-changed", None);
+    Ok(saved.id.unwrap_or_default())
+}
+
+// Save many blocks in one transaction with a single search commit, e.g. when
+// importing a schedule. `notes` pairs with `blocks` by position and may be shorter.
+// With `dedupe`, a new block matching an existing one's date, start and title updates
+// it instead, so re-running an import doesn't pile up duplicates.
+#[tauri::command]
+pub fn save_time_blocks(
+    blocks: Vec<TimeBlock>,
+    notes: Vec<Option<String>>,
+    dedupe: Option<bool>,
+    app: AppHandle,
+    state: State<AppState>
+) -> AppResult<BatchSaveResult> {
+    if notes.len() > blocks.len() {
+        return Err(AppError::Validation(format!("Got notes for {} blocks but only {} blocks", notes.len(), blocks.len())));
+    }
+    
+    let conn = db_conn(&state.db)?;
+    let tx = conn.unchecked_transaction()?;
+    
+    let mut result = BatchSaveResult { ids: Vec::with_capacity(blocks.len()), inserted: 0, updated: 0 };
+    let mut to_index = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let mut block = block.clone();
+        if block.id.is_none() && dedupe.unwrap_or(false) {
+            block.id = find_matching_block(&tx, &block)?;
+        }
+        if block.id.is_some() {
+            result.updated += 1;
+        } else {
+            result.inserted += 1;
+        }
         
-        // Index for search
-        if let Err(e) = state.search.index_time_block(&updated_block, &content) {
-            eprintln!("Failed to index time block: {}", e);
+        let notes_content = notes.get(i).cloned().flatten();
+        let saved = write_time_block(&tx, &state.files, &block, notes_content.as_deref())?;
+        result.ids.push(saved.id.unwrap_or_default());
+        if let Some(content) = notes_content {
+            to_index.push((saved, content));
         }
     }
+    tx.commit()?;
     
-    Ok(block_id)
+    if !to_index.is_empty() {
+        if let Err(e) = state.search.index_time_blocks(&to_index) {
+            error!("Failed to index time blocks: {}", e);
+        }
+    }
+    
+    app.trigger_global("blocks-changed", None);
+    Ok(result)
+}
+
+const BREAK_COLOR: &str = "#10b981";
+
+// (offset, length, is_break) segments alternating work and break over `duration`.
+// The plan always ends on work: a tail too short for another work segment after
+// a break is added to the last work segment instead.
+fn pomodoro_segments(duration: i32, work_minutes: i32, break_minutes: i32) -> Vec<(i32, i32, bool)> {
+    let mut segments = Vec::new();
+    let mut offset = 0;
+    while offset < duration {
+        let work = work_minutes.min(duration - offset);
+        segments.push((offset, work, false));
+        offset += work;
+        
+        let remaining = duration - offset;
+        if remaining == 0 {
+            break;
+        }
+        if remaining <= break_minutes {
+            if let Some(last) = segments.last_mut() {
+                last.1 += remaining;
+            }
+            break;
+        }
+        segments.push((offset, break_minutes, true));
+        offset += break_minutes;
+    }
+    segments
 }
 
+// Replace a block with alternating work and break blocks covering the same time.
+// The first work segment is the original block, so its notes and attachments stay with it.
 #[tauri::command]
-pub fn delete_time_block(block_id: i64, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+pub fn split_block_pomodoro(
+    block_id: i64,
+    work_min: i32,
+    break_min: i32,
+    app: AppHandle,
+    state: State<AppState>
+) -> AppResult<Vec<i64>> {
+    if work_min <= 0 || break_min <= 0 {
+        return Err(AppError::Validation("Work and break lengths must be positive".to_string()));
+    }
     
-    // Get notes file path before deletion
-    let mut stmt = conn.prepare("SELECT notes_file FROM time_blocks WHERE id = ?1")
-        .map_err(|e| e.to_string())?;
+    let conn = db_conn(&state.db)?;
+    let original = load_time_block(&conn, block_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Time block not found: {}", block_id)))?;
+    if original.duration_minutes <= work_min {
+        return Err(AppError::Validation(format!("A {} minute block is too short to split", original.duration_minutes)));
+    }
     
-    if let Ok(notes_file) = stmt.query_row([block_id], |row| {
-        Ok(row.get::<_, Option<String>>(0)?)
-    }) {
-        if let Some(file_path) = notes_file {
-            let _ = state.files.delete_notes(&file_path);
+    let tx = conn.unchecked_transaction()?;
+    let mut saved = Vec::new();
+    for (offset, length, is_break) in pomodoro_segments(original.duration_minutes, work_min, break_min) {
+        let mut segment = original.clone();
+        segment.start_minutes = original.start_minutes + offset;
+        segment.duration_minutes = length;
+        if offset > 0 {
+            segment.id = None;
+            segment.notes_file = None;
+            segment.completed = false;
+            segment.actual_duration_minutes = None;
+        }
+        if is_break {
+            segment.title = "Break".to_string();
+            segment.color = BREAK_COLOR.to_string();
+            segment.tags = vec!["break".to_string()];
+            segment.reminder_minutes = None;
         }
+        saved.push(write_time_block(&tx, &state.files, &segment, None)?);
     }
+    tx.commit()?;
     
-    // Delete attachments
-    let mut stmt = conn.prepare("SELECT file_path FROM attachments WHERE time_block_id = ?1")
-        .map_err(|e| e.to_string())?;
+    // Only the original has notes, and its indexed duration changed
+    if let Some(first) = saved.first() {
+        if let Some(notes_file) = &first.notes_file {
+            let content = state.files.load_notes(notes_file).unwrap_or_default();
+            if let Err(e) = state.search.index_time_blocks(&[(first.clone(), content)]) {
+                error!("Failed to index time blocks: {}", e);
+            }
+        }
+    }
     
-    let attachment_paths: Vec<String> = stmt.query_map([block_id], |row| {
-        Ok(row.get(0)?)
-    }).map_err(|e| e.to_string())?
-        .collect::<Result<Vec<String>, _>>()
-        .map_err(|e| e.to_string())?;
+    app.trigger_global("blocks-changed", None);
+    Ok(saved.iter().filter_map(|block| block.id).collect())
+}
+
+const MERGED_NOTES_SEPARATOR: &str = "\n\n---\n\n";
+
+// Notes of two merged blocks, skipping whichever is empty
+fn merge_notes(first: &str, second: &str) -> String {
+    match (first.trim().is_empty(), second.trim().is_empty()) {
+        (_, true) => first.to_string(),
+        (true, false) => second.to_string(),
+        (false, false) => format!("{}{}{}", first.trim_end(), MERGED_NOTES_SEPARATOR, second.trim_start()),
+    }
+}
+
+// Point an attachment at another block, moving its file (and generated thumbnail)
+// under that block's name. Deduplicated rows sharing the file follow it to the new
+// path. Returns the file moves so a failed commit can undo them.
+fn reassign_attachment(
+    conn: &Connection,
+    files: &crate::services::FileService,
+    attachment: &Attachment,
+    block_id: i64,
+    date: &str
+) -> anyhow::Result<Vec<(String, String)>> {
+    let file_path = files.reassign_attachment(&attachment.file_path, block_id, date)?;
+    let mut moves = vec![(attachment.file_path.clone(), file_path.clone())];
     
-    for path in attachment_paths {
-        let _ = state.files.delete_attachment(&path);
+    let generated = format!("{}.thumb.png", attachment.file_path);
+    if attachment.thumbnail_path.as_deref() == Some(generated.as_str()) && files.file_exists(&generated) {
+        let moved = format!("{}.thumb.png", file_path);
+        if let Err(e) = files.move_attachment(&generated, &moved) {
+            let _ = files.move_attachment(&file_path, &attachment.file_path);
+            return Err(e);
+        }
+        moves.push((generated, moved));
     }
     
-    // Delete from database
-    conn.execute("DELETE FROM time_blocks WHERE id = ?1", [block_id])
-        .map_err(|e| e.to_string())?;
+    let updated = moves.iter().try_for_each(|(from, to)| -> rusqlite::Result<()> {
+        conn.execute("UPDATE attachments SET file_path = ?1 WHERE file_path = ?2", (to, from))?;
+        conn.execute("UPDATE attachments SET thumbnail_path = ?1 WHERE thumbnail_path = ?2", (to, from))?;
+        Ok(())
+    }).and_then(|_| conn.execute("UPDATE attachments SET time_block_id = ?1 WHERE id = ?2", (block_id, attachment.id)));
+    if let Err(e) = updated {
+        for (from, to) in &moves {
+            let _ = files.move_attachment(to, from);
+        }
+        return Err(e.into());
+    }
+    Ok(moves)
+}
+
+// Fold `second` into `first`: one block spanning both, with their notes, tags,
+// attachments and notes sections. Returns the merged block and its notes.
+fn merge_time_blocks(
+    conn: &Connection,
+    files: &crate::services::FileService,
+    first_id: i64,
+    second_id: i64
+) -> AppResult<(TimeBlock, String)> {
+    let load = |id: i64| -> AppResult<TimeBlock> {
+        load_time_block(conn, id)?.ok_or_else(|| AppError::NotFound(format!("Time block not found: {}", id)))
+    };
+    if first_id == second_id {
+        return Err(AppError::Validation("Can't merge a block with itself".to_string()));
+    }
+    let (first, second) = (load(first_id)?, load(second_id)?);
+    if first.date != second.date {
+        return Err(AppError::Validation(format!("Can't merge blocks on different dates ({} and {})", first.date, second.date)));
+    }
+    if second.start_minutes != first.start_minutes + first.duration_minutes {
+        return Err(AppError::Validation(format!(
+            "'{}' doesn't start when '{}' ends, so they can't be merged", second.title, first.title
+        )));
+    }
     
-    // Remove from search index
-    if let Err(e) = state.search.delete_time_block(block_id) {
-        eprintln!("Failed to remove from search index: {}", e);
+    let load_notes = |block: &TimeBlock| block.notes_file.as_ref()
+        .map(|notes_file| files.load_notes(notes_file).unwrap_or_default());
+    let (first_notes, second_notes) = (load_notes(&first), load_notes(&second));
+    let notes = merge_notes(first_notes.as_deref().unwrap_or(""), second_notes.as_deref().unwrap_or(""));
+    
+    let tx = conn.unchecked_transaction()?;
+    if let Err(e) = crate::undo::record(&tx, &LoggedOperation::DeleteTimeBlock { block: second.clone(), notes: second_notes.clone() }) {
+        error!("Failed to record undo entry: {}", e);
     }
     
-    Ok(())
+    let mut moves = Vec::new();
+    let mut released = Vec::new();
+    let moved = (|| -> AppResult<TimeBlock> {
+        for attachment in query_attachments(&tx, second_id)? {
+            // A deduplicated row sharing a file moved earlier already points at its new path
+            if moves.iter().any(|(from, _)| *from == attachment.file_path) {
+                tx.execute("UPDATE attachments SET time_block_id = ?1 WHERE id = ?2", (first_id, attachment.id))?;
+                continue;
+            }
+            moves.extend(reassign_attachment(&tx, files, &attachment, first_id, &first.date)?);
+        }
+        
+        // Sections only the second block has move over; shared names are appended to
+        let mut stmt = tx.prepare("SELECT section_name, file_path FROM block_notes WHERE time_block_id = ?1")?;
+        let sections = stmt.query_map([second_id], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        for (section_name, file_path) in sections {
+            let existing: Option<String> = tx.query_row(
+                "SELECT file_path FROM block_notes WHERE time_block_id = ?1 AND section_name = ?2",
+                (first_id, &section_name),
+                |row| row.get(0)
+            ).optional()?;
+            match existing {
+                Some(existing) => {
+                    let combined = merge_notes(&files.load_notes(&existing)?, &files.load_notes(&file_path)?);
+                    files.save_note_section(&first, &section_name, Some(&existing), &combined)?;
+                    released.push(file_path);
+                }
+                None => {
+                    tx.execute(
+                        "UPDATE block_notes SET time_block_id = ?1 WHERE time_block_id = ?2 AND section_name = ?3",
+                        (first_id, second_id, &section_name),
+                    )?;
+                }
+            }
+        }
+        
+        let mut merged = first.clone();
+        merged.duration_minutes += second.duration_minutes;
+        for tag in &second.tags {
+            if !merged.tags.contains(tag) {
+                merged.tags.push(tag.clone());
+            }
+        }
+        let merged_notes = (first.notes_file.is_some() || second.notes_file.is_some()).then_some(notes.as_str());
+        let saved = write_time_block(&tx, files, &merged, merged_notes)?;
+        delete_time_block_rows(&tx, second_id)?;
+        Ok(saved)
+    })();
+    
+    let committed = moved.and_then(|saved| {
+        tx.commit()?;
+        Ok(saved)
+    });
+    let saved = match committed {
+        Ok(saved) => saved,
+        Err(e) => {
+            for (from, to) in moves.iter().rev() {
+                let _ = files.move_attachment(to, from);
+            }
+            return Err(e);
+        }
+    };
+    
+    released.extend(second.notes_file.clone());
+    for notes_file in released {
+        let _ = files.delete_notes(&notes_file);
+    }
+    Ok((saved, notes))
 }
 
+// Recombine two back-to-back blocks on the same day. The first block survives and
+// takes over the second's time, notes, tags and attachments.
 #[tauri::command]
-pub fn save_priorities(date: String, priorities: Vec<String>, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
-    
-    // Delete existing priorities for the date
-    conn.execute("DELETE FROM priorities WHERE date = ?1", [&date])
-        .map_err(|e| e.to_string())?;
+pub fn merge_blocks(first_id: i64, second_id: i64, app: AppHandle, state: State<AppState>) -> AppResult<TimeBlock> {
+    let conn = db_conn(&state.db)?;
+    let (merged, notes) = merge_time_blocks(&conn, &state.files, first_id, second_id)?;
     
-    // Insert new priorities
-    for (index, content) in priorities.iter().enumerate() {
-        if !content.trim().is_empty() {
-            conn.execute(
-                "INSERT INTO priorities (date, content, priority_order) VALUES (?1, ?2, ?3)",
-                (date.clone(), content, index as i32),
-            ).map_err(|e| e.to_string())?;
+    // The second block's documents go; the survivor's are rebuilt to pick up what it took over
+    let reindexed = (|| -> anyhow::Result<()> {
+        state.search.delete_time_block(second_id)?;
+        state.search.index_time_block(&merged, &notes)?;
+        for attachment in query_attachments(&conn, first_id)? {
+            index_attachment(&conn, &state.search, attachment.id.unwrap_or_default())?;
         }
+        let mut stmt = conn.prepare("SELECT id, section_name, file_path FROM block_notes WHERE time_block_id = ?1")?;
+        let sections = stmt.query_map([first_id], |row| Ok(NoteSectionDocument {
+            section_id: row.get(0)?,
+            time_block: merged.clone(),
+            section_name: row.get(1)?,
+            content: state.files.load_notes(&row.get::<_, String>(2)?).unwrap_or_default(),
+        }))?.collect::<rusqlite::Result<Vec<_>>>()?;
+        state.search.index_note_sections(&sections)
+    })();
+    if let Err(e) = reindexed {
+        error!("Failed to reindex merged block: {}", e);
     }
     
-    Ok(())
+    app.trigger_global("blocks-changed", None);
+    Ok(merged)
 }
 
 #[tauri::command]
-pub fn search_content(query: String, limit: Option<usize>, state: State<AppState>) -> Result<Vec<SearchResult>, String> {
-    let search_limit = limit.unwrap_or(20);
-    state.search.search(&query, search_limit)
-        .map_err(|e| e.to_string())
+pub fn save_week_template(template: WeekTemplate, state: State<AppState>) -> AppResult<i64> {
+    let mut conn = db_conn(&state.db)?;
+    crate::templates::save_template(&mut conn, &template)
 }
 
 #[tauri::command]
-pub fn get_settings(state: State<AppState>) -> Result<std::collections::HashMap<String, String>, String> {
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn.prepare("SELECT key, value FROM settings")
-        .map_err(|e| e.to_string())?;
+pub fn list_week_templates(state: State<AppState>) -> AppResult<Vec<WeekTemplate>> {
+    let conn = db_conn(&state.db)?;
+    crate::templates::list_templates(&conn)
+}
+
+#[tauri::command]
+pub fn delete_week_template(template_id: i64, state: State<AppState>) -> AppResult<()> {
+    let conn = db_conn(&state.db)?;
+    crate::templates::delete_template(&conn, template_id)
+}
+
+// Stamp a week template onto the seven days from week_start_date
+#[tauri::command]
+pub fn apply_week_template(template_id: i64, week_start_date: String, app: AppHandle, state: State<AppState>) -> AppResult<AppliedTemplate> {
+    let conn = db_conn(&state.db)?;
+    let applied = crate::templates::apply_template(&conn, &state.files, template_id, &week_start_date)?;
     
-    let settings_iter = stmt.query_map([], |row| {
-        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
-    }).map_err(|e| e.to_string())?;
+    if !applied.to_index.is_empty() {
+        if let Err(e) = state.search.index_time_blocks(&applied.to_index) {
+            error!("Failed to index time blocks: {}", e);
+        }
+        app.trigger_global("blocks-changed", None);
+    }
+    Ok(applied.result)
+}
+
+// An existing block with the same date, start time and title, used to make imports idempotent
+pub(crate) fn find_matching_block(conn: &Connection, block: &TimeBlock) -> rusqlite::Result<Option<i64>> {
+    conn.query_row(
+        "SELECT id FROM time_blocks WHERE date = ?1 AND start_minutes = ?2 AND title = ?3 ORDER BY id LIMIT 1",
+        (&block.date, block.start_minutes, &block.title),
+        |row| row.get(0),
+    ).optional()
+}
+
+// A blank title is stored as the default_block_title setting and flags the block as a
+// placeholder. It stays one until its title changes from the one it was given.
+fn placeholder_title(conn: &Connection, title: &str, previous: Option<&TimeBlock>) -> rusqlite::Result<(String, bool)> {
+    let title = title.trim();
+    if title.is_empty() {
+        let default_title = read_setting(conn, "default_block_title")?
+            .filter(|value| !value.trim().is_empty())
+            .unwrap_or_else(|| "Untitled".to_string());
+        return Ok((default_title, true));
+    }
     
-    let mut settings = std::collections::HashMap::new();
-    for setting in settings_iter {
-        let (key, value) = setting.map_err(|e| e.to_string())?;
-        settings.insert(key, value);
+    let unchanged = previous.is_some_and(|previous| previous.is_placeholder && previous.title == title);
+    Ok((title.to_string(), unchanged))
+}
+
+// Insert or update one block (tags, notes file) inside the caller's transaction.
+// Returns the block as saved, with its id and notes path filled in.
+pub(crate) fn write_time_block(
+    tx: &Connection,
+    files: &crate::services::FileService,
+    block: &TimeBlock,
+    notes_content: Option<&str>
+) -> AppResult<TimeBlock> {
+    let block = &TimeBlock { date: normalize_date(&block.date)?, ..block.clone() };
+    let tags_json = serde_json::to_string(&block.tags).unwrap_or_default();
+    let color = normalize_color(&block.color);
+    
+    let mut previous = None;
+    if let Some(id) = block.id {
+        previous = load_time_block(tx, id)?;
     }
+    let (title, is_placeholder) = placeholder_title(tx, &block.title, previous.as_ref())?;
     
-    Ok(settings)
+    let block_id = if let Some(id) = block.id {
+        // Keep the previous state so the edit can be undone
+        if let Some(previous) = &previous {
+            if let Err(e) = crate::undo::record(tx, &LoggedOperation::UpdateTimeBlock { previous: previous.clone() }) {
+                error!("Failed to record undo entry: {}", e);
+            }
+        }
+        
+        // Update existing
+        tx.execute(
+            "UPDATE time_blocks SET start_minutes = ?1, duration_minutes = ?2, title = ?3, 
+             notes_file = ?4, color = ?5, tags = ?6, reminder_minutes = ?7, is_placeholder = ?8, updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?9",
+            (block.start_minutes, block.duration_minutes, &title, 
+             &block.notes_file, &color, tags_json, block.reminder_minutes, is_placeholder, id),
+        )?;
+        id
+    } else {
+        // Insert new
+        tx.execute(
+            "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title, notes_file, color, tags, reminder_minutes,
+             completed, actual_duration_minutes, is_placeholder)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            (&block.date, block.start_minutes, block.duration_minutes, 
+             &title, &block.notes_file, &color, tags_json, block.reminder_minutes,
+             block.completed, block.actual_duration_minutes, is_placeholder),
+        )?;
+        tx.last_insert_rowid()
+    };
+    
+    sync_block_tags(tx, block_id, &block.tags)?;
+    
+    let mut updated_block = block.clone();
+    updated_block.id = Some(block_id);
+    updated_block.title = title;
+    updated_block.is_placeholder = is_placeholder;
+    if let Some(previous) = &previous {
+        // Updates keep the stored date
+        updated_block.date = previous.date.clone();
+    }
+    
+    // Keep the notes file named after the block's current start time, so moving
+    // a block doesn't leave its old file orphaned
+    let old_notes_file = previous.as_ref().and_then(|p| p.notes_file.clone()).or_else(|| block.notes_file.clone());
+    if let Some(old_notes_file) = old_notes_file {
+        let notes_path = files.rename_notes(&old_notes_file, &updated_block)?;
+        if block.notes_file.as_deref() != Some(notes_path.as_str()) {
+            tx.execute(
+                "UPDATE time_blocks SET notes_file = ?1 WHERE id = ?2",
+                (&notes_path, block_id),
+            )?;
+        }
+        updated_block.notes_file = Some(notes_path);
+    }
+    
+    // Save notes file if provided
+    if let Some(content) = notes_content {
+        let notes_path = files.save_notes(&updated_block, content)?;
+        
+        // Update notes_file path in database
+        tx.execute(
+            "UPDATE time_blocks SET notes_file = ?1 WHERE id = ?2",
+            (&notes_path, block_id),
+        )?;
+        updated_block.notes_file = Some(notes_path);
+    }
+    
+    Ok(updated_block)
 }
 
-#[tauri::command]
-pub fn update_setting(key: String, value: String, state: State<AppState>) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
-    conn.execute(
-        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
-        (key, value),
-    ).map_err(|e| e.to_string())?;
+// Replace a block's rows in block_tags so they match `tags`
+pub fn sync_block_tags(conn: &Connection, block_id: i64, tags: &[String]) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM block_tags WHERE time_block_id = ?1", [block_id])?;
     
+    for tag in tags.iter().map(|t| t.trim()).filter(|t| !t.is_empty()) {
+        conn.execute("INSERT OR IGNORE INTO tags (name) VALUES (?1)", [tag])?;
+        conn.execute(
+            "INSERT OR IGNORE INTO block_tags (time_block_id, tag_id) SELECT ?1, id FROM tags WHERE name = ?2",
+            (block_id, tag),
+        )?;
+    }
+    
+    prune_unused_tags(conn)
+}
+
+fn prune_unused_tags(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM block_tags)", [])?;
     Ok(())
 }
 
-#[tauri::command]
-pub fn load_notes(notes_file: String, state: State<AppState>) -> Result<String, String> {
-    state.files.load_notes(&notes_file)
-        .map_err(|e| e.to_string())
+// One-time migration of the JSON tags column into tags/block_tags
+pub fn backfill_block_tags(conn: &Connection) -> rusqlite::Result<()> {
+    let mut stmt = conn.prepare("SELECT id, tags FROM time_blocks WHERE tags IS NOT NULL AND tags NOT IN ('', '[]')")?;
+    let rows = stmt.query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))?
+        .collect::<rusqlite::Result<Vec<(i64, String)>>>()?;
+    
+    for (block_id, tags_json) in rows {
+        let tags: Vec<String> = serde_json::from_str(&tags_json).unwrap_or_default();
+        sync_block_tags(conn, block_id, &tags)?;
+    }
+    
+    Ok(())
 }
 
-#[tauri::command]
-pub fn get_available_intervals(state: State<AppState>) -> Result<Vec<TimeInterval>, String> {
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = 'available_intervals'")
-        .map_err(|e| e.to_string())?;
+// Remove a block's rows. Attachment rows are deleted explicitly rather than relying
+// solely on ON DELETE CASCADE, which only fires when foreign keys are enabled.
+fn delete_time_block_rows(conn: &Connection, block_id: i64) -> rusqlite::Result<()> {
+    conn.execute("DELETE FROM attachments WHERE time_block_id = ?1", [block_id])?;
+    conn.execute("DELETE FROM block_notes WHERE time_block_id = ?1", [block_id])?;
+    conn.execute("DELETE FROM block_tags WHERE time_block_id = ?1", [block_id])?;
+    conn.execute("DELETE FROM time_blocks WHERE id = ?1", [block_id])?;
+    prune_unused_tags(conn)
+}
+
+// A copy of a block as a new, not yet completed block on another date
+fn duplicate_block(block: &TimeBlock, date: &str) -> TimeBlock {
+    TimeBlock {
+        id: None,
+        date: date.to_string(),
+        notes_file: None,
+        created_at: None,
+        updated_at: None,
+        completed: false,
+        actual_duration_minutes: None,
+        notes_content: None,
+        ..block.clone()
+    }
+}
+
+struct DayCopy {
+    result: CopyDayResult,
+    replaced_ids: Vec<i64>,
+    to_index: Vec<(TimeBlock, String)>,
+}
+
+// Copy a day's blocks (and optionally notes and priorities) in one transaction.
+// With overwrite, the target day's blocks are removed first; their notes and
+// attachment files are only deleted once the copy has committed.
+fn copy_day_plan(
+    conn: &Connection,
+    files: &crate::services::FileService,
+    from_date: &str,
+    to_date: &str,
+    include_notes: bool,
+    include_priorities: bool,
+    overwrite: bool
+) -> AppResult<DayCopy> {
+    if from_date == to_date {
+        return Err(AppError::Validation("Can't copy a day onto itself".to_string()));
+    }
     
-    let intervals_json = stmt.query_row([], |row| {
-        Ok(row.get::<_, String>(0)?)
-    }).map_err(|e| e.to_string())?;
+    let existing = load_time_blocks(conn, to_date)?;
+    if !existing.is_empty() && !overwrite {
+        let result = CopyDayResult { skipped: true, ..Default::default() };
+        return Ok(DayCopy { result, replaced_ids: Vec::new(), to_index: Vec::new() });
+    }
     
-    let intervals: Vec<i32> = serde_json::from_str(&intervals_json)
-        .unwrap_or(vec![5, 15, 30, 60]);
+    let tx = conn.unchecked_transaction()?;
+    let mut result = CopyDayResult { replaced_blocks: existing.len(), ..Default::default() };
+    let mut released_notes = Vec::new();
+    let mut released_attachments = Vec::new();
+    for block in &existing {
+        let block_id = block.id.unwrap_or_default();
+        let notes = block.notes_file.as_ref().map(|file_path| files.load_notes(file_path).unwrap_or_default());
+        if let Err(e) = crate::undo::record(&tx, &LoggedOperation::DeleteTimeBlock { block: block.clone(), notes }) {
+            error!("Failed to record undo entry: {}", e);
+        }
+        released_notes.extend(block.notes_file.clone());
+        released_notes.extend(note_section_paths(&tx, block_id)?);
+        released_attachments.extend(query_attachments(&tx, block_id)?
+            .into_iter().map(|attachment| attachment.file_path));
+        delete_time_block_rows(&tx, block_id)?;
+    }
     
-    let time_intervals: Vec<TimeInterval> = intervals.into_iter().map(|minutes| {
-        let label = if minutes >= 60 {
-            let hours = minutes / 60;
-            let remaining_minutes = minutes % 60;
-            if remaining_minutes == 0 {
-                format!("{} hour{}", hours, if hours > 1 { "s" } else { "" })
-            } else {
-                format!("{}h {}m", hours, remaining_minutes)
-            }
-        } else {
-            format!("{} min", minutes)
+    let mut to_index = Vec::new();
+    for block in load_time_blocks(&tx, from_date)? {
+        let notes = match (&block.notes_file, include_notes) {
+            (Some(notes_file), true) => Some(files.load_notes(notes_file)?),
+            _ => None,
         };
-        
-        TimeInterval { minutes, label }
-    }).collect();
+        let saved = write_time_block(&tx, files, &duplicate_block(&block, to_date), notes.as_deref())?;
+        result.blocks += 1;
+        if let Some(notes) = notes {
+            result.notes += 1;
+            to_index.push((saved, notes));
+        }
+    }
     
-    Ok(time_intervals)
+    if include_priorities {
+        if overwrite {
+            tx.execute("DELETE FROM priorities WHERE date = ?1", [to_date])?;
+        }
+        // Copied priorities go after any the day already has
+        result.priorities = tx.execute(
+            "INSERT INTO priorities (date, content, priority_order)
+             SELECT ?2, content, priority_order + (SELECT COALESCE(MAX(priority_order) + 1, 0) FROM priorities WHERE date = ?2)
+             FROM priorities WHERE date = ?1 ORDER BY priority_order",
+            (from_date, to_date)
+        )?;
+    }
+    tx.commit()?;
+    
+    for notes_file in released_notes {
+        let _ = files.delete_notes(&notes_file);
+    }
+    for path in released_attachments {
+        if let Err(e) = release_attachment_file(conn, files, &path) {
+            error!("Failed to remove attachment file {}: {}", path, e);
+        }
+    }
+    
+    let replaced_ids = existing.iter().filter_map(|block| block.id).collect();
+    Ok(DayCopy { result, replaced_ids, to_index })
 }
 
+// Plan a day like another: copies its blocks with new ids, optionally with their
+// notes and the day's priorities. Blocks already on `to_date` are only replaced
+// with `overwrite`; otherwise nothing is copied and the result says skipped.
 #[tauri::command]
-pub fn save_attachment(
-    time_block_id: i64,
-    date: String,
-    file_data: Vec<u8>,
-    filename: String,
-    file_type: String,
+pub fn copy_day(
+    from_date: String,
+    to_date: String,
+    include_notes: bool,
+    include_priorities: bool,
+    overwrite: Option<bool>,
+    app: AppHandle,
     state: State<AppState>
-) -> Result<String, String> {
-    // Save file to disk
-    let file_path = state.files.save_attachment(time_block_id, &date, &file_data, &filename)
-        .map_err(|e| e.to_string())?;
+) -> AppResult<CopyDayResult> {
+    let from_date = normalize_date(&from_date)?;
+    let to_date = normalize_date(&to_date)?;
+    let conn = db_conn(&state.db)?;
+    let copy = copy_day_plan(
+        &conn, &state.files, &from_date, &to_date,
+        include_notes, include_priorities, overwrite.unwrap_or(false)
+    )?;
     
-    // Save to database
-    let conn = state.db.lock().unwrap();
-    conn.execute(
-        "INSERT INTO attachments (time_block_id, file_path, file_name, file_type, file_size) 
-         VALUES (?1, ?2, ?3, ?4, ?5)",
-        (time_block_id, &file_path, &filename, &file_type, file_data.len() as i64)
-    ).map_err(|e| e.to_string())?;
+    if !copy.replaced_ids.is_empty() {
+        if let Err(e) = state.search.delete_time_blocks(&copy.replaced_ids) {
+            error!("Failed to remove from search index: {}", e);
+        }
+    }
+    if !copy.to_index.is_empty() {
+        if let Err(e) = state.search.index_time_blocks(&copy.to_index) {
+            error!("Failed to index time blocks: {}", e);
+        }
+    }
     
-    Ok(file_path)
+    if !copy.result.skipped {
+        app.trigger_global("blocks-changed", None);
+    }
+    Ok(copy.result)
 }
 
-#[tauri::command]
-pub fn get_attachments(time_block_id: i64, state: State<AppState>) -> Result<Vec<crate::models::Attachment>, String> {
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn.prepare(
-        "SELECT id, time_block_id, file_path, file_name, file_type, file_size, created_at 
-         FROM attachments WHERE time_block_id = ?1 ORDER BY created_at DESC"
-    ).map_err(|e| e.to_string())?;
-    
-    let attachments = stmt.query_map([time_block_id], |row| {
-        Ok(crate::models::Attachment {
-            id: row.get(0)?,
-            time_block_id: row.get(1)?,
-            file_path: row.get(2)?,
-            file_name: row.get(3)?,
-            file_type: row.get(4)?,
-            file_size: row.get(5)?,
-            created_at: row.get(6)?,
-        })
-    }).map_err(|e| e.to_string())?;
+// Copy a block onto each later day of its week, skipping days that already have a
+// block with the same start, duration and title. Returns the new blocks' ids.
+fn repeat_through_week(conn: &Connection, files: &crate::services::FileService, block_id: i64) -> AppResult<Vec<i64>> {
+    let block = load_time_block(conn, block_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Time block not found: {}", block_id)))?;
+    let day = chrono::NaiveDate::parse_from_str(&block.date, "%Y-%m-%d")
+        .map_err(|_| AppError::Validation(format!("Invalid date: {}", block.date)))?;
+    let week = crate::scheduling::week_dates(day, week_start_setting(conn)?);
     
-    let mut result = Vec::new();
-    for attachment in attachments {
-        result.push(attachment.map_err(|e| e.to_string())?);
+    let tx = conn.unchecked_transaction()?;
+    let mut created = Vec::new();
+    for date in week.iter().filter(|date| **date > day).map(|date| date.to_string()) {
+        let already_there = load_time_blocks(&tx, &date)?.iter().any(|existing| {
+            existing.start_minutes == block.start_minutes
+                && existing.duration_minutes == block.duration_minutes
+                && existing.title == block.title
+        });
+        if already_there {
+            continue;
+        }
+        let saved = write_time_block(&tx, files, &duplicate_block(&block, &date), None)?;
+        created.extend(saved.id);
     }
+    tx.commit()?;
     
-    Ok(result)
+    Ok(created)
 }
 
+// "Do this every day this week" without setting up recurrence
 #[tauri::command]
-pub fn load_attachment(file_path: String, state: State<AppState>) -> Result<Vec<u8>, String> {
-    let full_path = state.files.get_data_dir().join(&file_path);
-    std::fs::read(&full_path).map_err(|e| e.to_string())
+pub fn repeat_block_this_week(block_id: i64, app: AppHandle, state: State<AppState>) -> AppResult<Vec<i64>> {
+    let conn = db_conn(&state.db)?;
+    let created = repeat_through_week(&conn, &state.files, block_id)?;
+    
+    if !created.is_empty() {
+        app.trigger_global("blocks-changed", None);
+    }
+    Ok(created)
 }
 
 #[tauri::command]
-pub fn get_time_block_notes(block_id: i64, state: State<AppState>) -> Result<String, String> {
-    let conn = state.db.lock().unwrap();
-    
-    // Get the notes file path for this block
-    let notes_file: Option<String> = conn.query_row(
-        "SELECT notes_file FROM time_blocks WHERE id = ?1",
-        [block_id],
-        |row| row.get(0)
-    ).map_err(|e| e.to_string())?;
+pub fn delete_time_block(block_id: i64, app: AppHandle, state: State<AppState>) -> AppResult<()> {
+    let conn = db_conn(&state.db)?;
     
-    if let Some(file_path) = notes_file {
-        // Load the notes content from file
-        match state.files.load_notes(&file_path) {
-            Ok(content) => Ok(content),
-            Err(_) => Ok(String::new()) // Return empty string if file doesn't exist
+    // Log the block and its notes before deletion so it can be restored
+    if let Some(block) = load_time_block(&conn, block_id)? {
+        let notes = block.notes_file.as_ref()
+            .map(|file_path| state.files.load_notes(file_path))
+            .transpose()?;
+        let notes_file = block.notes_file.clone();
+        
+        if let Err(e) = crate::undo::record(&conn, &LoggedOperation::DeleteTimeBlock { block, notes }) {
+            error!("Failed to record undo entry: {}", e);
         }
-    } else {
-        Ok(String::new()) // No notes file associated with this block
+        
+        if let Some(file_path) = notes_file {
+            let _ = state.files.delete_notes(&file_path);
+        }
+    }
+    for file_path in note_section_paths(&conn, block_id)? {
+        let _ = state.files.delete_notes(&file_path);
+    }
+    
+    // Delete attachments
+    let mut stmt = conn.prepare("SELECT file_path FROM attachments WHERE time_block_id = ?1")?;
+    
+    let attachment_paths: Vec<String> = stmt.query_map([block_id], |row| {
+        Ok(row.get(0)?)
+    })?
+        .collect::<Result<Vec<String>, _>>()?;
+    
+    // Delete from database
+    delete_time_block_rows(&conn, block_id)?;
+    
+    for path in attachment_paths {
+        release_attachment_file(&conn, &state.files, &path)?;
     }
-}
\ No newline at end of file
+    
+    // Remove from search index
+    if let Err(e) = state.search.delete_time_block(block_id) {
+        error!("Failed to remove from search index: {}", e);
+    }
+    
+    app.trigger_global("blocks-changed", None);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn undo_last(scope: Option<String>, app: AppHandle, state: State<AppState>) -> AppResult<Option<UndoResult>> {
+    let conn = db_conn(&state.db)?;
+    let result = crate::undo::undo_last(&conn, &state.files, scope.as_deref())?;
+    
+    if let Some(undone) = &result {
+        if let Some(notes_file) = &undone.block.notes_file {
+            let notes = state.files.load_notes(notes_file)?;
+            if let Err(e) = state.search.index_time_block(&undone.block, &notes) {
+                error!("Failed to index time block: {}", e);
+            }
+        }
+        app.trigger_global("blocks-changed", None);
+    }
+    
+    Ok(result)
+}
+
+// Move a block to another day, optionally at a new start time. Its notes and
+// attachment files follow it into the new date's directories.
+#[tauri::command]
+pub fn move_time_block(
+    block_id: i64,
+    date: String,
+    start_minutes: Option<i32>,
+    app: AppHandle,
+    state: State<AppState>
+) -> AppResult<TimeBlock> {
+    let date = normalize_date(&date)?;
+    if let Some(start) = start_minutes {
+        if !(0..MINUTES_PER_DAY).contains(&start) {
+            return Err(AppError::Validation(format!("Invalid start time: {}", start)));
+        }
+    }
+    
+    let conn = db_conn(&state.db)?;
+    let tx = conn.unchecked_transaction()?;
+    let previous = load_time_block(&tx, block_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Time block not found: {}", block_id)))?;
+    if let Err(e) = crate::undo::record(&tx, &LoggedOperation::UpdateTimeBlock { previous: previous.clone() }) {
+        error!("Failed to record undo entry: {}", e);
+    }
+    
+    let mut moved = previous.clone();
+    moved.date = date;
+    moved.start_minutes = start_minutes.unwrap_or(previous.start_minutes);
+    tx.execute(
+        "UPDATE time_blocks SET date = ?1, start_minutes = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?3",
+        (&moved.date, moved.start_minutes, block_id),
+    )?;
+    
+    let attachment_moves = relocate_attachments(&tx, &state.files, block_id, &previous.date, &moved.date)?;
+    if let Some(notes_file) = &previous.notes_file {
+        let notes_path = state.files.rename_notes(notes_file, &moved)?;
+        tx.execute("UPDATE time_blocks SET notes_file = ?1 WHERE id = ?2", (&notes_path, block_id))?;
+        moved.notes_file = Some(notes_path);
+    }
+    
+    if let Err(e) = tx.commit() {
+        for (from, to) in &attachment_moves {
+            let _ = state.files.move_attachment(to, from);
+        }
+        if let Some(notes_file) = &moved.notes_file {
+            let _ = state.files.rename_notes(notes_file, &previous);
+        }
+        return Err(e.into());
+    }
+    
+    // The search documents carry the date, so refresh them
+    let notes = match &moved.notes_file {
+        Some(notes_file) => state.files.load_notes(notes_file).unwrap_or_default(),
+        None => String::new(),
+    };
+    if let Err(e) = state.search.index_time_block(&moved, &notes) {
+        error!("Failed to index time block: {}", e);
+    }
+    
+    app.trigger_global("blocks-changed", None);
+    Ok(moved)
+}
+
+// Move a block's attachment files into its new date's directory and repoint every
+// row referencing them, including deduplicated rows on other blocks. Files are put
+// back if the database update fails. Returns the (old, new) paths moved.
+pub fn relocate_attachments(
+    conn: &Connection,
+    files: &crate::services::FileService,
+    block_id: i64,
+    old_date: &str,
+    new_date: &str
+) -> anyhow::Result<Vec<(String, String)>> {
+    let moves = files.move_attachments(block_id, old_date, new_date)?;
+    
+    for (from, to) in &moves {
+        let updated = conn.execute("UPDATE attachments SET file_path = ?1 WHERE file_path = ?2", (to, from))
+            .and_then(|_| conn.execute("UPDATE attachments SET thumbnail_path = ?1 WHERE thumbnail_path = ?2", (to, from)));
+        if let Err(e) = updated {
+            for (from, to) in &moves {
+                let _ = files.move_attachment(to, from);
+            }
+            return Err(e.into());
+        }
+    }
+    Ok(moves)
+}
+
+// Record that a block was done (or not), optionally with the time actually spent.
+// Kept separate from save_time_block so editing a block never resets its completion.
+#[tauri::command]
+pub fn mark_block_complete(
+    block_id: i64,
+    actual_duration: Option<i32>,
+    completed: Option<bool>,
+    app: AppHandle,
+    state: State<AppState>
+) -> AppResult<TimeBlock> {
+    if actual_duration.is_some_and(|minutes| minutes <= 0) {
+        return Err(AppError::Validation("Actual duration must be positive".to_string()));
+    }
+    
+    let conn = db_conn(&state.db)?;
+    let updated = complete_block(&conn, block_id, completed.unwrap_or(true), actual_duration)?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("Time block not found: {}", block_id)));
+    }
+    
+    let block = load_time_block(&conn, block_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Time block not found: {}", block_id)))?;
+    app.trigger_global("blocks-changed", None);
+    Ok(block)
+}
+
+// Set a block's completion, carrying it over to the priority it was scheduled from
+fn complete_block(conn: &Connection, block_id: i64, completed: bool, actual_duration: Option<i32>) -> rusqlite::Result<usize> {
+    let updated = conn.execute(
+        "UPDATE time_blocks SET completed = ?1, actual_duration_minutes = ?2, updated_at = CURRENT_TIMESTAMP WHERE id = ?3",
+        (completed, actual_duration, block_id)
+    )?;
+    conn.execute(
+        "UPDATE priorities SET completed = ?1 WHERE id = (SELECT priority_id FROM time_blocks WHERE id = ?2)",
+        (completed, block_id)
+    )?;
+    Ok(updated)
+}
+
+// A new block for a priority: titled after it and tagged "priority"
+fn priority_block(
+    conn: &Connection,
+    priority_id: i64,
+    date: &str,
+    start_minutes: i32,
+    duration_minutes: i32
+) -> AppResult<TimeBlock> {
+    if duration_minutes <= 0 || start_minutes < 0 || start_minutes + duration_minutes > MINUTES_PER_DAY {
+        return Err(AppError::Validation("Block must fit within the day".to_string()));
+    }
+    let content: String = conn.query_row(
+        "SELECT content FROM priorities WHERE id = ?1",
+        [priority_id],
+        |row| row.get(0)
+    ).optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Priority not found: {}", priority_id)))?;
+    
+    Ok(TimeBlock {
+        id: None,
+        date: date.to_string(),
+        start_minutes,
+        duration_minutes,
+        title: content,
+        notes_file: None,
+        color: DEFAULT_BLOCK_COLOR.to_string(),
+        tags: vec!["priority".to_string()],
+        created_at: None,
+        updated_at: None,
+        reminder_minutes: None,
+        completed: false,
+        actual_duration_minutes: None,
+        notes_content: None,
+        is_placeholder: false,
+    })
+}
+
+// Turn a priority into a scheduled block; completing the block completes the priority
+#[tauri::command]
+pub fn schedule_priority(
+    priority_id: i64,
+    date: String,
+    start_minutes: i32,
+    duration_minutes: i32,
+    app: AppHandle,
+    state: State<AppState>
+) -> AppResult<i64> {
+    let conn = db_conn(&state.db)?;
+    let block = priority_block(&conn, priority_id, &date, start_minutes, duration_minutes)?;
+    
+    let tx = conn.unchecked_transaction()?;
+    let saved = write_time_block(&tx, &state.files, &block, None)?;
+    let block_id = saved.id.unwrap_or_default();
+    tx.execute("UPDATE time_blocks SET priority_id = ?1 WHERE id = ?2", (priority_id, block_id))?;
+    tx.commit()?;
+    
+    app.trigger_global("blocks-changed", None);
+    Ok(block_id)
+}
+
+// Recolor a block without going through the full save (notes, tags, search)
+#[tauri::command]
+pub fn set_block_color(id: i64, color: String, app: AppHandle, state: State<AppState>) -> AppResult<()> {
+    let color = crate::models::parse_hex_color(&color)
+        .ok_or_else(|| AppError::Validation(format!("Invalid color: {}", color)))?;
+    
+    let conn = db_conn(&state.db)?;
+    let updated = conn.execute(
+        "UPDATE time_blocks SET color = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+        (&color, id)
+    )?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("Time block not found: {}", id)));
+    }
+    
+    app.trigger_global("blocks-changed", None);
+    Ok(())
+}
+
+pub fn day_summary(conn: &Connection, date: &str) -> rusqlite::Result<DaySummary> {
+    let blocks = load_time_blocks(conn, date)?;
+    let completed: Vec<&TimeBlock> = blocks.iter().filter(|block| block.completed).collect();
+    let hours = effective_hours(conn, date)?;
+    
+    Ok(DaySummary {
+        date: date.to_string(),
+        total_blocks: blocks.len() as i64,
+        completed_blocks: completed.len() as i64,
+        planned_minutes: blocks.iter().map(|block| block.duration_minutes as i64).sum(),
+        actual_minutes: completed.iter()
+            .map(|block| block.actual_duration_minutes.unwrap_or(block.duration_minutes) as i64)
+            .sum(),
+        working_minutes: (hours.end_minutes - hours.start_minutes) as i64,
+    })
+}
+
+#[tauri::command]
+pub fn get_day_summary(date: String, state: State<AppState>) -> AppResult<DaySummary> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    day_summary(&conn, &date).map_err(AppError::from)
+}
+
+// Planned against actual minutes of completed blocks in the inclusive range, overall
+// and per tag. Completed blocks without an actual duration are only counted.
+pub fn variance_report(conn: &Connection, date_from: &str, date_to: &str) -> rusqlite::Result<VarianceReport> {
+    let blocks = load_time_blocks_between(conn, date_from, date_to)?;
+    let completed: Vec<&TimeBlock> = blocks.iter().filter(|block| block.completed).collect();
+    let measured: Vec<(&TimeBlock, i32)> = completed.iter()
+        .filter_map(|block| block.actual_duration_minutes.map(|actual| (*block, actual)))
+        .collect();
+    
+    let bar = |label: &str, blocks: &[&(&TimeBlock, i32)]| {
+        let planned_minutes: i64 = blocks.iter().map(|(block, _)| block.duration_minutes as i64).sum();
+        let actual_minutes: i64 = blocks.iter().map(|(_, actual)| *actual as i64).sum();
+        let delta_minutes = actual_minutes - planned_minutes;
+        VarianceBar {
+            label: label.to_string(),
+            block_count: blocks.len() as i64,
+            planned_minutes,
+            actual_minutes,
+            delta_minutes,
+            delta_percent: (planned_minutes > 0).then(|| delta_minutes as f64 * 100.0 / planned_minutes as f64),
+        }
+    };
+    
+    let mut tags: Vec<&str> = measured.iter()
+        .flat_map(|(block, _)| block.tags.iter().map(String::as_str))
+        .collect();
+    tags.sort_unstable();
+    tags.dedup();
+    
+    let mut by_tag: Vec<VarianceBar> = tags.into_iter()
+        .map(|tag| {
+            let tagged: Vec<&(&TimeBlock, i32)> = measured.iter()
+                .filter(|(block, _)| block.tags.iter().any(|t| t == tag))
+                .collect();
+            bar(tag, &tagged)
+        })
+        .collect();
+    by_tag.sort_by(|a, b| b.planned_minutes.cmp(&a.planned_minutes).then_with(|| a.label.cmp(&b.label)));
+    
+    Ok(VarianceReport {
+        overall: bar("overall", &measured.iter().collect::<Vec<_>>()),
+        by_tag,
+        unmeasured: (completed.len() - measured.len()) as i64,
+    })
+}
+
+#[tauri::command]
+pub fn get_variance_report(date_from: String, date_to: String, state: State<AppState>) -> AppResult<VarianceReport> {
+    let date_from = normalize_date(&date_from)?;
+    let date_to = normalize_date(&date_to)?;
+    let conn = db_conn(&state.db)?;
+    variance_report(&conn, &date_from, &date_to).map_err(AppError::from)
+}
+
+// Days in the inclusive range that have blocks, priorities or a brain dump, in date order
+pub fn populated_dates(conn: &Connection, date_from: &str, date_to: &str) -> rusqlite::Result<Vec<PopulatedDate>> {
+    let mut stmt = conn.prepare(
+        "WITH days AS (
+             SELECT date FROM time_blocks WHERE date BETWEEN ?1 AND ?2
+             UNION SELECT date FROM priorities WHERE date BETWEEN ?1 AND ?2
+             UNION SELECT date FROM brain_dumps WHERE date BETWEEN ?1 AND ?2 AND content != ''
+         )
+         SELECT d.date, COALESCE(b.block_count, 0), COALESCE(b.minutes, 0),
+                EXISTS (SELECT 1 FROM priorities p WHERE p.date = d.date),
+                EXISTS (SELECT 1 FROM brain_dumps bd WHERE bd.date = d.date AND bd.content != '')
+         FROM days d
+         LEFT JOIN (
+             SELECT date, COUNT(*) AS block_count, SUM(duration_minutes) AS minutes
+             FROM time_blocks WHERE date BETWEEN ?1 AND ?2 GROUP BY date
+         ) b ON b.date = d.date
+         ORDER BY d.date"
+    )?;
+    
+    let dates = stmt.query_map([date_from, date_to], |row| {
+        Ok(PopulatedDate {
+            date: row.get(0)?,
+            block_count: row.get(1)?,
+            scheduled_minutes: row.get(2)?,
+            has_priorities: row.get(3)?,
+            has_brain_dump: row.get(4)?,
+        })
+    })?.collect::<rusqlite::Result<Vec<PopulatedDate>>>();
+    dates
+}
+
+#[tauri::command]
+pub fn get_populated_dates(date_from: String, date_to: String, state: State<AppState>) -> AppResult<Vec<PopulatedDate>> {
+    let date_from = normalize_date(&date_from)?;
+    let date_to = normalize_date(&date_to)?;
+    let conn = db_conn(&state.db)?;
+    populated_dates(&conn, &date_from, &date_to).map_err(AppError::from)
+}
+
+// The closest date before or after `date` with blocks, priorities or a brain dump
+pub fn adjacent_populated_date(conn: &Connection, date: &str, direction: &str) -> AppResult<Option<String>> {
+    let (aggregate, comparison) = match direction {
+        "next" => ("MIN", ">"),
+        "prev" => ("MAX", "<"),
+        _ => return Err(AppError::Validation(format!("Direction must be 'next' or 'prev', got '{}'", direction))),
+    };
+    let sql = format!(
+        "SELECT {0}(date) FROM (
+             SELECT date FROM time_blocks WHERE date {1} ?1
+             UNION ALL SELECT date FROM priorities WHERE date {1} ?1
+             UNION ALL SELECT date FROM brain_dumps WHERE date {1} ?1 AND content != ''
+         )",
+        aggregate, comparison
+    );
+    Ok(conn.query_row(&sql, [date], |row| row.get(0))?)
+}
+
+// For "jump to the next day with plans"; None when there's nothing further that way
+#[tauri::command]
+pub fn get_adjacent_populated_date(date: String, direction: String, state: State<AppState>) -> AppResult<Option<String>> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    adjacent_populated_date(&conn, &date, &direction)
+}
+
+// Update a day's priorities in place, so their ids and the blocks scheduled from them
+// survive edits. Rows are matched by content first, then reused in order; leftovers
+// are deleted and unlink their blocks.
+fn write_priorities(conn: &Connection, date: &str, priorities: &[String]) -> AppResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    let mut existing: Vec<(i64, String)> = {
+        let mut stmt = tx.prepare("SELECT id, content FROM priorities WHERE date = ?1 ORDER BY priority_order, id")?;
+        let rows = stmt.query_map([date], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows
+    };
+    
+    let wanted: Vec<(usize, &String)> = priorities.iter().enumerate()
+        .filter(|(_, content)| !content.trim().is_empty())
+        .collect();
+    let matched: Vec<Option<i64>> = wanted.iter()
+        .map(|(_, content)| {
+            let position = existing.iter().position(|(_, existing)| existing == *content)?;
+            Some(existing.remove(position).0)
+        })
+        .collect();
+    
+    let mut spare = existing.into_iter().map(|(id, _)| id);
+    for ((index, content), id) in wanted.into_iter().zip(matched) {
+        match id.or_else(|| spare.next()) {
+            Some(id) => tx.execute(
+                "UPDATE priorities SET content = ?1, priority_order = ?2 WHERE id = ?3",
+                (content, index as i32, id),
+            )?,
+            None => tx.execute(
+                "INSERT INTO priorities (date, content, priority_order) VALUES (?1, ?2, ?3)",
+                (date, content, index as i32),
+            )?,
+        };
+    }
+    for id in spare {
+        tx.execute("UPDATE time_blocks SET priority_id = NULL WHERE priority_id = ?1", [id])?;
+        tx.execute("DELETE FROM priorities WHERE id = ?1", [id])?;
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn save_priorities(date: String, priorities: Vec<String>, state: State<AppState>) -> AppResult<()> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    write_priorities(&conn, &date, &priorities)
+}
+
+// Refresh results' color and tags from their blocks, so they show the same chips as
+// the calendar even if the block changed since its notes were indexed
+fn with_block_details(conn: &Connection, mut results: Vec<SearchResult>) -> AppResult<Vec<SearchResult>> {
+    for result in &mut results {
+        if let Some(block) = load_time_block(conn, result.id)? {
+            result.color = block.color;
+            result.tags = block.tags;
+        }
+    }
+    Ok(results)
+}
+
+#[tauri::command]
+pub fn search_content(
+    query: String,
+    limit: Option<usize>,
+    sort_by: Option<String>,
+    highlight_fragments: Option<usize>,
+    highlight_max_chars: Option<usize>,
+    state: State<AppState>
+) -> AppResult<Vec<SearchResult>> {
+    let search_limit = limit.unwrap_or(20);
+    let sort = SearchSort::parse(sort_by.as_deref().unwrap_or("relevance"))?;
+    let highlights = HighlightOptions::new(highlight_fragments, highlight_max_chars);
+    let results = state.search.search_with_highlights(&query, search_limit, sort, highlights)?;
+    let conn = db_conn(&state.db)?;
+    with_block_details(&conn, results)
+}
+
+#[tauri::command]
+pub fn search_in_date(
+    date: String,
+    query: String,
+    limit: Option<usize>,
+    state: State<AppState>
+) -> AppResult<Vec<SearchResult>> {
+    let date = normalize_date(&date)?;
+    let results = state.search.search_in_date(&date, &query, limit.unwrap_or(20))?;
+    let conn = db_conn(&state.db)?;
+    with_block_details(&conn, results)
+}
+
+// Results scoped to blocks with one exact tag, for a tag-filtered search view
+#[tauri::command]
+pub fn search_by_tag(
+    tag: String,
+    query: String,
+    limit: Option<usize>,
+    state: State<AppState>
+) -> AppResult<Vec<SearchResult>> {
+    if tag.trim().is_empty() {
+        return Err(AppError::Validation("Tag can't be empty".to_string()));
+    }
+    let results = state.search.search_by_tag(&tag, &query, limit.unwrap_or(20))?;
+    let conn = db_conn(&state.db)?;
+    with_block_details(&conn, results)
+}
+
+const SUGGEST_BELOW_RESULTS: usize = 3;
+const MAX_SUGGESTIONS: usize = 5;
+
+// "Did you mean" alternatives, only offered when the query finds next to nothing
+#[tauri::command]
+pub fn search_suggest(query: String, state: State<AppState>) -> AppResult<Vec<String>> {
+    let results = state.search.search(&query, SUGGEST_BELOW_RESULTS, SearchSort::Relevance)?;
+    if results.len() >= SUGGEST_BELOW_RESULTS {
+        return Ok(Vec::new());
+    }
+    state.search.suggest(&query, MAX_SUGGESTIONS).map_err(AppError::from)
+}
+
+fn count_rows(conn: &Connection, table: &str) -> rusqlite::Result<i64> {
+    conn.query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| row.get(0))
+}
+
+#[tauri::command]
+pub fn get_stats(state: State<AppState>) -> AppResult<Stats> {
+    let conn = db_conn(&state.db)?;
+    let count = |table: &str| count_rows(&conn, table).map_err(AppError::from);
+    
+    Ok(Stats {
+        time_blocks: count("time_blocks")?,
+        priorities: count("priorities")?,
+        brain_dumps: count("brain_dumps")?,
+        attachments: count("attachments")?,
+        calendar_events: count("calendar_events")?,
+        notes_bytes: state.files.dir_size("notes")?,
+        search_documents: state.search.doc_count(),
+        search_index_bytes: state.files.dir_size("search")?,
+    })
+}
+
+// Setup fails loudly, but a service can still degrade later (locked database,
+// damaged index); this reports each one rather than failing as a whole
+#[tauri::command]
+pub fn health_check(state: State<AppState>) -> HealthStatus {
+    let mut problems = Vec::new();
+    
+    let db = db_conn(&state.db).and_then(|conn| {
+        conn.query_row("SELECT 1", [], |row| row.get::<_, i64>(0)).map_err(AppError::from)
+    });
+    if let Err(e) = &db {
+        problems.push(format!("Database: {}", e));
+    }
+    
+    let search = state.search.check();
+    if let Err(e) = &search {
+        problems.push(format!("Search index: {}", e));
+    }
+    
+    let crypto_ok = state.calendar.encryption_active();
+    if !crypto_ok {
+        problems.push("Token encryption unavailable; calendar tokens are stored in plain text".to_string());
+    }
+    
+    HealthStatus {
+        db_ok: db.is_ok(),
+        search_ok: search.is_ok(),
+        crypto_ok,
+        data_dir: state.files.get_data_dir().to_string_lossy().into_owned(),
+        problems,
+    }
+}
+
+// Find notes and attachment rows whose files have disappeared from disk. With
+// `repair`, missing notes references are cleared and attachment rows whose files
+// are gone are deleted; attachments of deleted blocks are only reported.
+pub fn check_integrity(
+    conn: &mut Connection,
+    files: &crate::services::FileService,
+    repair: bool
+) -> AppResult<IntegrityReport> {
+    let mut report = IntegrityReport::default();
+    
+    let notes: Vec<(i64, String)> = {
+        let mut stmt = conn.prepare("SELECT id, notes_file FROM time_blocks WHERE notes_file IS NOT NULL AND notes_file != '' ORDER BY id")?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows
+    };
+    report.notes_checked = notes.len();
+    report.missing_notes = notes.into_iter()
+        .filter(|(_, notes_file)| !files.file_exists(notes_file))
+        .map(|(id, path)| MissingFile { id, time_block_id: id, path })
+        .collect();
+    
+    let attachments: Vec<(i64, i64, String, bool)> = {
+        let mut stmt = conn.prepare(
+            "SELECT a.id, a.time_block_id, a.file_path, t.id IS NOT NULL
+             FROM attachments a LEFT JOIN time_blocks t ON t.id = a.time_block_id ORDER BY a.id"
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows
+    };
+    report.attachments_checked = attachments.len();
+    for (id, time_block_id, path, has_block) in attachments {
+        if !has_block {
+            report.dangling_attachments.push(id);
+        }
+        if !files.file_exists(&path) {
+            report.missing_attachments.push(MissingFile { id, time_block_id, path });
+        }
+    }
+    
+    if repair && (!report.missing_notes.is_empty() || !report.missing_attachments.is_empty()) {
+        let tx = conn.transaction()?;
+        for missing in &report.missing_notes {
+            tx.execute("UPDATE time_blocks SET notes_file = NULL WHERE id = ?1", [missing.id])?;
+        }
+        for missing in &report.missing_attachments {
+            let thumbnail_path: Option<String> = tx.query_row(
+                "DELETE FROM attachments WHERE id = ?1 RETURNING thumbnail_path",
+                [missing.id],
+                |row| row.get(0)
+            )?;
+            if let Some(thumbnail_path) = thumbnail_path {
+                release_thumbnail_file(&tx, files, &thumbnail_path)?;
+            }
+        }
+        tx.commit()?;
+        report.repaired = true;
+    }
+    
+    Ok(report)
+}
+
+#[tauri::command]
+pub fn verify_integrity(repair: Option<bool>, app: AppHandle, state: State<AppState>) -> AppResult<IntegrityReport> {
+    let mut conn = db_conn(&state.db)?;
+    let report = check_integrity(&mut conn, &state.files, repair.unwrap_or(false))?;
+    
+    if report.repaired {
+        for missing in &report.missing_attachments {
+            if let Err(e) = state.search.delete_attachment(missing.id) {
+                error!("Failed to remove attachment {} from search index: {}", missing.id, e);
+            }
+        }
+        app.trigger_global("blocks-changed", None);
+    }
+    Ok(report)
+}
+
+// Move blocks, priorities and brain dumps dated before `date` into archive.db
+#[tauri::command]
+pub fn archive_before(date: String, app: AppHandle, state: State<AppState>) -> AppResult<crate::archive::ArchiveSummary> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    let summary = crate::archive::archive_before(&conn, state.files.get_data_dir(), &date)?;
+    
+    if let Err(e) = state.search.delete_time_blocks(&summary.block_ids) {
+        error!("Failed to remove archived blocks from search index: {}", e);
+    }
+    
+    app.trigger_global("blocks-changed", None);
+    Ok(summary)
+}
+
+#[tauri::command]
+pub fn search_archive(query: String, limit: Option<usize>, state: State<AppState>) -> AppResult<Vec<SearchResult>> {
+    crate::archive::search_archive(state.files.get_data_dir(), &state.files, &query, limit.unwrap_or(20))
+        .map_err(AppError::from)
+}
+
+// Index every block and its notes from scratch, e.g. after the index schema changed
+pub fn reindex_search(conn: &Connection, files: &crate::services::FileService, search: &crate::search::SearchService) -> anyhow::Result<usize> {
+    let mut stmt = conn.prepare(&format!("SELECT {} FROM time_blocks", TIME_BLOCK_COLUMNS))?;
+    let blocks = stmt.query_map([], TimeBlock::from_row)?
+        .collect::<rusqlite::Result<Vec<TimeBlock>>>()?;
+    
+    let mut documents = Vec::with_capacity(blocks.len());
+    for block in blocks {
+        let notes = match &block.notes_file {
+            Some(notes_file) => files.load_notes(notes_file).unwrap_or_default(),
+            None => String::new(),
+        };
+        documents.push((block, notes));
+    }
+    
+    // Text recognized in attachments is kept in the database, so it survives a rebuild
+    let mut stmt = conn.prepare("SELECT id, time_block_id, file_name, ocr_text FROM attachments")?;
+    let attachments = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?, row.get::<_, Option<String>>(3)?))
+    })?.collect::<rusqlite::Result<Vec<_>>>()?;
+    let blocks_by_id: std::collections::HashMap<i64, &TimeBlock> = documents.iter()
+        .filter_map(|(block, _)| Some((block.id?, block)))
+        .collect();
+    let attachment_documents: Vec<AttachmentDocument> = attachments.into_iter()
+        .filter_map(|(id, block_id, file_name, text)| Some(AttachmentDocument {
+            attachment_id: id,
+            time_block: (*blocks_by_id.get(&block_id)?).clone(),
+            file_name,
+            text: text.unwrap_or_default(),
+        }))
+        .collect();
+    
+    let mut stmt = conn.prepare("SELECT id, time_block_id, section_name, file_path FROM block_notes")?;
+    let sections = stmt.query_map([], |row| {
+        Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?))
+    })?.collect::<rusqlite::Result<Vec<_>>>()?;
+    let section_documents: Vec<NoteSectionDocument> = sections.into_iter()
+        .filter_map(|(id, block_id, section_name, file_path)| Some(NoteSectionDocument {
+            section_id: id,
+            time_block: (*blocks_by_id.get(&block_id)?).clone(),
+            section_name,
+            content: files.load_notes(&file_path).unwrap_or_default(),
+        }))
+        .collect();
+    
+    search.index_time_blocks(&documents)?;
+    search.index_attachments(&attachment_documents)?;
+    search.index_note_sections(&section_documents)?;
+    Ok(documents.len())
+}
+
+// Repopulate the search index on demand, e.g. after it was recreated. Returns the number of blocks indexed.
+#[tauri::command]
+pub fn reindex_all(state: State<AppState>) -> AppResult<usize> {
+    let conn = db_conn(&state.db)?;
+    reindex_search(&conn, &state.files, &state.search).map_err(AppError::from)
+}
+
+// Distinct tags across all blocks with usage counts, most used first
+pub fn count_tags(conn: &Connection) -> rusqlite::Result<Vec<TagCount>> {
+    let mut stmt = conn.prepare(
+        "SELECT t.name, COUNT(*) AS uses FROM tags t
+         JOIN block_tags bt ON bt.tag_id = t.id
+         GROUP BY t.id ORDER BY uses DESC, t.name"
+    )?;
+    
+    let tags = stmt.query_map([], |row| {
+        Ok(TagCount { tag: row.get(0)?, count: row.get(1)? })
+    })?.collect::<rusqlite::Result<Vec<TagCount>>>();
+    tags
+}
+
+pub fn load_blocks_by_tag(conn: &Connection, tag: &str, date_from: &str, date_to: &str) -> rusqlite::Result<Vec<TimeBlock>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM time_blocks
+         WHERE id IN (SELECT bt.time_block_id FROM block_tags bt JOIN tags t ON t.id = bt.tag_id WHERE t.name = ?1)
+           AND date >= ?2 AND date <= ?3
+         ORDER BY date, start_minutes",
+        TIME_BLOCK_COLUMNS
+    ))?;
+    
+    let blocks = stmt.query_map((tag, date_from, date_to), TimeBlock::from_row)?
+        .collect::<rusqlite::Result<Vec<TimeBlock>>>();
+    blocks
+}
+
+#[tauri::command]
+pub fn get_blocks_by_tag(
+    tag: String,
+    date_from: Option<String>,
+    date_to: Option<String>,
+    state: State<AppState>
+) -> AppResult<Vec<TimeBlock>> {
+    let conn = db_conn(&state.db)?;
+    load_blocks_by_tag(
+        &conn,
+        tag.trim().trim_start_matches('#'),
+        date_from.as_deref().unwrap_or("0000-01-01"),
+        date_to.as_deref().unwrap_or("9999-12-31"),
+    ).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn list_all_tags(state: State<AppState>) -> AppResult<Vec<TagCount>> {
+    let conn = db_conn(&state.db)?;
+    count_tags(&conn).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn suggest_tags(prefix: String, limit: Option<usize>, state: State<AppState>) -> AppResult<Vec<TagCount>> {
+    let conn = db_conn(&state.db)?;
+    let prefix = prefix.trim().trim_start_matches('#').to_lowercase();
+    
+    Ok(count_tags(&conn)?
+        .into_iter()
+        .filter(|t| t.tag.to_lowercase().starts_with(&prefix))
+        .take(limit.unwrap_or(10))
+        .collect())
+}
+
+// Replace `old` with `new` in every block's tags, or remove it when `new` is None.
+// Returns the blocks as changed so the caller can reindex them.
+fn retag_blocks(conn: &Connection, old: &str, new: Option<&str>) -> AppResult<Vec<TimeBlock>> {
+    let old = old.trim();
+    let new = new.map(str::trim);
+    if new == Some("") {
+        return Err(AppError::Validation("Tag name cannot be empty".to_string()));
+    }
+    if old.is_empty() || new == Some(old) {
+        return Ok(Vec::new());
+    }
+    
+    let tx = conn.unchecked_transaction()?;
+    let mut changed = Vec::new();
+    for mut block in load_blocks_by_tag(&tx, old, "0000-01-01", "9999-12-31")? {
+        let mut tags: Vec<String> = Vec::new();
+        for tag in &block.tags {
+            let tag = if tag.trim() == old { new } else { Some(tag.as_str()) };
+            // Renaming onto a tag the block already has leaves just one
+            if let Some(tag) = tag.filter(|tag| !tags.iter().any(|t| t == tag)) {
+                tags.push(tag.to_string());
+            }
+        }
+        
+        let tags_json = serde_json::to_string(&tags)?;
+        tx.execute(
+            "UPDATE time_blocks SET tags = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2",
+            (&tags_json, block.id),
+        )?;
+        sync_block_tags(&tx, block.id.unwrap_or_default(), &tags)?;
+        block.tags = tags;
+        changed.push(block);
+    }
+    tx.execute("DELETE FROM tags WHERE name = ?1", [old])?;
+    tx.commit()?;
+    
+    Ok(changed)
+}
+
+fn reindex_retagged(state: &AppState, blocks: Vec<TimeBlock>) {
+    let documents: Vec<(TimeBlock, String)> = blocks.into_iter()
+        .map(|block| {
+            let notes = block.notes_file.as_ref()
+                .and_then(|notes_file| state.files.load_notes(notes_file).ok())
+                .unwrap_or_default();
+            (block, notes)
+        })
+        .collect();
+    if let Err(e) = state.search.index_time_blocks(&documents) {
+        error!("Failed to index time blocks: {}", e);
+    }
+}
+
+// Rename a tag on every block that has it. Returns the number of blocks changed.
+#[tauri::command]
+pub fn rename_tag(old: String, new: String, app: AppHandle, state: State<AppState>) -> AppResult<usize> {
+    let conn = db_conn(&state.db)?;
+    let changed = retag_blocks(&conn, &old, Some(&new))?;
+    let count = changed.len();
+    
+    if count > 0 {
+        reindex_retagged(&state, changed);
+        app.trigger_global("blocks-changed", None);
+    }
+    Ok(count)
+}
+
+// Remove a tag from every block. Returns the number of blocks changed.
+#[tauri::command]
+pub fn delete_tag(tag: String, app: AppHandle, state: State<AppState>) -> AppResult<usize> {
+    let conn = db_conn(&state.db)?;
+    let changed = retag_blocks(&conn, &tag, None)?;
+    let count = changed.len();
+    
+    if count > 0 {
+        reindex_retagged(&state, changed);
+        app.trigger_global("blocks-changed", None);
+    }
+    Ok(count)
+}
+
+#[tauri::command]
+pub fn get_settings(state: State<AppState>) -> AppResult<std::collections::HashMap<String, String>> {
+    let conn = db_conn(&state.db)?;
+    let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
+    
+    let settings_iter = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+    })?;
+    
+    let mut settings = std::collections::HashMap::new();
+    for setting in settings_iter {
+        let (key, value) = setting?;
+        settings.insert(key, value);
+    }
+    
+    Ok(settings)
+}
+
+// Settings as a portable JSON profile for setting up another machine
+#[tauri::command]
+pub fn export_settings(include_sensitive: Option<bool>, state: State<AppState>) -> AppResult<String> {
+    let conn = db_conn(&state.db)?;
+    crate::settings::export_profile(&conn, include_sensitive.unwrap_or(false))
+}
+
+// Apply a profile from export_settings; an invalid profile changes nothing
+#[tauri::command]
+pub fn import_settings(json: String, state: State<AppState>) -> AppResult<usize> {
+    let mut conn = db_conn(&state.db)?;
+    crate::settings::import_profile(&mut conn, &json)
+}
+
+// The date from a launch deep link, returned once so the frontend can navigate on startup
+#[tauri::command]
+pub fn take_pending_navigation(pending: State<crate::deep_link::PendingNavigation>) -> Option<String> {
+    pending.0.lock().unwrap_or_else(|e| e.into_inner()).take()
+}
+
+#[tauri::command]
+pub fn get_data_directory(state: State<AppState>) -> AppResult<String> {
+    Ok(state.files.get_data_dir().to_string_lossy().into_owned())
+}
+
+// Takes effect on the next launch. An empty path switches back to the default location.
+// With `migrate`, the current database and files are copied to the new location first.
+#[tauri::command]
+pub fn set_data_directory(path: String, migrate: Option<bool>, app: AppHandle, state: State<AppState>) -> AppResult<()> {
+    let default_dir = app.path_resolver()
+        .app_data_dir()
+        .ok_or_else(|| AppError::Internal("Failed to get app data directory".to_string()))?;
+    
+    let path = path.trim();
+    let target = if path.is_empty() { None } else { Some(std::path::PathBuf::from(path)) };
+    let current_dir = state.files.get_data_dir();
+    
+    if migrate.unwrap_or(false) {
+        let destination = target.as_deref().unwrap_or(default_dir.as_path());
+        if destination != current_dir.as_path() {
+            let conn = db_conn(&state.db)?;
+            crate::vault::migrate_data(&conn, current_dir, destination)?;
+        }
+    }
+    
+    crate::vault::set_data_dir(&default_dir, target.as_deref())?;
+    crate::allow_attachment_assets(&app, target.as_deref().unwrap_or(default_dir.as_path()));
+    Ok(())
+}
+
+#[tauri::command]
+pub fn update_setting(key: String, value: String, state: State<AppState>) -> AppResult<()> {
+    crate::settings::validate_setting(&key, &value)?;
+    
+    let conn = db_conn(&state.db)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)",
+        (key, value),
+    )?;
+    
+    Ok(())
+}
+
+// The tag_colors setting: a JSON object of tag -> hex color, e.g. {"meeting": "#ef4444"}.
+// Tags are matched case-insensitively.
+pub(crate) fn parse_tag_colors(json: &str) -> AppResult<std::collections::HashMap<String, String>> {
+    let raw: std::collections::HashMap<String, String> = serde_json::from_str(json)
+        .map_err(|e| AppError::Validation(format!("tag_colors must map tags to colors: {}", e)))?;
+    
+    raw.into_iter()
+        .map(|(tag, color)| {
+            let hex = parse_hex_color(&color)
+                .ok_or_else(|| AppError::Validation(format!("Invalid hex color for tag '{}': {}", tag, color)))?;
+            Ok((tag.trim().to_lowercase(), hex))
+        })
+        .collect()
+}
+
+// The event_colors setting: Google colorId -> hex, e.g. {"11": "#b91c1c"}, overriding
+// Google's own palette. "default" colors events that have no colorId.
+pub(crate) fn parse_event_colors(json: &str) -> AppResult<std::collections::HashMap<String, String>> {
+    let raw: std::collections::HashMap<String, String> = serde_json::from_str(json)
+        .map_err(|e| AppError::Validation(format!("event_colors must map color ids to colors: {}", e)))?;
+    
+    raw.into_iter()
+        .map(|(color_id, color)| {
+            let hex = parse_hex_color(&color)
+                .ok_or_else(|| AppError::Validation(format!("Invalid hex color for event color '{}': {}", color_id, color)))?;
+            Ok((color_id.trim().to_string(), hex))
+        })
+        .collect()
+}
+
+pub fn event_colors(conn: &Connection) -> AppResult<std::collections::HashMap<String, String>> {
+    match read_setting(conn, "event_colors")? {
+        Some(json) => parse_event_colors(&json),
+        None => Ok(std::collections::HashMap::new()),
+    }
+}
+
+pub fn tag_colors(conn: &Connection) -> AppResult<std::collections::HashMap<String, String>> {
+    match read_setting(conn, "tag_colors")? {
+        Some(json) => parse_tag_colors(&json),
+        None => Ok(std::collections::HashMap::new()),
+    }
+}
+
+// Show each block in its tag's color, for display only. When several of a block's
+// tags have colors, the first in the block's own tag order wins; blocks without a
+// colored tag keep their stored color.
+pub fn apply_tag_colors(blocks: &mut [TimeBlock], colors: &std::collections::HashMap<String, String>) {
+    for block in blocks {
+        if let Some(color) = block.tags.iter().find_map(|tag| colors.get(&tag.trim().to_lowercase())) {
+            block.color = color.clone();
+        }
+    }
+}
+
+// Notes as sanitized HTML, rendered the same way as the HTML export
+#[tauri::command]
+pub fn render_notes_html(notes_file: String, state: State<AppState>) -> AppResult<String> {
+    let markdown = state.files.load_notes(&notes_file)?;
+    Ok(crate::markdown::render_html(&markdown))
+}
+
+#[tauri::command]
+pub fn export_day_markdown(date: String, state: State<AppState>) -> AppResult<String> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    crate::export::day_markdown(&conn, &state.files, &date)
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn export_day_html(date: String, state: State<AppState>) -> AppResult<String> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    crate::export::day_html(&conn, &state.files, &date)
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn export_range_html(date_from: String, date_to: String, state: State<AppState>) -> AppResult<String> {
+    let date_from = normalize_date(&date_from)?;
+    let date_to = normalize_date(&date_to)?;
+    let conn = db_conn(&state.db)?;
+    crate::export::range_html(&conn, &state.files, &date_from, &date_to)
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn export_csv(date_from: String, date_to: String, state: State<AppState>) -> AppResult<String> {
+    let date_from = normalize_date(&date_from)?;
+    let date_to = normalize_date(&date_to)?;
+    let conn = db_conn(&state.db)?;
+    crate::export::blocks_csv(&conn, &date_from, &date_to)
+        .map_err(AppError::from)
+}
+
+// Availability for sharing: busy periods from blocks and synced events, nothing else.
+// Events are loaded by overlap, so one that starts before date_from still counts.
+#[tauri::command]
+pub fn export_freebusy_ics(date_from: String, date_to: String, state: State<AppState>) -> AppResult<String> {
+    let date_from = normalize_date(&date_from)?;
+    let date_to = normalize_date(&date_to)?;
+    let events = state.calendar.get_events_for_date_range(&date_from, &date_to)?;
+    let conn = db_conn(&state.db)?;
+    crate::export::freebusy_ics(&conn, &events, &date_from, &date_to)
+        .map_err(AppError::from)
+}
+
+// A zip of the block's notes, attachments and a manifest. Written straight to
+// `destination_path` when given (returns None), otherwise returned as bytes.
+#[tauri::command]
+pub fn export_block_bundle(block_id: i64, destination_path: Option<String>, state: State<AppState>) -> AppResult<Option<Vec<u8>>> {
+    let conn = db_conn(&state.db)?;
+    match destination_path {
+        Some(path) => {
+            let file = std::fs::File::create(&path)?;
+            crate::export::block_bundle(&conn, &state.files, block_id, std::io::BufWriter::new(file))?;
+            Ok(None)
+        }
+        None => {
+            let cursor = crate::export::block_bundle(&conn, &state.files, block_id, std::io::Cursor::new(Vec::new()))?;
+            Ok(Some(cursor.into_inner()))
+        }
+    }
+}
+
+// Quick-added blocks land on the snap_minutes grid
+#[tauri::command]
+pub fn parse_quick_add(text: String, date: String, state: State<AppState>) -> AppResult<TimeBlock> {
+    let date = normalize_date(&date)?;
+    let mut block = crate::quick_add::parse_quick_add(&text, &date).map_err(AppError::Validation)?;
+    let conn = db_conn(&state.db)?;
+    let snap = snap_setting(&conn)?;
+    block.start_minutes = crate::scheduling::snap_start(block.start_minutes, block.duration_minutes, snap);
+    Ok(block)
+}
+
+const DEFAULT_SNAP_MINUTES: i32 = 5;
+
+// How finely blocks are placed, independent of the displayed grid (default_time_interval)
+fn snap_setting(conn: &Connection) -> rusqlite::Result<i32> {
+    let snap = read_setting_i32(conn, "snap_minutes", DEFAULT_SNAP_MINUTES)?;
+    Ok(if crate::scheduling::is_valid_snap(snap) { snap } else { DEFAULT_SNAP_MINUTES })
+}
+
+#[tauri::command]
+pub fn snap_start_minutes(value: i32, state: State<AppState>) -> AppResult<i32> {
+    let conn = db_conn(&state.db)?;
+    let snap = snap_setting(&conn)?;
+    Ok(crate::scheduling::snap_start(value, 0, snap))
+}
+
+pub fn read_setting(conn: &Connection, key: &str) -> rusqlite::Result<Option<String>> {
+    conn.query_row(
+        "SELECT value FROM settings WHERE key = ?1",
+        [key],
+        |row| row.get(0)
+    ).optional()
+}
+
+// The time_format setting: "12h" for "1:30 PM", anything else for "13:30"
+pub fn uses_twelve_hour_clock(conn: &Connection) -> rusqlite::Result<bool> {
+    Ok(read_setting(conn, "time_format")?.as_deref() == Some("12h"))
+}
+
+// Read a numeric setting, falling back to `default` when missing or malformed
+pub fn read_setting_i32(conn: &Connection, key: &str, default: i32) -> rusqlite::Result<i32> {
+    Ok(read_setting(conn, key)?
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(default))
+}
+
+pub(crate) const MINUTES_PER_DAY: i32 = 24 * 60;
+pub(crate) const WEEKDAYS: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+
+// Normalize a day_overrides key: a YYYY-MM-DD date or a weekday name ("Sat", "saturday")
+fn day_override_key(date_or_weekday: &str) -> AppResult<String> {
+    let value = date_or_weekday.trim();
+    if chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok() {
+        return Ok(value.to_string());
+    }
+    value.parse::<chrono::Weekday>()
+        .map(|weekday| WEEKDAYS[weekday.num_days_from_monday() as usize].to_string())
+        .map_err(|_| AppError::Validation(format!("Expected a YYYY-MM-DD date or a weekday, got '{}'", date_or_weekday)))
+}
+
+// Working hours for a date: its own override, then its weekday's, then the global settings
+pub fn effective_hours(conn: &Connection, date: &str) -> rusqlite::Result<TimeRange> {
+    let mut keys = vec![date.to_string()];
+    if let Ok(day) = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d") {
+        keys.push(WEEKDAYS[day.weekday().num_days_from_monday() as usize].to_string());
+    }
+    
+    for key in keys {
+        let hours = conn.query_row(
+            "SELECT start_minutes, end_minutes FROM day_overrides WHERE key = ?1",
+            [&key],
+            |row| Ok(TimeRange { start_minutes: row.get(0)?, end_minutes: row.get(1)? }),
+        ).optional()?;
+        if let Some(hours) = hours {
+            return Ok(hours);
+        }
+    }
+    
+    Ok(TimeRange {
+        start_minutes: read_setting_i32(conn, "work_hours_start", 480)?,
+        end_minutes: read_setting_i32(conn, "work_hours_end", 1020)?,
+    })
+}
+
+#[tauri::command]
+pub fn set_day_hours(date_or_weekday: String, start_minutes: i32, end_minutes: i32, state: State<AppState>) -> AppResult<()> {
+    if !(0..MINUTES_PER_DAY).contains(&start_minutes) || end_minutes > MINUTES_PER_DAY || start_minutes >= end_minutes {
+        return Err(AppError::Validation("Working hours must start before they end, within the day".to_string()));
+    }
+    let key = day_override_key(&date_or_weekday)?;
+    
+    let conn = db_conn(&state.db)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO day_overrides (key, start_minutes, end_minutes) VALUES (?1, ?2, ?3)",
+        (key, start_minutes, end_minutes),
+    )?;
+    Ok(())
+}
+
+#[tauri::command]
+pub fn get_effective_hours(date: String, state: State<AppState>) -> AppResult<TimeRange> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    effective_hours(&conn, &date).map_err(AppError::from)
+}
+
+// First day of the week from the week_start setting; anything but "sunday" means Monday
+fn week_start_setting(conn: &Connection) -> rusqlite::Result<chrono::Weekday> {
+    Ok(match read_setting(conn, "week_start")?.as_deref() {
+        Some("sunday") => chrono::Weekday::Sun,
+        _ => chrono::Weekday::Mon,
+    })
+}
+
+// Dates of the week containing `date`, starting on the week_start setting ("monday" or "sunday")
+#[tauri::command]
+pub fn get_week_dates(date: String, state: State<AppState>) -> AppResult<Vec<String>> {
+    let day = chrono::NaiveDate::parse_from_str(&normalize_date(&date)?, "%Y-%m-%d")
+        .map_err(|_| AppError::Validation(format!("Invalid date: {}", date)))?;
+    
+    let conn = db_conn(&state.db)?;
+    let week_start = week_start_setting(&conn)?;
+    
+    Ok(crate::scheduling::week_dates(day, week_start)
+        .iter()
+        .map(|day| day.to_string())
+        .collect())
+}
+
+#[tauri::command]
+pub fn find_free_slots(
+    date: String,
+    duration_minutes: i32,
+    include_events: Option<bool>,
+    state: State<AppState>
+) -> AppResult<Vec<TimeRange>> {
+    let date = normalize_date(&date)?;
+    if duration_minutes <= 0 {
+        return Err(AppError::Validation("Duration must be positive".to_string()));
+    }
+    
+    let mut busy: Vec<TimeRange> = if include_events.unwrap_or(true) {
+        state.calendar.get_events_for_date_range(&date, &date)?
+            .iter()
+            .filter_map(|event| crate::scheduling::event_minute_range(event, &date))
+            .collect()
+    } else {
+        Vec::new()
+    };
+    
+    let conn = db_conn(&state.db)?;
+    let work_hours = effective_hours(&conn, &date)?;
+    
+    busy.extend(load_time_blocks(&conn, &date)?
+        .iter()
+        .map(crate::scheduling::block_range));
+    
+    Ok(crate::scheduling::free_slots(work_hours, busy, duration_minutes))
+}
+
+#[tauri::command]
+pub fn get_block_conflicts(date: String, state: State<AppState>) -> AppResult<Vec<BlockConflict>> {
+    let date = normalize_date(&date)?;
+    let events = state.calendar.get_events_for_date_range(&date, &date)?;
+    
+    let conn = db_conn(&state.db)?;
+    let blocks = load_time_blocks(&conn, &date)?;
+    
+    Ok(crate::scheduling::find_conflicts(&blocks, &events, &date))
+}
+
+#[tauri::command]
+pub fn get_calendar_events_as_blocks(date: String, state: State<AppState>) -> AppResult<Vec<EventBlock>> {
+    let date = normalize_date(&date)?;
+    let events = state.calendar.get_events_for_date_range(&date, &date)?;
+    let conn = db_conn(&state.db)?;
+    Ok(crate::scheduling::event_blocks(&events, &date, &event_colors(&conn)?))
+}
+
+#[tauri::command]
+pub fn get_palette(state: State<AppState>) -> AppResult<Vec<PaletteColor>> {
+    let conn = db_conn(&state.db)?;
+    let palette_json = read_setting(&conn, "color_palettes")?;
+    
+    Ok(palette_json
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_else(default_palette))
+}
+
+#[tauri::command]
+pub fn save_palette(colors: Vec<PaletteColor>, state: State<AppState>) -> AppResult<Vec<PaletteColor>> {
+    let mut palette = Vec::new();
+    for color in colors {
+        let name = color.name.trim();
+        if name.is_empty() {
+            return Err(AppError::Validation("Palette color name cannot be empty".to_string()));
+        }
+        let hex = parse_hex_color(&color.hex)
+            .ok_or_else(|| AppError::Validation(format!("Invalid hex color for '{}': {}", name, color.hex)))?;
+        palette.push(PaletteColor { name: name.to_string(), hex });
+    }
+    
+    let palette_json = serde_json::to_string(&palette)?;
+    let conn = db_conn(&state.db)?;
+    conn.execute(
+        "INSERT OR REPLACE INTO settings (key, value) VALUES ('color_palettes', ?1)",
+        [palette_json],
+    )?;
+    
+    Ok(palette)
+}
+
+#[tauri::command]
+pub fn load_notes(notes_file: String, state: State<AppState>) -> AppResult<String> {
+    state.files.load_notes(&notes_file)
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn get_available_intervals(state: State<AppState>) -> AppResult<Vec<TimeInterval>> {
+    let conn = db_conn(&state.db)?;
+    let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = 'available_intervals'")?;
+    
+    let intervals_json = stmt.query_row([], |row| {
+        Ok(row.get::<_, String>(0)?)
+    })?;
+    
+    let intervals: Vec<i32> = serde_json::from_str(&intervals_json)
+        .unwrap_or(vec![5, 15, 30, 60]);
+    
+    let time_intervals: Vec<TimeInterval> = intervals.into_iter().map(TimeInterval::new).collect();
+    
+    Ok(time_intervals)
+}
+
+// Common block lengths offered when resizing, from the `duration_presets` setting
+#[tauri::command]
+pub fn get_duration_presets(state: State<AppState>) -> AppResult<Vec<TimeInterval>> {
+    let conn = db_conn(&state.db)?;
+    let presets: Vec<i32> = read_setting(&conn, "duration_presets")?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_else(|| vec![15, 30, 45, 60, 90, 120]);
+    
+    Ok(presets.into_iter()
+        .filter(|&minutes| minutes > 0)
+        .map(TimeInterval::new)
+        .collect())
+}
+
+#[tauri::command]
+pub fn save_attachment(
+    time_block_id: i64,
+    date: String,
+    file_data: Vec<u8>,
+    filename: String,
+    file_type: String,
+    state: State<AppState>
+) -> AppResult<SavedAttachment> {
+    let conn = db_conn(&state.db)?;
+    check_attachment_size(&conn, file_data.len() as u64)?;
+    let (file_type, mime_type) = resolve_file_type(&file_type, &file_data)?;
+    
+    let content_hash = crate::services::content_hash(&file_data);
+    // Unreadable headers leave the duration empty rather than failing the upload
+    let duration_seconds = if file_type == "audio" {
+        crate::media::audio_duration_seconds(&file_data)
+    } else {
+        None
+    };
+    // Reuse the stored copy if identical content was attached before
+    let existing_path: Option<String> = conn.query_row(
+        "SELECT file_path FROM attachments WHERE content_hash = ?1 LIMIT 1",
+        [&content_hash],
+        |row| row.get(0)
+    ).optional()?;
+    
+    let file_path = match existing_path {
+        Some(path) if state.files.get_data_dir().join(&path).exists() => path,
+        _ => state.files.save_attachment(time_block_id, &date, &file_data, &filename)?,
+    };
+    let thumbnail_path = attachment_thumbnail(&conn, &state.files, &file_path, &filename, &file_type, &file_data)?;
+    
+    let inserted = conn.execute(
+        "INSERT INTO attachments (time_block_id, file_path, file_name, file_type, file_size, content_hash, duration_seconds, thumbnail_path, mime_type) 
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+        (time_block_id, &file_path, &filename, &file_type, file_data.len() as i64, &content_hash, duration_seconds, &thumbnail_path, &mime_type)
+    );
+    if let Err(e) = inserted {
+        discard_attachment_files(&conn, &state.files, &file_path, thumbnail_path.as_deref());
+        return Err(e.into());
+    }
+    
+    let attachment_id = conn.last_insert_rowid();
+    if let Err(e) = index_attachment(&conn, &state.search, attachment_id) {
+        error!("Failed to index attachment {}: {}", attachment_id, e);
+    }
+    
+    // OCR is optional and best effort; reocr_attachment retries it
+    if file_type == "image" && crate::ocr::is_available() {
+        if let Err(e) = ocr_attachment(&conn, &state.search, attachment_id, &file_data) {
+            error!("Failed to extract text from attachment {}: {}", attachment_id, e);
+        }
+    }
+    
+    Ok(SavedAttachment { file_path, file_type, mime_type })
+}
+
+// Copy an attachment straight from disk (e.g. a path from the native file dialog)
+// instead of passing its bytes through the frontend
+#[tauri::command]
+pub fn save_attachment_from_path(
+    time_block_id: i64,
+    date: String,
+    source_path: String,
+    file_type: String,
+    state: State<AppState>
+) -> AppResult<SavedAttachment> {
+    let source = std::path::Path::new(&source_path);
+    let metadata = std::fs::metadata(source)
+        .map_err(|e| AppError::from(std::io::Error::new(e.kind(), format!("Cannot read {}: {}", source_path, e))))?;
+    if !metadata.is_file() {
+        return Err(AppError::Validation(format!("Not a file: {}", source_path)));
+    }
+    let filename = source.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| AppError::Validation(format!("Not a file: {}", source_path)))?;
+    
+    let conn = db_conn(&state.db)?;
+    check_attachment_size(&conn, metadata.len())?;
+    let header = read_file_header(source)?;
+    let (file_type, mime_type) = resolve_file_type(&file_type, &header)?;
+    
+    let (copied_path, content_hash) = state.files
+        .copy_attachment(time_block_id, &date, source, &filename)?;
+    
+    // Identical content attached before keeps pointing at the earlier copy
+    let existing_path: Option<String> = conn.query_row(
+        "SELECT file_path FROM attachments WHERE content_hash = ?1 AND file_path != ?2 LIMIT 1",
+        (&content_hash, &copied_path),
+        |row| row.get(0)
+    ).optional()?;
+    let file_path = match existing_path {
+        Some(path) if state.files.get_data_dir().join(&path).exists() => {
+            state.files.delete_attachment(&copied_path)?;
+            path
+        }
+        _ => copied_path,
+    };
+    
+    // Durations and OCR need the whole file in memory, which this path avoids. Images
+    // and renderable PDFs are read back for their thumbnail; other previews only need
+    // the header.
+    let renders = file_type == "image" || (mime_type == "application/pdf" && crate::media::pdf_rendering_available());
+    let preview_data = if renders { state.files.load_attachment(&file_path)? } else { header };
+    let thumbnail_path = attachment_thumbnail(&conn, &state.files, &file_path, &filename, &file_type, &preview_data)?;
+    let inserted = conn.execute(
+        "INSERT INTO attachments (time_block_id, file_path, file_name, file_type, file_size, content_hash, thumbnail_path, mime_type)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        (time_block_id, &file_path, &filename, &file_type, metadata.len() as i64, &content_hash, &thumbnail_path, &mime_type)
+    );
+    if let Err(e) = inserted {
+        discard_attachment_files(&conn, &state.files, &file_path, thumbnail_path.as_deref());
+        return Err(e.into());
+    }
+    
+    let attachment_id = conn.last_insert_rowid();
+    if let Err(e) = index_attachment(&conn, &state.search, attachment_id) {
+        error!("Failed to index attachment {}: {}", attachment_id, e);
+    }
+    
+    Ok(SavedAttachment { file_path, file_type, mime_type })
+}
+
+// Save an attachment the frontend only has as a `data:<mime>;base64,<payload>` URL,
+// e.g. an image pasted from the clipboard
+#[tauri::command]
+pub fn save_attachment_data_url(
+    time_block_id: i64,
+    date: String,
+    data_url: String,
+    state: State<AppState>
+) -> AppResult<SavedAttachment> {
+    let (mime_type, payload) = split_data_url(&data_url)?;
+    {
+        let conn = db_conn(&state.db)?;
+        // Decoded data is about three quarters of the base64 length; reject before decoding
+        check_attachment_size(&conn, payload.len() as u64 / 4 * 3)?;
+    }
+    
+    use base64::Engine;
+    let file_data = base64::engine::general_purpose::STANDARD.decode(payload.trim())
+        .map_err(|e| AppError::Validation(format!("Invalid data URL payload: {}", e)))?;
+    
+    let filename = format!("pasted-{}.{}", chrono::Local::now().format("%Y%m%d-%H%M%S-%3f"), mime_extension(&mime_type));
+    let file_type = match mime_type.split('/').next() {
+        Some("image") => "image",
+        Some("audio") => "audio",
+        _ => "document",
+    };
+    save_attachment(time_block_id, date, file_data, filename, file_type.to_string(), state)
+}
+
+// The MIME type and base64 payload of a data URL. Only base64 data URLs are accepted.
+fn split_data_url(data_url: &str) -> AppResult<(String, &str)> {
+    let invalid = || AppError::Validation("Not a base64 data URL (expected data:<type>;base64,...)".to_string());
+    let rest = data_url.strip_prefix("data:").ok_or_else(invalid)?;
+    let (header, payload) = rest.split_once(',').ok_or_else(invalid)?;
+    
+    let mut params = header.split(';');
+    let mime_type = params.next().unwrap_or_default().trim().to_ascii_lowercase();
+    if !params.any(|param| param.trim().eq_ignore_ascii_case("base64")) {
+        return Err(invalid());
+    }
+    // A missing type means text/plain per RFC 2397
+    let mime_type = if mime_type.is_empty() { "text/plain".to_string() } else { mime_type };
+    if !mime_type.contains('/') || payload.trim().is_empty() {
+        return Err(invalid());
+    }
+    Ok((mime_type, payload))
+}
+
+// File extension for a MIME type, e.g. "image/svg+xml" -> "svg"
+fn mime_extension(mime_type: &str) -> String {
+    match mime_type {
+        "image/jpeg" => "jpg".to_string(),
+        "audio/mpeg" => "mp3".to_string(),
+        "text/plain" => "txt".to_string(),
+        "text/markdown" => "md".to_string(),
+        _ => {
+            let subtype = mime_type.split('/').nth(1).unwrap_or_default();
+            let extension = subtype.split('+').next().unwrap_or_default().trim_start_matches("x-");
+            if !extension.is_empty() && extension.chars().all(|c| c.is_ascii_alphanumeric()) {
+                extension.to_string()
+            } else {
+                "bin".to_string()
+            }
+        }
+    }
+}
+
+// Enough of a file to recognize it by its magic bytes
+const FILE_HEADER_BYTES: u64 = 8192;
+
+fn read_file_header(path: &std::path::Path) -> AppResult<Vec<u8>> {
+    use std::io::Read;
+    let mut header = Vec::new();
+    std::fs::File::open(path)?.take(FILE_HEADER_BYTES).read_to_end(&mut header)?;
+    Ok(header)
+}
+
+// The attachment type and MIME type to store. The content wins over the caller's
+// claim; content we can't recognize is kept as a generic document.
+fn resolve_file_type(claimed: &str, header: &[u8]) -> AppResult<(String, String)> {
+    match crate::media::detect_file_type(header) {
+        Ok(Some((mime_type, file_type))) => {
+            if file_type != claimed {
+                warn!("Attachment claimed to be '{}' but its content is {}", claimed, mime_type);
+            }
+            Ok((file_type.to_string(), mime_type.to_string()))
+        }
+        Ok(None) => Ok(("document".to_string(), "application/octet-stream".to_string())),
+        Err(mime_type) => Err(AppError::Validation(format!("Attachments of type {} are not allowed", mime_type))),
+    }
+}
+
+fn check_attachment_size(conn: &Connection, size: u64) -> AppResult<()> {
+    let max_mb = read_setting_i32(conn, "max_attachment_size_mb", 100)?;
+    if max_mb > 0 && size > max_mb as u64 * 1024 * 1024 {
+        return Err(AppError::Validation(format!("Attachment is larger than the {} MB limit", max_mb)));
+    }
+    Ok(())
+}
+
+// Run OCR over an image attachment, storing and indexing any text found
+fn ocr_attachment(
+    conn: &Connection,
+    search: &crate::search::SearchService,
+    attachment_id: i64,
+    image_data: &[u8]
+) -> anyhow::Result<Option<String>> {
+    let text = crate::ocr::extract_text(image_data)?;
+    conn.execute("UPDATE attachments SET ocr_text = ?1 WHERE id = ?2", (&text, attachment_id))?;
+    
+    index_attachment(conn, search, attachment_id)?;
+    Ok(text)
+}
+
+// (Re)index an attachment's file name and extracted text under its block
+fn index_attachment(conn: &Connection, search: &crate::search::SearchService, attachment_id: i64) -> anyhow::Result<()> {
+    let (time_block_id, file_name, text): (i64, String, Option<String>) = conn.query_row(
+        "SELECT time_block_id, file_name, ocr_text FROM attachments WHERE id = ?1",
+        [attachment_id],
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+    )?;
+    
+    match load_time_block(conn, time_block_id)? {
+        Some(time_block) => search.index_attachments(&[AttachmentDocument {
+            attachment_id,
+            time_block,
+            file_name,
+            text: text.unwrap_or_default(),
+        }]),
+        None => search.delete_attachment(attachment_id),
+    }
+}
+
+// Retry OCR for an image attachment. Returns the recognized text, if any.
+#[tauri::command]
+pub fn reocr_attachment(id: i64, state: State<AppState>) -> AppResult<Option<String>> {
+    let conn = db_conn(&state.db)?;
+    let (file_path, file_type): (String, String) = conn.query_row(
+        "SELECT file_path, file_type FROM attachments WHERE id = ?1",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?))
+    ).optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Attachment not found: {}", id)))?;
+    
+    if file_type != "image" {
+        return Err(AppError::Validation(format!("Attachment {} is not an image", id)));
+    }
+    
+    let image_data = state.files.load_attachment(&file_path)?;
+    ocr_attachment(&conn, &state.search, id, &image_data)
+        .map_err(AppError::from)
+}
+
+// Remove an attachment's file from disk once no attachment row references it.
+// Deduplicated attachments share a file_path, so this also covers rows sharing a hash.
+fn release_attachment_file(conn: &Connection, files: &crate::services::FileService, file_path: &str) -> anyhow::Result<()> {
+    let references: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM attachments WHERE file_path = ?1",
+        [file_path],
+        |row| row.get(0)
+    )?;
+    
+    if references == 0 {
+        files.delete_attachment(file_path)?;
+    }
+    
+    Ok(())
+}
+
+// Generated thumbnails are shared like the files they preview
+fn release_thumbnail_file(conn: &Connection, files: &crate::services::FileService, thumbnail_path: &str) -> anyhow::Result<()> {
+    if thumbnail_path.starts_with("builtin:") {
+        return Ok(());
+    }
+    let references: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM attachments WHERE thumbnail_path = ?1",
+        [thumbnail_path],
+        |row| row.get(0)
+    )?;
+    
+    if references == 0 {
+        files.delete_attachment(thumbnail_path)?;
+    }
+    
+    Ok(())
+}
+
+// Clean up after an attachment row that couldn't be inserted (e.g. its block is gone),
+// so the file and thumbnail written for it don't linger. Shared copies are kept.
+fn discard_attachment_files(conn: &Connection, files: &crate::services::FileService, file_path: &str, thumbnail_path: Option<&str>) {
+    if let Some(thumbnail_path) = thumbnail_path {
+        if let Err(e) = release_thumbnail_file(conn, files, thumbnail_path) {
+            error!("Failed to remove thumbnail {}: {}", thumbnail_path, e);
+        }
+    }
+    if let Err(e) = release_attachment_file(conn, files, file_path) {
+        error!("Failed to remove attachment file {}: {}", file_path, e);
+    }
+}
+
+#[tauri::command]
+pub fn delete_attachment(attachment_id: i64, state: State<AppState>) -> AppResult<()> {
+    let conn = db_conn(&state.db)?;
+    let (file_path, thumbnail_path): (String, Option<String>) = conn.query_row(
+        "SELECT file_path, thumbnail_path FROM attachments WHERE id = ?1",
+        [attachment_id],
+        |row| Ok((row.get(0)?, row.get(1)?))
+    ).optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Attachment not found: {}", attachment_id)))?;
+    
+    conn.execute("DELETE FROM attachments WHERE id = ?1", [attachment_id])?;
+    if let Err(e) = state.search.delete_attachment(attachment_id) {
+        error!("Failed to remove attachment from search index: {}", e);
+    }
+    if let Some(thumbnail_path) = &thumbnail_path {
+        release_thumbnail_file(&conn, &state.files, thumbnail_path)?;
+    }
+    release_attachment_file(&conn, &state.files, &file_path)
+        .map_err(AppError::from)
+}
+
+// Preview for an image or PDF (a PNG stored next to it), or a document placeholder
+fn generate_thumbnail(
+    files: &crate::services::FileService,
+    file_path: &str,
+    file_name: &str,
+    file_type: &str,
+    data: &[u8]
+) -> anyhow::Result<Option<String>> {
+    Ok(match file_type {
+        "image" => match crate::media::image_thumbnail(data) {
+            Some(png) => Some(files.save_thumbnail(file_path, &png)?),
+            None => None,
+        },
+        "document" => match crate::media::pdf_thumbnail(file_name, data) {
+            Some(png) => Some(files.save_thumbnail(file_path, &png)?),
+            None => crate::media::document_thumbnail(file_name, data),
+        },
+        _ => None,
+    })
+}
+
+// Thumbnail for a newly saved attachment. A deduplicated file reuses the preview its
+// other rows already have; generating one is best effort and never fails the upload.
+fn attachment_thumbnail(
+    conn: &Connection,
+    files: &crate::services::FileService,
+    file_path: &str,
+    file_name: &str,
+    file_type: &str,
+    data: &[u8]
+) -> AppResult<Option<String>> {
+    let shared: Option<String> = conn.query_row(
+        "SELECT thumbnail_path FROM attachments WHERE file_path = ?1 AND thumbnail_path IS NOT NULL LIMIT 1",
+        [file_path],
+        |row| row.get(0)
+    ).optional()?;
+    if shared.is_some() {
+        return Ok(shared);
+    }
+    match generate_thumbnail(files, file_path, file_name, file_type, data) {
+        Ok(thumbnail_path) => Ok(thumbnail_path),
+        Err(e) => {
+            error!("Failed to generate thumbnail for {}: {}", file_path, e);
+            Ok(None)
+        }
+    }
+}
+
+// Generate thumbnails for attachments saved before thumbnails existed. Each one is
+// stored as it's made, so an interrupted run picks up where it left off, and
+// attachments that already have one are never redone.
+pub fn backfill_thumbnails_for(
+    conn: &Connection,
+    files: &crate::services::FileService,
+    mut on_progress: impl FnMut(BackfillProgress)
+) -> anyhow::Result<ThumbnailBackfill> {
+    let pending: Vec<(i64, String, String, String)> = {
+        let mut stmt = conn.prepare(
+            "SELECT id, file_path, file_name, file_type FROM attachments
+             WHERE thumbnail_path IS NULL AND file_type IN ('image', 'document') ORDER BY id"
+        )?;
+        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)))?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        rows
+    };
+    
+    let mut summary = ThumbnailBackfill::default();
+    for (index, (id, file_path, file_name, file_type)) in pending.iter().enumerate() {
+        let data = match files.load_attachment(file_path) {
+            Ok(data) => data,
+            Err(_) => {
+                warn!("Attachment {} is missing its file {}; candidate for cleanup", id, file_path);
+                summary.missing.push(file_path.clone());
+                on_progress(BackfillProgress { processed: index + 1, total: pending.len() });
+                continue;
+            }
+        };
+        
+        match generate_thumbnail(files, file_path, file_name, file_type, &data)? {
+            Some(thumbnail_path) => {
+                conn.execute("UPDATE attachments SET thumbnail_path = ?1 WHERE id = ?2", (&thumbnail_path, id))?;
+                summary.generated += 1;
+            }
+            None => summary.unsupported += 1,
+        }
+        on_progress(BackfillProgress { processed: index + 1, total: pending.len() });
+    }
+    
+    Ok(summary)
+}
+
+// Emits "thumbnail-backfill-progress" after each attachment
+#[tauri::command]
+pub fn backfill_thumbnails(app: AppHandle, state: State<AppState>) -> AppResult<ThumbnailBackfill> {
+    let conn = db_conn(&state.db)?;
+    backfill_thumbnails_for(&conn, &state.files, |progress| {
+        let _ = app.emit_all("thumbnail-backfill-progress", progress);
+    }).map_err(AppError::from)
+}
+
+// Rename an attachment's display name and its file on disk. Deduplicated rows
+// share the file, so every row pointing at it follows the new path.
+#[tauri::command]
+pub fn rename_attachment(id: i64, new_name: String, state: State<AppState>) -> AppResult<String> {
+    let new_name = new_name.trim().to_string();
+    let conn = db_conn(&state.db)?;
+    let (time_block_id, file_path): (i64, String) = conn.query_row(
+        "SELECT time_block_id, file_path FROM attachments WHERE id = ?1",
+        [id],
+        |row| Ok((row.get(0)?, row.get(1)?))
+    ).optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Attachment not found: {}", id)))?;
+    
+    let tx = conn.unchecked_transaction()?;
+    let new_path = state.files.rename_attachment(&file_path, time_block_id, &new_name)?;
+    
+    let updated = tx.execute(
+        "UPDATE attachments SET file_path = ?1 WHERE file_path = ?2",
+        (&new_path, &file_path)
+    ).and_then(|_| tx.execute(
+        "UPDATE attachments SET file_name = ?1 WHERE id = ?2",
+        (&new_name, id)
+    )).and_then(|_| tx.commit());
+    
+    // Put the file back if the rows couldn't be updated, so they never diverge
+    if let Err(e) = updated {
+        if let Err(restore_err) = state.files.move_attachment(&new_path, &file_path) {
+            error!("Failed to restore attachment {}: {}", file_path, restore_err);
+        }
+        return Err(e.into());
+    }
+    
+    if let Err(e) = index_attachment(&conn, &state.search, id) {
+        error!("Failed to index attachment {}: {}", id, e);
+    }
+    Ok(new_path)
+}
+
+// Move an attachment to another block, e.g. one attached to the wrong block. The file
+// moves into the target's date directory under its name. Returns the new relative path.
+fn reparent_attachment(
+    conn: &Connection,
+    files: &crate::services::FileService,
+    attachment_id: i64,
+    new_block_id: i64
+) -> AppResult<String> {
+    let attachment = conn.query_row(
+        &format!("SELECT {} FROM attachments a WHERE a.id = ?1", ATTACHMENT_COLUMNS),
+        [attachment_id],
+        attachment_from_row
+    ).optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Attachment not found: {}", attachment_id)))?;
+    let target = load_time_block(conn, new_block_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Time block not found: {}", new_block_id)))?;
+    if attachment.time_block_id == new_block_id {
+        return Ok(attachment.file_path);
+    }
+    
+    let tx = conn.unchecked_transaction()?;
+    let moves = reassign_attachment(&tx, files, &attachment, new_block_id, &target.date)?;
+    if let Err(e) = tx.commit() {
+        for (from, to) in &moves {
+            let _ = files.move_attachment(to, from);
+        }
+        return Err(e.into());
+    }
+    Ok(moves[0].1.clone())
+}
+
+#[tauri::command]
+pub fn move_attachment_to_block(attachment_id: i64, new_block_id: i64, app: AppHandle, state: State<AppState>) -> AppResult<String> {
+    let conn = db_conn(&state.db)?;
+    let new_path = reparent_attachment(&conn, &state.files, attachment_id, new_block_id)?;
+    
+    if let Err(e) = index_attachment(&conn, &state.search, attachment_id) {
+        error!("Failed to index attachment {}: {}", attachment_id, e);
+    }
+    app.trigger_global("blocks-changed", None);
+    Ok(new_path)
+}
+
+#[tauri::command]
+pub fn get_storage_stats(state: State<AppState>) -> AppResult<StorageStats> {
+    let conn = db_conn(&state.db)?;
+    
+    let (attachment_count, total_bytes): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(file_size), 0) FROM attachments",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?))
+    )?;
+    
+    let (unique_files, stored_bytes): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(size), 0) FROM 
+         (SELECT MAX(file_size) AS size FROM attachments GROUP BY file_path)",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?))
+    )?;
+    
+    Ok(StorageStats {
+        attachment_count,
+        unique_files,
+        total_bytes,
+        stored_bytes,
+        bytes_saved: total_bytes - stored_bytes,
+    })
+}
+
+#[tauri::command]
+pub fn get_attachment_metadata(id: i64, state: State<AppState>) -> AppResult<AttachmentMetadata> {
+    let conn = db_conn(&state.db)?;
+    conn.query_row(
+        "SELECT id, file_name, file_type, file_size, duration_seconds FROM attachments WHERE id = ?1",
+        [id],
+        |row| Ok(AttachmentMetadata {
+            id: row.get(0)?,
+            file_name: row.get(1)?,
+            file_type: row.get(2)?,
+            file_size: row.get(3)?,
+            duration_seconds: row.get(4)?,
+        })
+    ).optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Attachment not found: {}", id)))
+}
+
+const ATTACHMENT_COLUMNS: &str =
+    "a.id, a.time_block_id, a.file_path, a.file_name, a.file_type, a.file_size, a.created_at, a.content_hash, a.duration_seconds, a.thumbnail_path, a.mime_type";
+
+// Map a row selected with ATTACHMENT_COLUMNS into an Attachment
+fn attachment_from_row(row: &Row) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        id: row.get(0)?,
+        time_block_id: row.get(1)?,
+        file_path: row.get(2)?,
+        file_name: row.get(3)?,
+        file_type: row.get(4)?,
+        file_size: row.get(5)?,
+        created_at: row.get::<_, Option<String>>(6)?.map(|ts| to_rfc3339(&ts)),
+        content_hash: row.get(7)?,
+        duration_seconds: row.get(8)?,
+        thumbnail_path: row.get(9)?,
+        mime_type: row.get(10)?,
+    })
+}
+
+pub fn query_attachments(conn: &Connection, time_block_id: i64) -> rusqlite::Result<Vec<Attachment>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM attachments a WHERE a.time_block_id = ?1 ORDER BY a.created_at DESC, a.id DESC",
+        ATTACHMENT_COLUMNS
+    ))?;
+    
+    let attachments = stmt.query_map([time_block_id], attachment_from_row)?
+        .collect::<rusqlite::Result<Vec<Attachment>>>();
+    attachments
+}
+
+pub fn query_attachment_page(conn: &Connection, time_block_id: i64, limit: usize, offset: usize) -> rusqlite::Result<AttachmentPage> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM attachments a WHERE a.time_block_id = ?1 ORDER BY a.created_at DESC, a.id DESC LIMIT ?2 OFFSET ?3",
+        ATTACHMENT_COLUMNS
+    ))?;
+    let attachments = stmt.query_map((time_block_id, limit as i64, offset as i64), attachment_from_row)?
+        .collect::<rusqlite::Result<Vec<Attachment>>>()?;
+    let total = conn.query_row(
+        "SELECT COUNT(*) FROM attachments WHERE time_block_id = ?1",
+        [time_block_id],
+        |row| row.get(0)
+    )?;
+    Ok(AttachmentPage { attachments, total })
+}
+
+// Every attachment on a day's blocks, by block start time then upload order. Images
+// carry their own file as the thumbnail; other types have no thumbnail path.
+pub fn query_day_attachments(conn: &Connection, date: &str) -> rusqlite::Result<Vec<Attachment>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM attachments a JOIN time_blocks b ON b.id = a.time_block_id
+         WHERE b.date = ?1 ORDER BY b.start_minutes, a.created_at, a.id",
+        ATTACHMENT_COLUMNS
+    ))?;
+    
+    let attachments = stmt.query_map([date], attachment_from_row)?
+        .map(|attachment| attachment.map(|mut attachment| {
+            attachment.thumbnail_path = if attachment.file_type == "image" {
+                attachment.thumbnail_path.take().or_else(|| Some(attachment.file_path.clone()))
+            } else {
+                None
+            };
+            attachment
+        }))
+        .collect::<rusqlite::Result<Vec<Attachment>>>();
+    attachments
+}
+
+#[tauri::command]
+pub fn get_day_attachments(date: String, state: State<AppState>) -> AppResult<Vec<Attachment>> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    query_day_attachments(&conn, &date).map_err(AppError::from)
+}
+
+const DEFAULT_ATTACHMENT_PAGE: usize = 100;
+const MAX_ATTACHMENT_PAGE: usize = 500;
+
+// Pages through a block's attachments so the gallery can load incrementally.
+// Without arguments this is the first page of DEFAULT_ATTACHMENT_PAGE.
+#[tauri::command]
+pub fn get_attachments(
+    time_block_id: i64,
+    limit: Option<usize>,
+    offset: Option<usize>,
+    state: State<AppState>
+) -> AppResult<AttachmentPage> {
+    let limit = limit.unwrap_or(DEFAULT_ATTACHMENT_PAGE).clamp(1, MAX_ATTACHMENT_PAGE);
+    let conn = db_conn(&state.db)?;
+    query_attachment_page(&conn, time_block_id, limit, offset.unwrap_or(0)).map_err(AppError::from)
+}
+
+// Returns the whole file over IPC, so only use this for small attachments.
+// Large files (PDFs, audio) should go through get_attachment_path and the asset protocol.
+#[tauri::command]
+pub fn load_attachment(file_path: String, state: State<AppState>) -> AppResult<Vec<u8>> {
+    state.files.load_attachment(&file_path).map_err(AppError::from)
+}
+
+#[tauri::command]
+pub fn load_thumbnail(attachment_id: i64, state: State<AppState>) -> AppResult<Option<Thumbnail>> {
+    let conn = db_conn(&state.db)?;
+    let thumbnail_path: Option<String> = conn.query_row(
+        "SELECT thumbnail_path FROM attachments WHERE id = ?1",
+        [attachment_id],
+        |row| row.get(0)
+    ).optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Attachment not found: {}", attachment_id)))?;
+    
+    match thumbnail_path.as_deref() {
+        None => Ok(None),
+        Some(crate::media::GENERIC_DOCUMENT_THUMBNAIL) => Ok(Some(Thumbnail {
+            mime_type: "image/svg+xml".to_string(),
+            data: crate::media::GENERIC_DOCUMENT_SVG.as_bytes().to_vec(),
+        })),
+        Some(path) => {
+            let data = state.files.load_attachment(path)?;
+            Ok(Some(Thumbnail { mime_type: "image/png".to_string(), data }))
+        }
+    }
+}
+
+// Absolute path for convertFileSrc, letting the webview stream the file from disk
+#[tauri::command]
+pub fn get_attachment_path(file_path: String, state: State<AppState>) -> AppResult<String> {
+    let path = state.files.attachment_path(&file_path)?;
+    Ok(path.to_string_lossy().into_owned())
+}
+
+#[tauri::command]
+pub fn get_time_block_notes(block_id: i64, state: State<AppState>) -> AppResult<String> {
+    let conn = db_conn(&state.db)?;
+    
+    // Get the notes file path for this block
+    let notes_file: Option<String> = conn.query_row(
+        "SELECT notes_file FROM time_blocks WHERE id = ?1",
+        [block_id],
+        |row| row.get(0)
+    )?;
+    
+    if let Some(file_path) = notes_file {
+        // Load the notes content from file
+        match state.files.load_notes(&file_path) {
+            Ok(content) => Ok(content),
+            Err(_) => Ok(String::new()) // Return empty string if file doesn't exist
+        }
+    } else {
+        Ok(String::new()) // No notes file associated with this block
+    }
+}
+
+// Write just a block's notes, creating the file if it had none. Returns the block as updated.
+fn write_block_notes(
+    conn: &Connection,
+    files: &crate::services::FileService,
+    block_id: i64,
+    content: &str
+) -> AppResult<TimeBlock> {
+    let mut block = load_time_block(conn, block_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Time block not found: {}", block_id)))?;
+    
+    // Bring a stale file name in line first so the write doesn't leave it orphaned
+    if let Some(notes_file) = &block.notes_file {
+        block.notes_file = Some(files.rename_notes(notes_file, &block)?);
+    }
+    let notes_path = files.save_notes(&block, content)?;
+    
+    let updated_at: Option<String> = conn.query_row(
+        "UPDATE time_blocks SET notes_file = ?1, updated_at = CURRENT_TIMESTAMP WHERE id = ?2 RETURNING updated_at",
+        (&notes_path, block_id),
+        |row| row.get(0)
+    )?;
+    block.notes_file = Some(notes_path);
+    block.updated_at = updated_at.map(|ts| crate::models::to_rfc3339(&ts));
+    Ok(block)
+}
+
+// Focused save for the notes editor; leaves the block's title, time and color alone
+#[tauri::command]
+pub fn save_block_notes(block_id: i64, content: String, app: AppHandle, state: State<AppState>) -> AppResult<String> {
+    let conn = db_conn(&state.db)?;
+    let block = write_block_notes(&conn, &state.files, block_id, &content)?;
+    
+    if let Err(e) = state.search.index_time_block(&block, &content) {
+        error!("Failed to index time block: {}", e);
+    }
+    
+    app.trigger_global("blocks-changed", None);
+    Ok(block.notes_file.unwrap_or_default())
+}
+
+fn note_section_paths(conn: &Connection, block_id: i64) -> rusqlite::Result<Vec<String>> {
+    let mut stmt = conn.prepare("SELECT file_path FROM block_notes WHERE time_block_id = ?1")?;
+    let paths = stmt.query_map([block_id], |row| row.get(0))?
+        .collect::<rusqlite::Result<Vec<String>>>();
+    paths
+}
+
+fn note_section_name(section: &str) -> AppResult<String> {
+    let section = section.trim();
+    if section.is_empty() {
+        return Err(AppError::Validation("Section name can't be empty".to_string()));
+    }
+    if section.chars().count() > 64 {
+        return Err(AppError::Validation("Section name can be at most 64 characters".to_string()));
+    }
+    Ok(section.to_string())
+}
+
+// The block's sections, "default" (its notes_file) first if it has notes, then by name
+pub fn list_note_sections(conn: &Connection, block_id: i64) -> AppResult<Vec<NoteSection>> {
+    let block = load_time_block(conn, block_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Time block not found: {}", block_id)))?;
+    
+    let mut sections: Vec<NoteSection> = block.notes_file.into_iter()
+        .map(|file_path| NoteSection {
+            section_name: DEFAULT_NOTE_SECTION.to_string(),
+            file_path,
+            updated_at: block.updated_at.clone(),
+        })
+        .collect();
+    let mut stmt = conn.prepare(
+        "SELECT section_name, file_path, updated_at FROM block_notes WHERE time_block_id = ?1 ORDER BY section_name"
+    )?;
+    let named = stmt.query_map([block_id], |row| Ok(NoteSection {
+        section_name: row.get(0)?,
+        file_path: row.get(1)?,
+        updated_at: row.get::<_, Option<String>>(2)?.map(|ts| crate::models::to_rfc3339(&ts)),
+    }))?;
+    for section in named {
+        sections.push(section?);
+    }
+    Ok(sections)
+}
+
+// Write one notes section of a block. "default" is the block's own notes; other
+// sections get a file and a block_notes row on first save. Returns the block, the
+// section and, for named sections, its row id.
+fn write_note_section(
+    conn: &Connection,
+    files: &crate::services::FileService,
+    block_id: i64,
+    section: &str,
+    content: &str
+) -> AppResult<(TimeBlock, NoteSection, Option<i64>)> {
+    let section_name = note_section_name(section)?;
+    if section_name == DEFAULT_NOTE_SECTION {
+        let block = write_block_notes(conn, files, block_id, content)?;
+        let saved = NoteSection {
+            section_name,
+            file_path: block.notes_file.clone().unwrap_or_default(),
+            updated_at: block.updated_at.clone(),
+        };
+        return Ok((block, saved, None));
+    }
+    
+    let block = load_time_block(conn, block_id)?
+        .ok_or_else(|| AppError::NotFound(format!("Time block not found: {}", block_id)))?;
+    let existing: Option<String> = conn.query_row(
+        "SELECT file_path FROM block_notes WHERE time_block_id = ?1 AND section_name = ?2",
+        (block_id, &section_name),
+        |row| row.get(0)
+    ).optional()?;
+    let file_path = files.save_note_section(&block, &section_name, existing.as_deref(), content)?;
+    
+    let (id, updated_at) = conn.query_row(
+        "INSERT INTO block_notes (time_block_id, section_name, file_path) VALUES (?1, ?2, ?3)
+         ON CONFLICT(time_block_id, section_name) DO UPDATE SET file_path = excluded.file_path, updated_at = CURRENT_TIMESTAMP
+         RETURNING id, updated_at",
+        (block_id, &section_name, &file_path),
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?))
+    )?;
+    let updated_at = updated_at.map(|ts| crate::models::to_rfc3339(&ts));
+    Ok((block, NoteSection { section_name, file_path, updated_at }, Some(id)))
+}
+
+#[tauri::command]
+pub fn save_block_note_section(
+    block_id: i64,
+    section: String,
+    content: String,
+    app: AppHandle,
+    state: State<AppState>
+) -> AppResult<NoteSection> {
+    let conn = db_conn(&state.db)?;
+    let (block, saved, section_id) = write_note_section(&conn, &state.files, block_id, &section, &content)?;
+    
+    let indexed = match section_id {
+        Some(section_id) => state.search.index_note_sections(&[NoteSectionDocument {
+            section_id,
+            time_block: block,
+            section_name: saved.section_name.clone(),
+            content,
+        }]),
+        None => state.search.index_time_block(&block, &content),
+    };
+    if let Err(e) = indexed {
+        error!("Failed to index notes section: {}", e);
+    }
+    
+    app.trigger_global("blocks-changed", None);
+    Ok(saved)
+}
+
+#[tauri::command]
+pub fn list_block_note_sections(block_id: i64, state: State<AppState>) -> AppResult<Vec<NoteSection>> {
+    let conn = db_conn(&state.db)?;
+    list_note_sections(&conn, block_id)
+}
+
+#[tauri::command]
+pub fn load_block_note_section(block_id: i64, section: String, state: State<AppState>) -> AppResult<String> {
+    let conn = db_conn(&state.db)?;
+    let section_name = note_section_name(&section)?;
+    let file_path = list_note_sections(&conn, block_id)?.into_iter()
+        .find(|saved| saved.section_name == section_name)
+        .map(|saved| saved.file_path);
+    
+    match file_path {
+        Some(file_path) => Ok(state.files.load_notes(&file_path)?),
+        // A block without notes yet has an empty default section
+        None if section_name == DEFAULT_NOTE_SECTION => Ok(String::new()),
+        None => Err(AppError::NotFound(format!("Notes section not found: {}", section_name))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+        conn
+    }
+    
+    fn insert_block(conn: &Connection, date: &str, start_minutes: i32, title: &str) -> i64 {
+        conn.execute(
+            "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title) VALUES (?1, ?2, 30, ?3)",
+            (date, start_minutes, title),
+        ).unwrap();
+        conn.last_insert_rowid()
+    }
+    
+    #[test]
+    fn test_day_summary_compares_planned_and_actual() {
+        let conn = test_db();
+        let done = insert_block(&conn, "2025-06-01", 540, "Focus");
+        let estimated = insert_block(&conn, "2025-06-01", 600, "Review");
+        insert_block(&conn, "2025-06-01", 660, "Email");
+        
+        conn.execute("UPDATE time_blocks SET completed = 1, actual_duration_minutes = 50 WHERE id = ?1", [done]).unwrap();
+        conn.execute("UPDATE time_blocks SET completed = 1 WHERE id = ?1", [estimated]).unwrap();
+        
+        assert_eq!(day_summary(&conn, "2025-06-01").unwrap(), DaySummary {
+            date: "2025-06-01".to_string(),
+            total_blocks: 3,
+            completed_blocks: 2,
+            planned_minutes: 90,
+            actual_minutes: 80,
+            working_minutes: 540,
+        });
+        
+        // Rows created before the migration read as not completed
+        let block = load_time_block(&conn, insert_block(&conn, "2025-06-02", 540, "Old")).unwrap().unwrap();
+        assert!(!block.completed);
+        assert_eq!(block.actual_duration_minutes, None);
+    }
+    
+    #[test]
+    fn test_find_matching_block() {
+        let conn = test_db();
+        let id = insert_block(&conn, "2025-06-01", 540, "Focus");
+        let mut block = load_time_block(&conn, id).unwrap().unwrap();
+        block.id = None;
+        assert_eq!(find_matching_block(&conn, &block).unwrap(), Some(id));
+        
+        block.title = "Focus time".to_string();
+        assert_eq!(find_matching_block(&conn, &block).unwrap(), None);
+    }
+    
+    #[test]
+    fn test_day_attachments_ordered_by_block_start() {
+        let conn = test_db();
+        let late = insert_block(&conn, "2025-06-01", 600, "Review");
+        let early = insert_block(&conn, "2025-06-01", 540, "Focus");
+        let other_day = insert_block(&conn, "2025-06-02", 540, "Focus");
+        for (block_id, path, file_type) in [
+            (late, "attachments/late.png", "image"),
+            (early, "attachments/early.pdf", "document"),
+            (early, "attachments/early.png", "image"),
+            (other_day, "attachments/other.png", "image"),
+        ] {
+            conn.execute(
+                "INSERT INTO attachments (time_block_id, file_path, file_name, file_type, thumbnail_path) VALUES (?1, ?2, 'f', ?3, ?4)",
+                (block_id, path, file_type, if file_type == "document" { Some("builtin:document") } else { None }),
+            ).unwrap();
+        }
+        
+        let attachments = query_day_attachments(&conn, "2025-06-01").unwrap();
+        let paths: Vec<(&str, Option<&str>)> = attachments.iter()
+            .map(|a| (a.file_path.as_str(), a.thumbnail_path.as_deref()))
+            .collect();
+        assert_eq!(paths, vec![
+            ("attachments/early.pdf", None),
+            ("attachments/early.png", Some("attachments/early.png")),
+            ("attachments/late.png", Some("attachments/late.png")),
+        ]);
+    }
+    
+    #[test]
+    fn test_effective_hours_prefers_date_then_weekday() {
+        let conn = test_db();
+        // 2025-06-07 is a Saturday
+        assert_eq!(effective_hours(&conn, "2025-06-07").unwrap(), TimeRange { start_minutes: 480, end_minutes: 1020 });
+        
+        assert_eq!(day_override_key("Sat").unwrap(), "saturday");
+        assert!(day_override_key("someday").is_err());
+        conn.execute("INSERT INTO day_overrides (key, start_minutes, end_minutes) VALUES ('saturday', 600, 720)", []).unwrap();
+        assert_eq!(effective_hours(&conn, "2025-06-07").unwrap(), TimeRange { start_minutes: 600, end_minutes: 720 });
+        assert_eq!(effective_hours(&conn, "2025-06-06").unwrap(), TimeRange { start_minutes: 480, end_minutes: 1020 });
+        
+        conn.execute("INSERT INTO day_overrides (key, start_minutes, end_minutes) VALUES ('2025-06-07', 540, 600)", []).unwrap();
+        assert_eq!(effective_hours(&conn, "2025-06-07").unwrap(), TimeRange { start_minutes: 540, end_minutes: 600 });
+        assert_eq!(effective_hours(&conn, "2025-06-14").unwrap(), TimeRange { start_minutes: 600, end_minutes: 720 });
+    }
+    
+    #[test]
+    fn test_attachment_pages() {
+        let conn = test_db();
+        let block_id = insert_block(&conn, "2025-06-01", 540, "Focus");
+        for name in ["a.png", "b.png", "c.png"] {
+            conn.execute(
+                "INSERT INTO attachments (time_block_id, file_path, file_name, file_type) VALUES (?1, ?2, ?2, 'image')",
+                (block_id, name),
+            ).unwrap();
+        }
+        
+        let names = |page: AttachmentPage| page.attachments.into_iter().map(|a| a.file_name).collect::<Vec<_>>();
+        let first = query_attachment_page(&conn, block_id, 2, 0).unwrap();
+        assert_eq!(first.total, 3);
+        assert_eq!(names(first), vec!["c.png", "b.png"]);
+        assert_eq!(names(query_attachment_page(&conn, block_id, 2, 2).unwrap()), vec!["a.png"]);
+        assert!(query_attachment_page(&conn, block_id, 2, 4).unwrap().attachments.is_empty());
+    }
+    
+    #[test]
+    fn test_delete_time_block_removes_attachment_rows() {
+        // Foreign keys deliberately left off to cover databases opened without the pragma
+        let conn = test_db();
+        let block_id = insert_block(&conn, "2025-06-01", 540, "Focus");
+        let other_id = insert_block(&conn, "2025-06-01", 600, "Other");
+        for (id, path) in [(block_id, "a.png"), (block_id, "b.png"), (other_id, "c.png")] {
+            conn.execute(
+                "INSERT INTO attachments (time_block_id, file_path, file_name, file_type) VALUES (?1, ?2, ?2, 'image')",
+                (id, path),
+            ).unwrap();
+        }
+        
+        delete_time_block_rows(&conn, block_id).unwrap();
+        
+        assert!(query_attachments(&conn, block_id).unwrap().is_empty());
+        assert_eq!(query_attachments(&conn, other_id).unwrap().len(), 1);
+        let orphans: i64 = conn.query_row(
+            "SELECT COUNT(*) FROM attachments WHERE time_block_id NOT IN (SELECT id FROM time_blocks)",
+            [],
+            |row| row.get(0),
+        ).unwrap();
+        assert_eq!(orphans, 0);
+    }
+    
+    #[test]
+    fn test_count_tags_orders_by_frequency() {
+        let conn = test_db();
+        for tags in [r#"["work","focus"]"#, r#"["work"]"#, r#"["admin","focus","work"]"#, ""] {
+            conn.execute(
+                "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title, tags) VALUES ('2025-06-01', 540, 30, 'Block', ?1)",
+                [tags],
+            ).unwrap();
+        }
+        backfill_block_tags(&conn).unwrap();
+        
+        let counts: Vec<(String, i64)> = count_tags(&conn).unwrap()
+            .into_iter()
+            .map(|t| (t.tag, t.count))
+            .collect();
+        assert_eq!(counts, vec![
+            ("work".to_string(), 3),
+            ("focus".to_string(), 2),
+            ("admin".to_string(), 1),
+        ]);
+    }
+    
+    #[test]
+    fn test_block_tags_follow_updates_and_deletes() {
+        let conn = test_db();
+        let focus = insert_block(&conn, "2025-06-01", 540, "Focus");
+        let later = insert_block(&conn, "2025-06-03", 540, "Later");
+        sync_block_tags(&conn, focus, &["deep".to_string(), "work".to_string()]).unwrap();
+        sync_block_tags(&conn, later, &["deep".to_string()]).unwrap();
+        
+        let titles = |tag: &str, from: &str, to: &str| -> Vec<String> {
+            load_blocks_by_tag(&conn, tag, from, to).unwrap().into_iter().map(|b| b.title).collect()
+        };
+        assert_eq!(titles("deep", "2025-06-01", "2025-06-30"), vec!["Focus", "Later"]);
+        assert_eq!(titles("deep", "2025-06-02", "2025-06-30"), vec!["Later"]);
+        
+        sync_block_tags(&conn, focus, &["work".to_string()]).unwrap();
+        assert_eq!(titles("deep", "2025-06-01", "2025-06-30"), vec!["Later"]);
+        
+        delete_time_block_rows(&conn, later).unwrap();
+        assert!(titles("deep", "2025-06-01", "2025-06-30").is_empty());
+        let tag_names: Vec<String> = count_tags(&conn).unwrap().into_iter().map(|t| t.tag).collect();
+        assert_eq!(tag_names, vec!["work"]);
+    }
+    
+    #[test]
+    fn test_scheduled_priority_completes_with_block() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        conn.execute("INSERT INTO priorities (date, content) VALUES ('2025-06-01', 'Write proposal')", []).unwrap();
+        let priority_id = conn.last_insert_rowid();
+        
+        let block = priority_block(&conn, priority_id, "2025-06-02", 540, 60).unwrap();
+        assert_eq!(block.title, "Write proposal");
+        assert_eq!(block.tags, vec!["priority"]);
+        assert!(priority_block(&conn, priority_id + 1, "2025-06-02", 540, 60).is_err());
+        assert!(priority_block(&conn, priority_id, "2025-06-02", 1400, 60).is_err());
+        
+        let block_id = write_time_block(&conn, &files, &block, None).unwrap().id.unwrap();
+        conn.execute("UPDATE time_blocks SET priority_id = ?1 WHERE id = ?2", (priority_id, block_id)).unwrap();
+        
+        assert_eq!(complete_block(&conn, block_id, true, None).unwrap(), 1);
+        assert!(load_priorities(&conn, "2025-06-01").unwrap()[0].completed);
+        complete_block(&conn, block_id, false, None).unwrap();
+        assert!(!load_priorities(&conn, "2025-06-01").unwrap()[0].completed);
+    }
+    
+    #[test]
+    fn test_save_priorities_keeps_ids_and_block_links() {
+        let conn = test_db();
+        let strings = |items: &[&str]| items.iter().map(|item| item.to_string()).collect::<Vec<_>>();
+        write_priorities(&conn, "2025-06-01", &strings(&["Proposal", "Email", "Gym"])).unwrap();
+        let ids: Vec<i64> = load_priorities(&conn, "2025-06-01").unwrap().iter().filter_map(|p| p.id).collect();
+        let block_id = insert_block(&conn, "2025-06-01", 540, "Proposal");
+        conn.execute("UPDATE time_blocks SET priority_id = ?1 WHERE id = ?2", (ids[0], block_id)).unwrap();
+        
+        // Reordered and edited: "Proposal" keeps its row, the edit reuses a spare one
+        write_priorities(&conn, "2025-06-01", &strings(&["Email", "", "Proposal", "Gym class"])).unwrap();
+        let saved = load_priorities(&conn, "2025-06-01").unwrap();
+        let saved: Vec<(Option<i64>, &str, i32)> = saved.iter().map(|p| (p.id, p.content.as_str(), p.priority_order)).collect();
+        assert_eq!(saved, vec![(Some(ids[1]), "Email", 0), (Some(ids[0]), "Proposal", 2), (Some(ids[2]), "Gym class", 3)]);
+        
+        write_priorities(&conn, "2025-06-01", &strings(&["Email"])).unwrap();
+        assert_eq!(load_priorities(&conn, "2025-06-01").unwrap().len(), 1);
+        let linked: Option<i64> = conn.query_row("SELECT priority_id FROM time_blocks WHERE id = ?1", [block_id], |row| row.get(0)).unwrap();
+        assert_eq!(linked, None);
+    }
+    
+    #[test]
+    fn test_blank_title_saves_as_placeholder() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        conn.execute("UPDATE settings SET value = 'Hold' WHERE key = 'default_block_title'", []).unwrap();
+        
+        let mut block = load_time_block(&conn, insert_block(&conn, "2025-06-01", 540, "Focus")).unwrap().unwrap();
+        block.id = None;
+        block.title = "   ".to_string();
+        let saved = write_time_block(&conn, &files, &block, None).unwrap();
+        let stored = load_time_block(&conn, saved.id.unwrap()).unwrap().unwrap();
+        assert_eq!((stored.title.as_str(), stored.is_placeholder), ("Hold", true));
+        
+        // Saving it back unchanged keeps the flag; naming it clears it
+        write_time_block(&conn, &files, &stored, None).unwrap();
+        assert!(load_time_block(&conn, saved.id.unwrap()).unwrap().unwrap().is_placeholder);
+        let mut named = stored.clone();
+        named.title = "Deep work".to_string();
+        write_time_block(&conn, &files, &named, None).unwrap();
+        assert!(!load_time_block(&conn, saved.id.unwrap()).unwrap().unwrap().is_placeholder);
+    }
+    
+    #[test]
+    fn test_pomodoro_segments_end_on_work() {
+        // 25/5/25/5/25 leaves 5 minutes, too short for another break and work
+        assert_eq!(pomodoro_segments(90, 25, 5), vec![
+            (0, 25, false), (25, 5, true), (30, 25, false), (55, 5, true), (60, 30, false),
+        ]);
+        // A partial last work segment is kept as is
+        assert_eq!(pomodoro_segments(70, 25, 5), vec![
+            (0, 25, false), (25, 5, true), (30, 25, false), (55, 5, true), (60, 10, false),
+        ]);
+        assert_eq!(pomodoro_segments(25, 25, 5), vec![(0, 25, false)]);
+    }
+    
+    #[test]
+    fn test_backfill_thumbnails_resumes_and_skips_missing() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let block_id = insert_block(&conn, "2025-06-01", 540, "Focus");
+        
+        let mut png = Vec::new();
+        image::RgbImage::new(8, 8)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let photo = files.save_attachment(block_id, "2025-06-01", &png, "photo.png").unwrap();
+        let report = files.save_attachment(block_id, "2025-06-01", b"%PDF-1.7", "report.pdf").unwrap();
+        for (path, file_type) in [(photo.as_str(), "image"), (report.as_str(), "document"), ("attachments/gone.png", "image")] {
+            conn.execute(
+                "INSERT INTO attachments (time_block_id, file_path, file_name, file_type) VALUES (?1, ?2, ?3, ?4)",
+                (block_id, path, path.rsplit('/').next().unwrap(), file_type),
+            ).unwrap();
+        }
+        
+        let mut progress = Vec::new();
+        let summary = backfill_thumbnails_for(&conn, &files, |p| progress.push(p.processed)).unwrap();
+        assert_eq!(summary.generated, 2);
+        assert_eq!(summary.missing, vec!["attachments/gone.png"]);
+        assert_eq!(progress, vec![1, 2, 3]);
+        
+        let mut stmt = conn.prepare("SELECT thumbnail_path FROM attachments ORDER BY id").unwrap();
+        let thumbnails: Vec<Option<String>> = stmt.query_map([], |row| row.get(0)).unwrap()
+            .map(|row| row.unwrap()).collect();
+        assert_eq!(thumbnails, vec![
+            Some(format!("{}.thumb.png", photo)),
+            Some(crate::media::GENERIC_DOCUMENT_THUMBNAIL.to_string()),
+            None,
+        ]);
+        
+        // Only the missing file is looked at again
+        let summary = backfill_thumbnails_for(&conn, &files, |_| {}).unwrap();
+        assert_eq!((summary.generated, summary.missing.len()), (0, 1));
+    }
+    
+    #[test]
+    fn test_new_attachments_get_thumbnails() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let block_id = insert_block(&conn, "2025-06-01", 540, "Focus");
+        
+        let mut png = Vec::new();
+        image::RgbImage::new(8, 8)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        let photo = files.save_attachment(block_id, "2025-06-01", &png, "photo.png").unwrap();
+        let thumbnail = attachment_thumbnail(&conn, &files, &photo, "photo.png", "image", &png).unwrap();
+        assert_eq!(thumbnail, Some(format!("{}.thumb.png", photo)));
+        assert!(files.file_exists(thumbnail.as_deref().unwrap()));
+        
+        // A deduplicated copy shares the existing preview
+        conn.execute(
+            "INSERT INTO attachments (time_block_id, file_path, file_name, file_type, thumbnail_path) VALUES (?1, ?2, 'photo.png', 'image', 'shared.png')",
+            (block_id, &photo),
+        ).unwrap();
+        assert_eq!(attachment_thumbnail(&conn, &files, &photo, "photo.png", "image", &png).unwrap(), Some("shared.png".to_string()));
+        
+        assert_eq!(attachment_thumbnail(&conn, &files, "attachments/x.png", "x.png", "image", b"not an image").unwrap(), None);
+    }
+    
+    #[test]
+    fn test_copy_day_plan() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        
+        let mut focus = load_time_block(&conn, insert_block(&conn, "2025-06-01", 540, "Focus")).unwrap().unwrap();
+        focus.tags = vec!["deep".to_string()];
+        write_time_block(&conn, &files, &focus, Some("chapter outline")).unwrap();
+        conn.execute("UPDATE time_blocks SET completed = 1 WHERE id = ?1", [focus.id]).unwrap();
+        insert_block(&conn, "2025-06-01", 600, "Email");
+        conn.execute("INSERT INTO priorities (date, content, priority_order) VALUES ('2025-06-01', 'Ship it', 0)", []).unwrap();
+        insert_block(&conn, "2025-06-02", 480, "Already planned");
+        
+        let copy = copy_day_plan(&conn, &files, "2025-06-01", "2025-06-02", true, true, false).unwrap();
+        assert!(copy.result.skipped);
+        
+        let copy = copy_day_plan(&conn, &files, "2025-06-01", "2025-06-02", true, true, true).unwrap();
+        assert_eq!(copy.result, CopyDayResult { blocks: 2, notes: 1, priorities: 1, replaced_blocks: 1, skipped: false });
+        
+        let copied = load_time_blocks(&conn, "2025-06-02").unwrap();
+        let titles: Vec<&str> = copied.iter().map(|b| b.title.as_str()).collect();
+        assert_eq!(titles, vec!["Focus", "Email"]);
+        assert_ne!(copied[0].id, focus.id);
+        assert_eq!(copied[0].tags, vec!["deep"]);
+        assert!(!copied[0].completed);
+        assert_eq!(files.load_notes(copied[0].notes_file.as_ref().unwrap()).unwrap(), "chapter outline");
+        assert_eq!(load_priorities(&conn, "2025-06-02").unwrap()[0].content, "Ship it");
+        // The source day is untouched
+        assert_eq!(load_time_blocks(&conn, "2025-06-01").unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_write_block_notes_creates_and_overwrites() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let id = insert_block(&conn, "2025-06-01", 540, "Focus");
+        
+        let block = write_block_notes(&conn, &files, id, "First draft").unwrap();
+        let notes_file = block.notes_file.clone().unwrap();
+        assert_eq!(files.load_notes(&notes_file).unwrap(), "First draft");
+        
+        let block = write_block_notes(&conn, &files, id, "Second draft").unwrap();
+        assert_eq!(block.notes_file.as_deref(), Some(notes_file.as_str()));
+        assert_eq!(block.title, "Focus");
+        assert_eq!(files.load_notes(&notes_file).unwrap(), "Second draft");
+        
+        let stored = load_time_block(&conn, id).unwrap().unwrap();
+        assert_eq!(stored.notes_file, Some(notes_file));
+        assert!(write_block_notes(&conn, &files, id + 1, "x").is_err());
+    }
+    
+    #[test]
+    fn test_merge_adjacent_blocks() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let first = insert_block(&conn, "2025-06-01", 540, "Write");
+        let second = insert_block(&conn, "2025-06-01", 570, "Write more");
+        conn.execute("UPDATE time_blocks SET tags = '[\"deep\"]' WHERE id = ?1", [first]).unwrap();
+        conn.execute("UPDATE time_blocks SET tags = '[\"deep\",\"draft\"]' WHERE id = ?1", [second]).unwrap();
+        write_block_notes(&conn, &files, first, "Intro").unwrap();
+        let second_notes = write_block_notes(&conn, &files, second, "Conclusion").unwrap().notes_file.unwrap();
+        let attachment_path = files.save_attachment(second, "2025-06-01", b"img", "chart.png").unwrap();
+        conn.execute(
+            "INSERT INTO attachments (time_block_id, file_path, file_name, file_type) VALUES (?1, ?2, 'chart.png', 'image')",
+            (second, &attachment_path),
+        ).unwrap();
+        
+        let (merged, notes) = merge_time_blocks(&conn, &files, first, second).unwrap();
+        assert_eq!((merged.id, merged.start_minutes, merged.duration_minutes), (Some(first), 540, 60));
+        assert_eq!(merged.tags, vec!["deep", "draft"]);
+        assert_eq!(notes, "Intro\n\n---\n\nConclusion");
+        assert_eq!(files.load_notes(merged.notes_file.as_ref().unwrap()).unwrap(), notes);
+        assert!(!files.file_exists(&second_notes));
+        assert!(load_time_block(&conn, second).unwrap().is_none());
+        
+        let attachments = query_attachments(&conn, first).unwrap();
+        assert_eq!(attachments.len(), 1);
+        assert_eq!(attachments[0].file_path, format!("attachments/2025-06-01/{}_chart.png", first));
+        assert!(files.file_exists(&attachments[0].file_path));
+    }
+    
+    #[test]
+    fn test_reparent_attachment_moves_file_to_target_block() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let wrong = insert_block(&conn, "2025-06-01", 540, "Wrong");
+        let right = insert_block(&conn, "2025-06-03", 600, "Right");
+        let file_path = files.save_attachment(wrong, "2025-06-01", b"img", "scan.png").unwrap();
+        let thumbnail = files.save_thumbnail(&file_path, b"png").unwrap();
+        // A deduplicated copy on the same block shares the file
+        for _ in 0..2 {
+            conn.execute(
+                "INSERT INTO attachments (time_block_id, file_path, file_name, file_type, thumbnail_path) VALUES (?1, ?2, 'scan.png', 'image', ?3)",
+                (wrong, &file_path, &thumbnail),
+            ).unwrap();
+        }
+        let id = query_attachments(&conn, wrong).unwrap()[0].id.unwrap();
+        
+        let new_path = reparent_attachment(&conn, &files, id, right).unwrap();
+        assert_eq!(new_path, format!("attachments/2025-06-03/{}_scan.png", right));
+        assert!(files.file_exists(&new_path) && !files.file_exists(&file_path));
+        let moved = &query_attachments(&conn, right).unwrap()[0];
+        assert_eq!(moved.thumbnail_path, Some(format!("{}.thumb.png", new_path)));
+        assert_eq!(query_attachments(&conn, wrong).unwrap()[0].file_path, new_path);
+        
+        assert_eq!(reparent_attachment(&conn, &files, id, 999).unwrap_err().code(), "not_found");
+        assert_eq!(reparent_attachment(&conn, &files, 999, right).unwrap_err().code(), "not_found");
+    }
+    
+    #[test]
+    fn test_merge_rejects_gaps_and_other_days() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let first = insert_block(&conn, "2025-06-01", 540, "Write");
+        let gap = insert_block(&conn, "2025-06-01", 600, "Later");
+        let other_day = insert_block(&conn, "2025-06-02", 570, "Tomorrow");
+        
+        for (a, b) in [(first, gap), (first, other_day), (gap, first), (first, first)] {
+            assert_eq!(merge_time_blocks(&conn, &files, a, b).unwrap_err().code(), "validation");
+        }
+        assert_eq!(merge_time_blocks(&conn, &files, first, 999).unwrap_err().code(), "not_found");
+        assert_eq!(load_time_blocks(&conn, "2025-06-01").unwrap().len(), 2);
+    }
+    
+    #[test]
+    fn test_merge_moves_shared_attachment_file_once() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let first = insert_block(&conn, "2025-06-01", 540, "Write");
+        let second = insert_block(&conn, "2025-06-01", 570, "Write more");
+        let file_path = files.save_attachment(second, "2025-06-01", b"img", "chart.png").unwrap();
+        let thumbnail = files.save_thumbnail(&file_path, b"png").unwrap();
+        for _ in 0..2 {
+            conn.execute(
+                "INSERT INTO attachments (time_block_id, file_path, file_name, file_type, thumbnail_path) VALUES (?1, ?2, 'chart.png', 'image', ?3)",
+                (second, &file_path, &thumbnail),
+            ).unwrap();
+        }
+        
+        merge_time_blocks(&conn, &files, first, second).unwrap();
+        let new_path = format!("attachments/2025-06-01/{}_chart.png", first);
+        let attachments = query_attachments(&conn, first).unwrap();
+        assert_eq!(attachments.len(), 2);
+        for attachment in &attachments {
+            assert_eq!(attachment.file_path, new_path);
+            assert_eq!(attachment.thumbnail_path, Some(format!("{}.thumb.png", new_path)));
+        }
+        assert!(files.file_exists(&new_path) && !files.file_exists(&file_path));
+    }
+    
+    #[test]
+    fn test_discard_attachment_files_keeps_shared_copies() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let block_id = insert_block(&conn, "2025-06-01", 540, "Write");
+        
+        // Nothing points at a file written for a row that failed to insert
+        let orphan = files.save_attachment(block_id, "2025-06-01", b"img", "chart.png").unwrap();
+        let thumbnail = files.save_thumbnail(&orphan, b"png").unwrap();
+        discard_attachment_files(&conn, &files, &orphan, Some(&thumbnail));
+        assert!(!files.file_exists(&orphan) && !files.file_exists(&thumbnail));
+        
+        // A deduplicated file other rows still use stays
+        let shared = files.save_attachment(block_id, "2025-06-01", b"img", "chart.png").unwrap();
+        conn.execute(
+            "INSERT INTO attachments (time_block_id, file_path, file_name, file_type) VALUES (?1, ?2, 'chart.png', 'image')",
+            (block_id, &shared),
+        ).unwrap();
+        discard_attachment_files(&conn, &files, &shared, Some(crate::media::GENERIC_DOCUMENT_THUMBNAIL));
+        assert!(files.file_exists(&shared));
+    }
+    
+    #[test]
+    fn test_note_sections_alongside_default_notes() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let id = insert_block(&conn, "2025-06-01", 540, "Planning");
+        assert!(list_note_sections(&conn, id).unwrap().is_empty());
+        
+        let (_, agenda, agenda_id) = write_note_section(&conn, &files, id, " Agenda ", "1. Budget").unwrap();
+        assert_eq!(agenda.section_name, "Agenda");
+        assert!(agenda_id.is_some());
+        let (block, default, default_id) = write_note_section(&conn, &files, id, "default", "Overview").unwrap();
+        assert_eq!(block.notes_file.as_deref(), Some(default.file_path.as_str()));
+        assert_eq!(default_id, None);
+        
+        // Saving again updates the same row and file
+        let (_, again, again_id) = write_note_section(&conn, &files, id, "Agenda", "1. Budget\n2. Hiring").unwrap();
+        assert_eq!((again.file_path.as_str(), again_id), (agenda.file_path.as_str(), agenda_id));
+        assert_eq!(files.load_notes(&agenda.file_path).unwrap(), "1. Budget\n2. Hiring");
+        
+        let names: Vec<String> = list_note_sections(&conn, id).unwrap().into_iter().map(|s| s.section_name).collect();
+        assert_eq!(names, vec!["default", "Agenda"]);
+        // Both kinds report RFC 3339 timestamps, like time blocks do
+        for updated_at in [&default.updated_at, &again.updated_at] {
+            assert!(updated_at.as_deref().is_some_and(|ts| chrono::DateTime::parse_from_rfc3339(ts).is_ok()));
+        }
+        for section in list_note_sections(&conn, id).unwrap() {
+            assert!(section.updated_at.as_deref().is_some_and(|ts| ts.contains('T') && ts.ends_with('Z')));
+        }
+        assert_eq!(write_note_section(&conn, &files, id, "  ", "x").unwrap_err().code(), "validation");
+        assert_eq!(write_note_section(&conn, &files, id + 1, "Agenda", "x").unwrap_err().code(), "not_found");
+        
+        delete_time_block_rows(&conn, id).unwrap();
+        let remaining: i64 = conn.query_row("SELECT COUNT(*) FROM block_notes", [], |row| row.get(0)).unwrap();
+        assert_eq!(remaining, 0);
+    }
+
+    #[test]
+    fn test_resolve_file_type_prefers_content() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        assert_eq!(resolve_file_type("document", png).unwrap(), ("image".to_string(), "image/png".to_string()));
+        assert_eq!(
+            resolve_file_type("image", b"plain text").unwrap(),
+            ("document".to_string(), "application/octet-stream".to_string())
+        );
+        
+        let exe = b"MZ\x90\0\x03\0\0\0\x04\0\0\0\xff\xff";
+        assert_eq!(resolve_file_type("image", exe).unwrap_err().code(), "validation");
+    }
+
+    #[test]
+    fn test_repeat_through_week_skips_existing_and_past_days() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        // 2025-06-04 is a Wednesday; the week runs Monday 2nd to Sunday 8th
+        let id = insert_block(&conn, "2025-06-04", 540, "Exercise");
+        insert_block(&conn, "2025-06-06", 540, "Exercise");
+        insert_block(&conn, "2025-06-07", 540, "Something else");
+        
+        let created = repeat_through_week(&conn, &files, id).unwrap();
+        assert_eq!(created.len(), 3);
+        for date in ["2025-06-05", "2025-06-07", "2025-06-08"] {
+            assert!(load_time_blocks(&conn, date).unwrap().iter().any(|b| b.title == "Exercise"), "{}", date);
+        }
+        assert_eq!(load_time_blocks(&conn, "2025-06-06").unwrap().len(), 1);
+        assert!(load_time_blocks(&conn, "2025-06-03").unwrap().is_empty());
+        
+        // A Sunday week start ends the week on Saturday
+        conn.execute("INSERT OR REPLACE INTO settings (key, value) VALUES ('week_start', 'sunday')", []).unwrap();
+        let sunday_block = insert_block(&conn, "2025-06-13", 600, "Review");
+        assert_eq!(repeat_through_week(&conn, &files, sunday_block).unwrap().len(), 1);
+        assert!(load_time_blocks(&conn, "2025-06-15").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_apply_tag_colors_uses_first_matching_tag() {
+        let conn = test_db();
+        conn.execute(
+            "INSERT OR REPLACE INTO settings (key, value) VALUES ('tag_colors', '{\"Meeting\": \"#EF4444\", \"focus\": \"#10b981\"}')",
+            [],
+        ).unwrap();
+        for (title, tags) in [("Sync", r#"["meeting", "focus"]"#), ("Deep work", r#"["focus", "meeting"]"#), ("Lunch", r#"["food"]"#)] {
+            conn.execute(
+                "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title, color, tags) VALUES ('2025-06-01', 540, 30, ?1, '#3b82f6', ?2)",
+                (title, tags),
+            ).unwrap();
+        }
+        
+        let mut blocks = load_time_blocks(&conn, "2025-06-01").unwrap();
+        apply_tag_colors(&mut blocks, &tag_colors(&conn).unwrap());
+        let colors: Vec<(&str, &str)> = blocks.iter().map(|b| (b.title.as_str(), b.color.as_str())).collect();
+        assert!(colors.contains(&("Sync", "#ef4444")));
+        assert!(colors.contains(&("Deep work", "#10b981")));
+        assert!(colors.contains(&("Lunch", "#3b82f6")));
+        
+        // Stored colors are untouched
+        assert!(load_time_blocks(&conn, "2025-06-01").unwrap().iter().all(|b| b.color == "#3b82f6"));
+        assert!(parse_tag_colors(r#"{"meeting": "red"}"#).is_err());
+    }
+
+    #[test]
+    fn test_populated_dates() {
+        let conn = test_db();
+        insert_block(&conn, "2025-06-01", 540, "Focus");
+        insert_block(&conn, "2025-06-01", 600, "Review");
+        insert_block(&conn, "2025-06-09", 540, "Out of range");
+        conn.execute("INSERT INTO priorities (date, content, priority_order) VALUES ('2025-06-03', 'Ship it', 0)", []).unwrap();
+        conn.execute("INSERT INTO brain_dumps (date, content) VALUES ('2025-06-01', 'ideas')", []).unwrap();
+        conn.execute("INSERT INTO brain_dumps (date, content) VALUES ('2025-06-05', '')", []).unwrap();
+        
+        let dates = populated_dates(&conn, "2025-06-01", "2025-06-07").unwrap();
+        assert_eq!(dates, vec![
+            PopulatedDate {
+                date: "2025-06-01".to_string(),
+                block_count: 2,
+                scheduled_minutes: 60,
+                has_priorities: false,
+                has_brain_dump: true,
+            },
+            PopulatedDate {
+                date: "2025-06-03".to_string(),
+                block_count: 0,
+                scheduled_minutes: 0,
+                has_priorities: true,
+                has_brain_dump: false,
+            },
+        ]);
+    }
+    
+    #[test]
+    fn test_adjacent_populated_date() {
+        let conn = test_db();
+        insert_block(&conn, "2025-06-01", 540, "Focus");
+        conn.execute("INSERT INTO priorities (date, content, priority_order) VALUES ('2025-06-10', 'Ship it', 0)", []).unwrap();
+        conn.execute("INSERT INTO brain_dumps (date, content) VALUES ('2025-06-05', '')", []).unwrap();
+        
+        assert_eq!(adjacent_populated_date(&conn, "2025-06-01", "next").unwrap().as_deref(), Some("2025-06-10"));
+        assert_eq!(adjacent_populated_date(&conn, "2025-06-10", "prev").unwrap().as_deref(), Some("2025-06-01"));
+        assert_eq!(adjacent_populated_date(&conn, "2025-06-01", "prev").unwrap(), None);
+        assert_eq!(adjacent_populated_date(&conn, "2025-06-10", "next").unwrap(), None);
+        assert!(adjacent_populated_date(&conn, "2025-06-01", "up").is_err());
+    }
+    
+    #[test]
+    fn test_search_results_take_block_color_and_tags() {
+        let conn = test_db();
+        let id = insert_block(&conn, "2025-06-01", 540, "Planning");
+        conn.execute("UPDATE time_blocks SET color = '#10b981', tags = '[\"deep work\"]' WHERE id = ?1", [id]).unwrap();
+        let result = |id: i64| SearchResult {
+            id,
+            title: "Planning".to_string(),
+            content: String::new(),
+            date: "2025-06-01".to_string(),
+            start_minutes: 540,
+            duration_minutes: 30,
+            tags: vec!["stale".to_string()],
+            score: 1.0,
+            highlights: Vec::new(),
+            section: None,
+            color: DEFAULT_BLOCK_COLOR.to_string(),
+        };
+        
+        let results = with_block_details(&conn, vec![result(id), result(id + 1)]).unwrap();
+        assert_eq!((results[0].color.as_str(), results[0].tags.clone()), ("#10b981", vec!["deep work".to_string()]));
+        // A block deleted since it was indexed keeps what the index had
+        assert_eq!(results[1].color, DEFAULT_BLOCK_COLOR);
+    }
+    
+    #[test]
+    fn test_normalize_date() {
+        assert_eq!(normalize_date("2025-06-01").unwrap(), "2025-06-01");
+        assert_eq!(normalize_date("2025-6-1").unwrap(), "2025-06-01");
+        assert_eq!(normalize_date(" 2025/12/5 ").unwrap(), "2025-12-05");
+        assert_eq!(normalize_date("2025.1.31").unwrap(), "2025-01-31");
+        for invalid in ["2025-02-30", "2025-13-01", "25-06-01", "June 1", "", "2025-06-01T09:00"] {
+            assert_eq!(normalize_date(invalid).unwrap_err().code(), "validation", "{}", invalid);
+        }
+    }
+    
+    #[test]
+    fn test_write_time_block_stores_canonical_date() {
+        let conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let mut block = load_time_block(&conn, insert_block(&conn, "2025-06-01", 540, "Focus")).unwrap().unwrap();
+        block.id = None;
+        block.date = "2025-6-2".to_string();
+        
+        let saved = write_time_block(&conn, &files, &block, None).unwrap();
+        assert_eq!(saved.date, "2025-06-02");
+        assert_eq!(load_time_blocks(&conn, "2025-06-02").unwrap().len(), 1);
+        
+        block.date = "2025-02-30".to_string();
+        assert!(write_time_block(&conn, &files, &block, None).is_err());
+    }
+
+    #[test]
+    fn test_retag_blocks_renames_dedupes_and_deletes() {
+        let conn = test_db();
+        for (title, tags) in [("Sync", r#"["meeting", "team"]"#), ("1:1", r#"["meetings", "meeting"]"#), ("Focus", r#"["deep"]"#)] {
+            conn.execute(
+                "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title, tags) VALUES ('2025-06-01', 540, 30, ?1, ?2)",
+                (title, tags),
+            ).unwrap();
+            let id = conn.last_insert_rowid();
+            let tags: Vec<String> = serde_json::from_str(tags).unwrap();
+            sync_block_tags(&conn, id, &tags).unwrap();
+        }
+        
+        let changed = retag_blocks(&conn, "meeting", Some("meetings")).unwrap();
+        assert_eq!(changed.len(), 2);
+        let tags_of = |title: &str| load_time_blocks(&conn, "2025-06-01").unwrap()
+            .into_iter().find(|b| b.title == title).unwrap().tags;
+        assert_eq!(tags_of("Sync"), vec!["meetings", "team"]);
+        assert_eq!(tags_of("1:1"), vec!["meetings"]);
+        
+        let counts = count_tags(&conn).unwrap();
+        assert!(counts.iter().all(|t| t.tag != "meeting"));
+        assert_eq!(counts.iter().find(|t| t.tag == "meetings").unwrap().count, 2);
+        
+        assert_eq!(retag_blocks(&conn, "meetings", None).unwrap().len(), 2);
+        assert!(tags_of("1:1").is_empty());
+        assert!(retag_blocks(&conn, "deep", Some("  ")).is_err());
+    }
+
+    #[test]
+    fn test_split_data_url() {
+        let (mime_type, payload) = split_data_url("data:image/PNG;base64,iVBORw0KGgo=").unwrap();
+        assert_eq!(mime_type, "image/png");
+        assert_eq!(payload, "iVBORw0KGgo=");
+        assert_eq!(mime_extension(&mime_type), "png");
+        assert_eq!(mime_extension("image/svg+xml"), "svg");
+        assert_eq!(mime_extension("image/jpeg"), "jpg");
+        
+        assert_eq!(split_data_url("data:;base64,aGk=").unwrap().0, "text/plain");
+        assert!(split_data_url("image/png;base64,aGk=").is_err());
+        assert!(split_data_url("data:text/plain,hello").is_err());
+        assert!(split_data_url("data:image/png;base64,").is_err());
+    }
+    
+    #[test]
+    fn test_check_integrity_finds_and_repairs_missing_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = crate::services::FileService::new(dir.path().to_path_buf()).unwrap();
+        let mut conn = test_db();
+        let kept = insert_block(&conn, "2025-06-01", 540, "Kept");
+        let lost = insert_block(&conn, "2025-06-01", 600, "Lost");
+        let kept_block = load_time_block(&conn, kept).unwrap().unwrap();
+        let notes_file = files.save_notes(&kept_block, "still here").unwrap();
+        conn.execute("UPDATE time_blocks SET notes_file = ?1 WHERE id = ?2", (&notes_file, kept)).unwrap();
+        conn.execute("UPDATE time_blocks SET notes_file = 'notes/2025-06-01/0600-gone.md' WHERE id = ?1", [lost]).unwrap();
+        
+        let saved = files.save_attachment(kept, "2025-06-01", b"data", "a.txt").unwrap();
+        conn.execute(
+            "INSERT INTO attachments (time_block_id, file_path, file_name, file_type, file_size) VALUES (?1, ?2, 'a.txt', 'document', 4)",
+            (kept, &saved),
+        ).unwrap();
+        conn.execute(
+            "INSERT INTO attachments (time_block_id, file_path, file_name, file_type, file_size) VALUES (999, 'attachments/2025-06-01/999_b.txt', 'b.txt', 'document', 4)",
+            [],
+        ).unwrap();
+        let vanished = conn.last_insert_rowid();
+        
+        let report = check_integrity(&mut conn, &files, false).unwrap();
+        assert_eq!((report.notes_checked, report.attachments_checked), (2, 2));
+        assert_eq!(report.missing_notes.iter().map(|m| m.id).collect::<Vec<_>>(), vec![lost]);
+        assert_eq!(report.missing_attachments.iter().map(|m| m.id).collect::<Vec<_>>(), vec![vanished]);
+        assert_eq!(report.dangling_attachments, vec![vanished]);
+        assert!(!report.repaired);
+        
+        assert!(check_integrity(&mut conn, &files, true).unwrap().repaired);
+        let after = check_integrity(&mut conn, &files, false).unwrap();
+        assert!(after.missing_notes.is_empty() && after.missing_attachments.is_empty());
+        assert_eq!((after.notes_checked, after.attachments_checked), (1, 1));
+    }
+    
+    #[test]
+    fn test_variance_report_counts_measured_completed_blocks() {
+        let conn = test_db();
+        let over = insert_block(&conn, "2025-06-01", 540, "Writing");
+        let under = insert_block(&conn, "2025-06-02", 540, "Review");
+        let unmeasured = insert_block(&conn, "2025-06-02", 600, "Email");
+        insert_block(&conn, "2025-06-02", 660, "Not done");
+        insert_block(&conn, "2025-06-10", 540, "Out of range");
+        conn.execute("UPDATE time_blocks SET completed = 1, actual_duration_minutes = 45, tags = '[\"deep\",\"writing\"]' WHERE id = ?1", [over]).unwrap();
+        conn.execute("UPDATE time_blocks SET completed = 1, actual_duration_minutes = 20, tags = '[\"deep\"]' WHERE id = ?1", [under]).unwrap();
+        conn.execute("UPDATE time_blocks SET completed = 1 WHERE id = ?1", [unmeasured]).unwrap();
+        
+        let report = variance_report(&conn, "2025-06-01", "2025-06-07").unwrap();
+        assert_eq!(report.unmeasured, 1);
+        assert_eq!((report.overall.block_count, report.overall.planned_minutes, report.overall.actual_minutes), (2, 60, 65));
+        assert_eq!(report.overall.delta_minutes, 5);
+        assert!((report.overall.delta_percent.unwrap() - 8.333).abs() < 0.01);
+        
+        let tags: Vec<(&str, i64, i64)> = report.by_tag.iter()
+            .map(|bar| (bar.label.as_str(), bar.planned_minutes, bar.delta_minutes))
+            .collect();
+        assert_eq!(tags, vec![("deep", 60, 5), ("writing", 30, 15)]);
+        
+        let empty = variance_report(&conn, "2025-07-01", "2025-07-31").unwrap();
+        assert_eq!(empty.overall.delta_percent, None);
+        assert!(empty.by_tag.is_empty());
+    }
+}
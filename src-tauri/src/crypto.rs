@@ -1,61 +1,125 @@
 use anyhow::{anyhow, Result};
+use log::warn;
 use ring::aead::{Aad, LessSafeKey, Nonce, UnboundKey, AES_256_GCM};
 use ring::rand::{SecureRandom, SystemRandom};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 const KEY_SIZE: usize = 32; // 256 bits
 const NONCE_SIZE: usize = 12; // 96 bits for GCM
+const TAG_SIZE: usize = 16; // GCM authentication tag
+const KEY_FILE: &str = ".encryption_key";
+
+/// Location of the key file within a data directory
+pub fn key_path(data_dir: &Path) -> PathBuf {
+    data_dir.join(KEY_FILE)
+}
+
+/// Heuristic for when no key is available to try decrypting: ciphertext is
+/// base64 of at least a nonce plus tag. OAuth tokens contain '.', '-' or '/'
+/// and rarely decode that cleanly.
+pub fn looks_encrypted(value: &str) -> bool {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD.decode(value).is_ok_and(|data| data.len() > NONCE_SIZE + TAG_SIZE)
+}
+
+// Keychain entry holding the key. The account name doesn't depend on the data
+// directory, so the key is still found after the data is moved elsewhere.
+const KEYCHAIN_SERVICE: &str = "timebloc";
+const KEYCHAIN_ACCOUNT: &str = "encryption-key";
+
+/// Where the encryption key is kept, from the `encryption_key_storage` setting
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum KeyStorage {
+    File,      // Hex in .encryption_key in the data directory
+    Keychain,  // The OS secret store (Keychain, Credential Manager, Secret Service)
+}
+
+impl KeyStorage {
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "file" => Some(KeyStorage::File),
+            "keychain" => Some(KeyStorage::Keychain),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeyStorage::File => "file",
+            KeyStorage::Keychain => "keychain",
+        }
+    }
+}
 
 pub struct TokenEncryption {
     key: LessSafeKey,
     random: SystemRandom,
+    storage: KeyStorage,  // Where the key was actually loaded from
 }
 
 impl TokenEncryption {
     /// Create a new encryption instance with a generated or loaded key
     pub fn new(data_dir: &PathBuf) -> Result<Self> {
-        let key_path = data_dir.join(".encryption_key");
-        
-        // Load or generate encryption key
-        let key_bytes = if key_path.exists() {
-            // Load existing key
-            let key_hex = fs::read_to_string(&key_path)?;
-            hex::decode(key_hex.trim())?
-        } else {
-            // Generate new key
-            let random = SystemRandom::new();
-            let mut key_bytes = vec![0u8; KEY_SIZE];
-            random.fill(&mut key_bytes)
-                .map_err(|_| anyhow!("Failed to generate key"))?;
-            
-            // Save key for future use
-            let key_hex = hex::encode(&key_bytes);
-            fs::write(&key_path, key_hex)?;
-            
-            // Set restrictive permissions on key file (Unix-like systems)
-            #[cfg(unix)]
-            {
-                use std::os::unix::fs::PermissionsExt;
-                let mut perms = fs::metadata(&key_path)?.permissions();
-                perms.set_mode(0o600); // Read/write for owner only
-                fs::set_permissions(&key_path, perms)?;
-            }
-            
-            key_bytes
-        };
+        Self::from_key(&file_key(data_dir)?, KeyStorage::File)
+    }
+    
+    /// Like `new`, but with Keychain the key lives in the OS secret store. A key file
+    /// that hasn't been migrated yet is still used, and a new key falls back to the
+    /// file if the keychain can't store it. With File, a key only found in the keychain
+    /// is copied back out to the key file. The key bytes are the same wherever they
+    /// live, so tokens decrypt across a migration. A new key is never generated while
+    /// `has_encrypted_tokens`, since it would orphan every one of them.
+    pub fn with_storage(data_dir: &PathBuf, storage: KeyStorage, has_encrypted_tokens: bool) -> Result<Self> {
+        let key_path = key_path(data_dir);
+        if key_path.exists() {
+            return Self::new(data_dir);
+        }
         
+        match (keychain_key(data_dir), storage) {
+            (Ok(Some(key_bytes)), KeyStorage::Keychain) => Self::from_key(&key_bytes, KeyStorage::Keychain),
+            (Ok(Some(key_bytes)), KeyStorage::File) => {
+                write_key_file(&key_path, &key_bytes)?;
+                Self::from_key(&key_bytes, KeyStorage::File)
+            }
+            // A key may be stored there; minting a new one would orphan every encrypted token
+            (Err(e), KeyStorage::Keychain) => Err(anyhow!("Keychain unavailable: {}", e)),
+            _ if has_encrypted_tokens => Err(anyhow!(
+                "Encryption key not found at {} or in the keychain; stored tokens can't be decrypted",
+                key_path.display()
+            )),
+            (_, KeyStorage::File) => Self::new(data_dir),
+            (Ok(None), KeyStorage::Keychain) => {
+                let key_bytes = generate_key()?;
+                match keychain_entry()?.set_password(&hex::encode(&key_bytes)) {
+                    Ok(()) => Self::from_key(&key_bytes, KeyStorage::Keychain),
+                    Err(e) => {
+                        warn!("Keychain unavailable, storing the encryption key in a file: {}", e);
+                        write_key_file(&key_path, &key_bytes)?;
+                        Self::from_key(&key_bytes, KeyStorage::File)
+                    }
+                }
+            }
+        }
+    }
+    
+    fn from_key(key_bytes: &[u8], storage: KeyStorage) -> Result<Self> {
         // Create encryption key
-        let unbound_key = UnboundKey::new(&AES_256_GCM, &key_bytes)
+        let unbound_key = UnboundKey::new(&AES_256_GCM, key_bytes)
             .map_err(|_| anyhow!("Failed to create encryption key"))?;
         let key = LessSafeKey::new(unbound_key);
         
         Ok(Self {
             key,
             random: SystemRandom::new(),
+            storage,
         })
     }
     
+    pub fn storage(&self) -> KeyStorage {
+        self.storage
+    }
+    
     /// Encrypt a token
     pub fn encrypt(&self, plaintext: &str) -> Result<String> {
         let mut in_out = plaintext.as_bytes().to_vec();
@@ -106,6 +170,98 @@ impl TokenEncryption {
     }
 }
 
+// Load the key file, generating it on first use
+fn file_key(data_dir: &Path) -> Result<Vec<u8>> {
+    let key_path = key_path(data_dir);
+    if key_path.exists() {
+        let key_hex = fs::read_to_string(&key_path)?;
+        return Ok(hex::decode(key_hex.trim())?);
+    }
+    
+    let key_bytes = generate_key()?;
+    write_key_file(&key_path, &key_bytes)?;
+    Ok(key_bytes)
+}
+
+fn generate_key() -> Result<Vec<u8>> {
+    let mut key_bytes = vec![0u8; KEY_SIZE];
+    SystemRandom::new().fill(&mut key_bytes)
+        .map_err(|_| anyhow!("Failed to generate key"))?;
+    Ok(key_bytes)
+}
+
+fn write_key_file(key_path: &Path, key_bytes: &[u8]) -> Result<()> {
+    fs::write(key_path, hex::encode(key_bytes))?;
+    
+    // Set restrictive permissions on key file (Unix-like systems)
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = fs::metadata(key_path)?.permissions();
+        perms.set_mode(0o600); // Read/write for owner only
+        fs::set_permissions(key_path, perms)?;
+    }
+    Ok(())
+}
+
+fn keychain_entry() -> Result<keyring::Entry> {
+    Ok(keyring::Entry::new(KEYCHAIN_SERVICE, KEYCHAIN_ACCOUNT)?)
+}
+
+// Keys used to be stored per data directory path; found there, they're copied to
+// the stable entry
+fn legacy_keychain_entry(data_dir: &Path) -> Result<keyring::Entry> {
+    let user = format!("encryption-key:{}", data_dir.display());
+    Ok(keyring::Entry::new(KEYCHAIN_SERVICE, &user)?)
+}
+
+// The key stored in the keychain, if any
+fn keychain_key(data_dir: &Path) -> Result<Option<Vec<u8>>> {
+    let entry = keychain_entry()?;
+    let key_hex = match entry.get_password() {
+        Ok(key_hex) => key_hex,
+        Err(keyring::Error::NoEntry) => match legacy_keychain_entry(data_dir)?.get_password() {
+            Ok(key_hex) => {
+                if let Err(e) = entry.set_password(&key_hex) {
+                    warn!("Failed to copy the encryption key to its new keychain entry: {}", e);
+                }
+                key_hex
+            }
+            Err(keyring::Error::NoEntry) => return Ok(None),
+            Err(e) => return Err(e.into()),
+        },
+        Err(e) => return Err(e.into()),
+    };
+    Ok(Some(hex::decode(key_hex.trim())?))
+}
+
+/// Move the key file into the OS keychain. The file is only deleted once the
+/// keychain hands back the same key. Already-migrated keys are left alone.
+pub fn migrate_key_to_keychain(data_dir: &Path) -> Result<()> {
+    let key_path = key_path(data_dir);
+    if !key_path.exists() {
+        return match keychain_key(data_dir) {
+            Ok(Some(_)) => Ok(()),
+            Ok(None) => Err(anyhow!("No encryption key to migrate")),
+            Err(e) => Err(anyhow!("No encryption key to migrate: {}", e)),
+        };
+    }
+    
+    let key_hex = fs::read_to_string(&key_path)?.trim().to_string();
+    if !hex::decode(&key_hex).is_ok_and(|key| key.len() == KEY_SIZE) {
+        return Err(anyhow!("Key file {} is not a valid key", key_path.display()));
+    }
+    
+    let entry = keychain_entry()?;
+    entry.set_password(&key_hex)
+        .map_err(|e| anyhow!("Keychain unavailable: {}", e))?;
+    if entry.get_password()? != key_hex {
+        return Err(anyhow!("Keychain did not store the key; keeping the key file"));
+    }
+    fs::remove_file(&key_path)?;
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -140,4 +296,28 @@ mod tests {
         assert_eq!(crypto.decrypt(&encrypted1).unwrap(), original);
         assert_eq!(crypto.decrypt(&encrypted2).unwrap(), original);
     }
-}
\ No newline at end of file
+    
+    #[test]
+    fn test_looks_encrypted() {
+        let temp_dir = tempdir().unwrap();
+        let crypto = TokenEncryption::new(&temp_dir.path().to_path_buf()).unwrap();
+        
+        assert!(looks_encrypted(&crypto.encrypt("ya29.a0AfH6SM").unwrap()));
+        assert!(!looks_encrypted("ya29.a0AfH6SMBx-example_token"));
+        assert!(!looks_encrypted("1//0gLp-refresh"));
+    }
+    
+    #[test]
+    fn test_keychain_mode_keeps_using_unmigrated_key_file() {
+        let temp_dir = tempdir().unwrap();
+        let data_dir = temp_dir.path().to_path_buf();
+        let encrypted = TokenEncryption::new(&data_dir).unwrap().encrypt("token").unwrap();
+        
+        let crypto = TokenEncryption::with_storage(&data_dir, KeyStorage::Keychain, true).unwrap();
+        assert_eq!(crypto.storage(), KeyStorage::File);
+        assert_eq!(crypto.decrypt(&encrypted).unwrap(), "token");
+        
+        assert_eq!(KeyStorage::parse("keychain"), Some(KeyStorage::Keychain));
+        assert_eq!(KeyStorage::parse("vault"), None);
+    }
+}
@@ -0,0 +1,66 @@
+use chrono::NaiveDate;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use log::{error, warn};
+
+pub const SCHEME: &str = "timebloc";
+
+// A date from the link the app was launched with, held until the frontend is ready to ask for it
+#[derive(Default)]
+pub struct PendingNavigation(pub Mutex<Option<String>>);
+
+// "timebloc://date/2025-06-01" -> "2025-06-01". Anything else is ignored.
+pub fn parse_date_link(url: &str) -> Option<String> {
+    let rest = url.trim().strip_prefix("timebloc://")?;
+    let date = rest.strip_prefix("date/")?.trim_end_matches('/');
+    let parsed = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+
+    // Reject loose forms like "2025-6-1" so links stay canonical
+    let formatted = parsed.format("%Y-%m-%d").to_string();
+    (formatted == date).then_some(formatted)
+}
+
+// Register the scheme and forward links received while running to the frontend.
+// A second launch also arrives here (with no link), so the window is focused first.
+pub fn register(app: AppHandle) {
+    let result = tauri_plugin_deep_link::register(SCHEME, move |url| {
+        crate::instance::focus_main_window(&app);
+        if url.trim().is_empty() {
+            return;
+        }
+        match parse_date_link(&url) {
+            Some(date) => {
+                if let Err(e) = app.emit_all("navigate-date", date) {
+                    error!("Failed to emit navigate-date: {}", e);
+                }
+            }
+            None => warn!("Ignoring malformed deep link: {}", url),
+        }
+    });
+
+    if let Err(e) = result {
+        error!("Failed to register {}:// URL scheme: {}", SCHEME, e);
+    }
+}
+
+// On Windows and Linux a cold start receives the link as a command-line argument
+pub fn launch_date() -> Option<String> {
+    std::env::args().skip(1).find_map(|arg| parse_date_link(&arg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_date_link() {
+        assert_eq!(parse_date_link("timebloc://date/2025-06-01"), Some("2025-06-01".to_string()));
+        assert_eq!(parse_date_link("timebloc://date/2025-06-01/"), Some("2025-06-01".to_string()));
+
+        assert_eq!(parse_date_link("timebloc://date/2025-02-30"), None);
+        assert_eq!(parse_date_link("timebloc://date/2025-6-1"), None);
+        assert_eq!(parse_date_link("timebloc://date/"), None);
+        assert_eq!(parse_date_link("timebloc://block/12"), None);
+        assert_eq!(parse_date_link("https://date/2025-06-01"), None);
+    }
+}
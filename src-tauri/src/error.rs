@@ -0,0 +1,146 @@
+use serde::ser::{Serialize, SerializeStruct, Serializer};
+use std::fmt;
+
+/// Error returned by commands. Serializes as `{ "code": "not_found", "message": "..." }`
+/// so the frontend can switch on `code` instead of parsing messages.
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    NotFound(String),
+    Validation(String),
+    Database(String),
+    Io(String),
+    Auth(String),
+    Search(String),
+    Busy(String),  // The operation is already running; try again once it finishes
+    Internal(String),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "not_found",
+            AppError::Validation(_) => "validation",
+            AppError::Database(_) => "database",
+            AppError::Io(_) => "io",
+            AppError::Auth(_) => "auth",
+            AppError::Search(_) => "search",
+            AppError::Busy(_) => "busy",
+            AppError::Internal(_) => "internal",
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            AppError::NotFound(message)
+            | AppError::Validation(message)
+            | AppError::Database(message)
+            | AppError::Io(message)
+            | AppError::Auth(message)
+            | AppError::Search(message)
+            | AppError::Busy(message)
+            | AppError::Internal(message) => message,
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut state = serializer.serialize_struct("AppError", 2)?;
+        state.serialize_field("code", self.code())?;
+        state.serialize_field("message", self.message())?;
+        state.end()
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(e: rusqlite::Error) -> Self {
+        match e {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(e.to_string()),
+            e => AppError::Database(e.to_string()),
+        }
+    }
+}
+
+impl From<r2d2::Error> for AppError {
+    fn from(e: r2d2::Error) -> Self {
+        AppError::Database(e.to_string())
+    }
+}
+
+impl From<std::io::Error> for AppError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => AppError::NotFound(e.to_string()),
+            _ => AppError::Io(e.to_string()),
+        }
+    }
+}
+
+impl From<tantivy::TantivyError> for AppError {
+    fn from(e: tantivy::TantivyError) -> Self {
+        AppError::Search(e.to_string())
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(e: serde_json::Error) -> Self {
+        AppError::Internal(e.to_string())
+    }
+}
+
+// Services use anyhow; keep the category when the underlying error is one we know
+impl From<anyhow::Error> for AppError {
+    fn from(e: anyhow::Error) -> Self {
+        if let Some(app_error) = e.downcast_ref::<AppError>() {
+            return app_error.clone();
+        }
+        if e.is::<rusqlite::Error>() {
+            return e.downcast::<rusqlite::Error>().unwrap().into();
+        }
+        if e.is::<r2d2::Error>() {
+            return AppError::Database(e.to_string());
+        }
+        if let Some(io_error) = e.downcast_ref::<std::io::Error>() {
+            return AppError::from(std::io::Error::new(io_error.kind(), e.to_string()));
+        }
+        if e.is::<tantivy::TantivyError>() {
+            return AppError::Search(e.to_string());
+        }
+        AppError::Internal(e.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serializes_code_and_message() {
+        let error = AppError::NotFound("Time block not found: 7".to_string());
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({ "code": "not_found", "message": "Time block not found: 7" })
+        );
+    }
+
+    #[test]
+    fn test_conversions_keep_category() {
+        assert_eq!(AppError::from(rusqlite::Error::QueryReturnedNoRows).code(), "not_found");
+        assert_eq!(AppError::from(rusqlite::Error::InvalidQuery).code(), "database");
+
+        let missing = std::io::Error::new(std::io::ErrorKind::NotFound, "gone");
+        assert_eq!(AppError::from(anyhow::Error::new(missing)).code(), "not_found");
+        assert_eq!(AppError::from(anyhow::anyhow!("Invalid path: ../x")).code(), "internal");
+        assert_eq!(AppError::from(anyhow::Error::new(AppError::Auth("expired".to_string()))).code(), "auth");
+    }
+}
@@ -0,0 +1,462 @@
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use rusqlite::{Connection, OptionalExtension};
+use std::io::{Seek, Write};
+
+use crate::commands::{load_priorities, load_time_block, load_time_blocks, load_time_blocks_between, query_attachments, uses_twelve_hour_clock};
+use crate::markdown::render_html;
+use crate::models::{CalendarEvent, TimeBlock};
+use crate::scheduling::{block_range, event_minute_range, merge_ranges};
+use crate::services::FileService;
+
+// Longest range export_range_html and freebusy_ics accept
+const MAX_EXPORT_DAYS: i64 = 366;
+
+// Inlined so the exported file is self-contained
+const HTML_EXPORT_STYLE: &str = "body{font-family:-apple-system,BlinkMacSystemFont,'Segoe UI',sans-serif;\
+max-width:760px;margin:2rem auto;padding:0 1rem;line-height:1.5;color:#1f2937}\
+h1{border-bottom:2px solid #3b82f6;padding-bottom:.25rem}h2{margin-top:1.5rem;color:#1e40af}\
+section+section{margin-top:3rem}ul{padding-left:1.25rem}li:has(>input){list-style:none;margin-left:-1.25rem}\
+code,pre{background:#f3f4f6;border-radius:4px}pre{padding:.75rem;overflow-x:auto}\
+img{max-width:100%}table{border-collapse:collapse}td,th{border:1px solid #d1d5db;padding:.25rem .5rem}\
+@media print{body{margin:0}section{page-break-after:always}}";
+
+// Build a single Markdown document for a day: priorities, brain dump, then blocks by start time
+pub fn day_markdown(conn: &Connection, files: &FileService, date: &str) -> Result<String> {
+    let mut doc = format!("# {}\n", date);
+
+    let priorities = load_priorities(conn, date)?;
+    if !priorities.is_empty() {
+        doc.push_str("\n## Priorities\n\n");
+        for priority in &priorities {
+            let mark = if priority.completed { "x" } else { " " };
+            doc.push_str(&format!("- [{}] {}\n", mark, priority.content));
+        }
+    }
+
+    let brain_dump: Option<String> = conn.query_row(
+        "SELECT content FROM brain_dumps WHERE date = ?1 ORDER BY updated_at DESC, id DESC LIMIT 1",
+        [date],
+        |row| row.get(0),
+    ).optional()?;
+    if let Some(content) = brain_dump.filter(|c| !c.trim().is_empty()) {
+        doc.push_str("\n## Brain Dump\n\n");
+        doc.push_str(content.trim_end());
+        doc.push('\n');
+    }
+
+    let twelve_hour = uses_twelve_hour_clock(conn)?;
+    for block in load_time_blocks(conn, date)? {
+        doc.push_str(&format!(
+            "\n## {} - {} {}\n",
+            block.start_time_formatted_with(twelve_hour),
+            block.end_time_formatted_with(twelve_hour),
+            block.title
+        ));
+
+        if !block.tags.is_empty() {
+            let tags: Vec<String> = block.tags.iter().map(|t| format!("#{}", t)).collect();
+            doc.push_str(&format!("\nTags: {}\n", tags.join(" ")));
+        }
+
+        if let Some(notes_file) = &block.notes_file {
+            let notes = files.load_notes(notes_file)?;
+            if !notes.trim().is_empty() {
+                doc.push('\n');
+                doc.push_str(notes.trim_end());
+                doc.push('\n');
+            }
+        }
+    }
+
+    Ok(doc)
+}
+
+fn html_document(title: &str, body: &str) -> String {
+    format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>{}</style>\n</head>\n<body>\n{}</body>\n</html>\n",
+        title, HTML_EXPORT_STYLE, body
+    )
+}
+
+// The day's Markdown export rendered as sanitized HTML
+fn day_section(conn: &Connection, files: &FileService, date: &str) -> Result<String> {
+    Ok(format!("<section>\n{}</section>\n", render_html(&day_markdown(conn, files, date)?)))
+}
+
+// A styled, self-contained HTML page for a day, in the same order as the Markdown export
+pub fn day_html(conn: &Connection, files: &FileService, date: &str) -> Result<String> {
+    let day = parse_export_date(date)?;
+    Ok(html_document(&day.to_string(), &day_section(conn, files, date)?))
+}
+
+// One section per day in the inclusive range, skipping days with nothing recorded
+pub fn range_html(conn: &Connection, files: &FileService, date_from: &str, date_to: &str) -> Result<String> {
+    let from = parse_export_date(date_from)?;
+    let to = parse_export_date(date_to)?;
+    if to < from {
+        return Err(anyhow!("{} is before {}", date_to, date_from));
+    }
+    if (to - from).num_days() >= MAX_EXPORT_DAYS {
+        return Err(anyhow!("Can export at most {} days at once", MAX_EXPORT_DAYS));
+    }
+
+    let mut body = String::new();
+    for day in from.iter_days().take_while(|day| *day <= to) {
+        let date = day.to_string();
+        if day_markdown(conn, files, &date)? != format!("# {}\n", date) {
+            body.push_str(&day_section(conn, files, &date)?);
+        }
+    }
+
+    Ok(html_document(&format!("{} to {}", from, to), &body))
+}
+
+fn parse_export_date(date: &str) -> Result<NaiveDate> {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").map_err(|_| anyhow!("Invalid date: {}", date))
+}
+
+// RFC 4180 quoting: wrap fields containing separators, quotes or newlines and double any quotes
+fn csv_field(value: &str) -> String {
+    if value.contains(|c: char| matches!(c, ',' | '"' | '\n' | '\r')) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+// One row per block in the inclusive date range, for spreadsheets. Times stay
+// 24-hour regardless of time_format so spreadsheets parse them.
+pub fn blocks_csv(conn: &Connection, date_from: &str, date_to: &str) -> Result<String> {
+    let mut csv = String::from("date,start_time,end_time,duration_minutes,title,tags,color\r\n");
+
+    for block in load_time_blocks_between(conn, date_from, date_to)? {
+        let row = [
+            block.date.clone(),
+            block.start_time_formatted(),
+            block.end_time_formatted(),
+            block.duration_minutes.to_string(),
+            block.title.clone(),
+            block.tags.join(";"),
+            block.color.clone(),
+        ];
+        let fields: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&fields.join(","));
+        csv.push_str("\r\n");
+    }
+
+    Ok(csv)
+}
+
+// Busy time in the inclusive date range as merged local (start, end) periods, from
+// blocks and timed events. All-day events don't mark time as busy.
+pub fn busy_periods(blocks: &[TimeBlock], events: &[CalendarEvent], from: NaiveDate, to: NaiveDate) -> Vec<(NaiveDateTime, NaiveDateTime)> {
+    let mut periods: Vec<(NaiveDateTime, NaiveDateTime)> = Vec::new();
+    for day in from.iter_days().take_while(|day| *day <= to) {
+        let date = day.to_string();
+        let midnight = day.and_hms_opt(0, 0, 0).unwrap();
+        let ranges = blocks.iter()
+            .filter(|block| block.date == date)
+            .map(block_range)
+            .chain(events.iter().filter_map(|event| event_minute_range(event, &date)))
+            .collect();
+
+        for range in merge_ranges(ranges) {
+            let start = midnight + Duration::minutes(range.start_minutes as i64);
+            let end = midnight + Duration::minutes(range.end_minutes as i64);
+            // Join a period that runs on from the end of the previous day
+            match periods.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => periods.push((start, end)),
+            }
+        }
+    }
+    periods
+}
+
+fn ics_utc(local: NaiveDateTime) -> String {
+    // A time skipped by a DST change has no local instant; fall back to reading it as UTC
+    let utc = Local.from_local_datetime(&local)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local));
+    utc.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+// A VFREEBUSY calendar covering the inclusive range, for sharing availability.
+// Only busy periods are written: no titles, notes, tags or locations.
+pub fn freebusy_ics(conn: &Connection, events: &[CalendarEvent], date_from: &str, date_to: &str) -> Result<String> {
+    let from = parse_export_date(date_from)?;
+    let to = parse_export_date(date_to)?;
+    if to < from {
+        return Err(anyhow!("{} is before {}", date_to, date_from));
+    }
+    if (to - from).num_days() >= MAX_EXPORT_DAYS {
+        return Err(anyhow!("Can export at most {} days at once", MAX_EXPORT_DAYS));
+    }
+
+    let blocks = load_time_blocks_between(conn, date_from, date_to)?;
+    let range_start = from.and_hms_opt(0, 0, 0).unwrap();
+    let range_end = (to + Duration::days(1)).and_hms_opt(0, 0, 0).unwrap();
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//TimeBloc//Free-Busy//EN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        "BEGIN:VFREEBUSY".to_string(),
+        format!("UID:{}", uuid::Uuid::new_v4()),
+        format!("DTSTAMP:{}", Utc::now().format("%Y%m%dT%H%M%SZ")),
+        format!("DTSTART:{}", ics_utc(range_start)),
+        format!("DTEND:{}", ics_utc(range_end)),
+    ];
+    for (start, end) in busy_periods(&blocks, events, from, to) {
+        lines.push(format!("FREEBUSY;FBTYPE=BUSY:{}/{}", ics_utc(start), ics_utc(end)));
+    }
+    lines.push("END:VFREEBUSY".to_string());
+    lines.push("END:VCALENDAR".to_string());
+
+    Ok(lines.join("\r\n") + "\r\n")
+}
+
+// Zip a block's notes and attachment files with a manifest.json describing the block.
+// Attachments are copied in one at a time, so only the zip writer buffers them.
+pub fn block_bundle<W: Write + Seek>(conn: &Connection, files: &FileService, block_id: i64, writer: W) -> Result<W> {
+    let block = load_time_block(conn, block_id)?
+        .ok_or_else(|| anyhow!("Time block not found: {}", block_id))?;
+    let options = zip::write::SimpleFileOptions::default()
+        .compression_method(zip::CompressionMethod::Deflated);
+    let mut zip = zip::ZipWriter::new(writer);
+
+    if let Some(notes_file) = &block.notes_file {
+        zip.start_file("notes.md", options)?;
+        zip.write_all(files.load_notes(notes_file)?.as_bytes())?;
+    }
+
+    let mut included: Vec<String> = Vec::new();
+    let mut missing: Vec<String> = Vec::new();
+    for attachment in query_attachments(conn, block_id)? {
+        let mut source = match files.attachment_path(&attachment.file_path).and_then(|path| Ok(std::fs::File::open(path)?)) {
+            Ok(source) => source,
+            Err(_) => {
+                missing.push(attachment.file_name);
+                continue;
+            }
+        };
+        // Two attachments can share a display name; keep both
+        let name = if included.contains(&attachment.file_name) {
+            format!("{}-{}", attachment.id.unwrap_or_default(), attachment.file_name)
+        } else {
+            attachment.file_name.clone()
+        };
+        zip.start_file(format!("attachments/{}", name), options)?;
+        std::io::copy(&mut source, &mut zip)?;
+        included.push(name);
+    }
+
+    let manifest = serde_json::json!({
+        "title": block.title,
+        "date": block.date,
+        "start_time": block.start_time_formatted(),
+        "end_time": block.end_time_formatted(),
+        "tags": block.tags,
+        "notes": block.notes_file.is_some(),
+        "attachments": included,
+        "missing_attachments": missing,
+    });
+    zip.start_file("manifest.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+
+    Ok(zip.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn insert_block(conn: &Connection, files: &FileService, start_minutes: i32, title: &str, tags: &str, notes: Option<&str>) {
+        conn.execute(
+            "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title, tags) VALUES ('2025-06-01', ?1, 30, ?2, ?3)",
+            (start_minutes, title, tags),
+        ).unwrap();
+        let id = conn.last_insert_rowid();
+
+        if let Some(notes) = notes {
+            let block = TimeBlock {
+                id: Some(id),
+                date: "2025-06-01".to_string(),
+                start_minutes,
+                duration_minutes: 30,
+                title: title.to_string(),
+                notes_file: None,
+                color: "#3b82f6".to_string(),
+                tags: vec![],
+                created_at: None,
+                updated_at: None,
+                reminder_minutes: None,
+                completed: false,
+                actual_duration_minutes: None,
+                notes_content: None,
+                is_placeholder: false,
+            };
+            let path = files.save_notes(&block, notes).unwrap();
+            conn.execute("UPDATE time_blocks SET notes_file = ?1 WHERE id = ?2", (path, id)).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_day_markdown() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+
+        conn.execute(
+            "INSERT INTO priorities (date, content, completed, priority_order) VALUES ('2025-06-01', 'Ship release', 1, 0), ('2025-06-01', 'Write docs', 0, 1)",
+            [],
+        ).unwrap();
+        insert_block(&conn, &files, 600, "Review", "[]", None);
+        insert_block(&conn, &files, 540, "Focus", r#"["deep","work"]"#, Some("Outline the chapter"));
+
+        let markdown = day_markdown(&conn, &files, "2025-06-01").unwrap();
+
+        assert_eq!(markdown, "# 2025-06-01\n\
+            \n## Priorities\n\n\
+            - [x] Ship release\n\
+            - [ ] Write docs\n\
+            \n## 09:00 - 09:30 Focus\n\
+            \nTags: #deep #work\n\
+            \nOutline the chapter\n\
+            \n## 10:00 - 10:30 Review\n");
+    }
+
+    #[test]
+    fn test_range_html_skips_empty_days_and_sanitizes() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+
+        insert_block(&conn, &files, 540, "Focus", "[]", Some("**Bold** <script>alert(1)</script>"));
+
+        let html = range_html(&conn, &files, "2025-05-31", "2025-06-02").unwrap();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("<style>"));
+        assert_eq!(html.matches("<section>").count(), 1);
+        assert!(html.contains("<h2>09:00 - 09:30 Focus</h2>"));
+        assert!(html.contains("<strong>Bold</strong>"));
+        assert!(!html.contains("<script>"));
+
+        assert!(range_html(&conn, &files, "2025-06-02", "2025-06-01").is_err());
+        assert!(day_html(&conn, &files, "June 1st").is_err());
+    }
+
+    #[test]
+    fn test_blocks_csv_escapes_titles() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+
+        insert_block(&conn, &files, 600, r#"Plan Q3, "draft" v2"#, r#"["deep","work"]"#, None);
+        insert_block(&conn, &files, 540, "Standup", "[]", None);
+        conn.execute(
+            "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title) VALUES ('2025-06-03', 540, 30, 'Outside range')",
+            [],
+        ).unwrap();
+
+        let csv = blocks_csv(&conn, "2025-06-01", "2025-06-02").unwrap();
+
+        assert_eq!(csv, "date,start_time,end_time,duration_minutes,title,tags,color\r\n\
+            2025-06-01,09:00,09:30,30,Standup,,#3b82f6\r\n\
+            2025-06-01,10:00,10:30,30,\"Plan Q3, \"\"draft\"\" v2\",deep;work,#3b82f6\r\n");
+    }
+
+    #[test]
+    fn test_block_bundle() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+
+        insert_block(&conn, &files, 540, "Focus", r#"["deep"]"#, Some("Outline the chapter"));
+        let id = conn.last_insert_rowid();
+        let path = files.save_attachment(id, "2025-06-01", b"col1,col2", "budget.csv").unwrap();
+        for file_path in [path.as_str(), "attachments/2025-06-01/gone.png"] {
+            conn.execute(
+                "INSERT INTO attachments (time_block_id, file_path, file_name, file_type) VALUES (?1, ?2, ?3, 'document')",
+                (id, file_path, file_path.rsplit('/').next().unwrap()),
+            ).unwrap();
+        }
+
+        let bytes = block_bundle(&conn, &files, id, std::io::Cursor::new(Vec::new())).unwrap().into_inner();
+        let mut archive = zip::ZipArchive::new(std::io::Cursor::new(bytes)).unwrap();
+        let read = |archive: &mut zip::ZipArchive<_>, name: &str| {
+            let mut content = String::new();
+            std::io::Read::read_to_string(&mut archive.by_name(name).unwrap(), &mut content).unwrap();
+            content
+        };
+
+        assert_eq!(read(&mut archive, "notes.md"), "Outline the chapter");
+        assert!(read(&mut archive, &format!("attachments/{}", path.rsplit('/').next().unwrap())).starts_with("col1"));
+        let manifest: serde_json::Value = serde_json::from_str(&read(&mut archive, "manifest.json")).unwrap();
+        assert_eq!(manifest["title"], "Focus");
+        assert_eq!(manifest["start_time"], "09:00");
+        assert_eq!(manifest["missing_attachments"], serde_json::json!(["gone.png"]));
+        assert_eq!(archive.len(), 3);
+    }
+
+    #[test]
+    fn test_busy_periods_merge_blocks_and_events() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+        insert_block(&conn, &files, 540, "Secret project", r#"["private"]"#, Some("Do not share"));
+        insert_block(&conn, &files, 555, "Overlapping", "[]", None);
+        conn.execute(
+            "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title) VALUES ('2025-06-02', 0, 60, 'Early')",
+            [],
+        ).unwrap();
+
+        let event = |start: &str, end: &str, is_all_day: bool| CalendarEvent {
+            id: None,
+            connection_id: 1,
+            external_id: "evt".to_string(),
+            calendar_id: "primary".to_string(),
+            title: "Dentist".to_string(),
+            start_time: start.to_string(),
+            end_time: end.to_string(),
+            description: None,
+            location: Some("Main St".to_string()),
+            is_all_day,
+            attendees: vec![],
+            last_updated: String::new(),
+            color_id: None,
+        };
+        let events = vec![
+            event("2025-06-01T23:00:00", "2025-06-02T00:30:00", false),
+            event("2025-06-01T00:00:00", "2025-06-02T00:00:00", true),
+        ];
+
+        let day = |d: u32| NaiveDate::from_ymd_opt(2025, 6, d).unwrap();
+        let at = |d: u32, h: u32, m: u32| day(d).and_hms_opt(h, m, 0).unwrap();
+        let blocks = load_time_blocks_between(&conn, "2025-06-01", "2025-06-02").unwrap();
+        assert_eq!(busy_periods(&blocks, &events, day(1), day(2)), vec![
+            (at(1, 9, 0), at(1, 9, 45)),
+            (at(1, 23, 0), at(2, 1, 0)),
+        ]);
+
+        // An event already running when the range starts is busy from its first minute
+        let overnight = vec![event("2025-05-31T22:00:00", "2025-06-01T02:00:00", false)];
+        assert_eq!(busy_periods(&[], &overnight, day(1), day(1)), vec![(at(1, 0, 0), at(1, 2, 0))]);
+
+        let ics = freebusy_ics(&conn, &events, "2025-06-01", "2025-06-02").unwrap();
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("FREEBUSY;FBTYPE=BUSY:").count(), 2);
+        for private in ["Secret", "Dentist", "private", "Main St", "Do not share"] {
+            assert!(!ics.contains(private), "{}", private);
+        }
+    }
+}
@@ -0,0 +1,73 @@
+use anyhow::Result;
+use fs2::FileExt;
+use log::warn;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use tauri::{AppHandle, Manager};
+
+const LOCK_FILE: &str = "timebloc.lock";
+
+// Held for the life of the process so a second instance can't open the same database
+// and search index. The OS drops the lock when the process exits, even after a crash,
+// so a lock file left behind by a dead instance is simply taken over.
+pub struct InstanceLock {
+    _file: File,
+}
+
+impl InstanceLock {
+    // Ok(None) if another running instance holds the lock
+    pub fn acquire(data_dir: &Path) -> Result<Option<Self>> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(data_dir.join(LOCK_FILE))?;
+
+        if file.try_lock_exclusive().is_err() {
+            return Ok(None);
+        }
+
+        let mut previous = String::new();
+        file.read_to_string(&mut previous)?;
+        if !previous.trim().is_empty() {
+            warn!("Clearing stale instance lock left by process {}", previous.trim());
+        }
+
+        // The pid is only informational; the lock itself is what counts
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        write!(file, "{}", std::process::id())?;
+        file.flush()?;
+        Ok(Some(InstanceLock { _file: file }))
+    }
+}
+
+// Bring the main window forward, e.g. when the user launches the app again
+pub fn focus_main_window(app: &AppHandle) {
+    if let Some(window) = app.get_window("main") {
+        let _ = window.unminimize();
+        let _ = window.show();
+        let _ = window.set_focus();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lock_is_exclusive_and_released_on_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join(LOCK_FILE), "999999").unwrap();
+
+        let lock = InstanceLock::acquire(dir.path()).unwrap().expect("stale lock is taken over");
+        let pid = std::fs::read_to_string(dir.path().join(LOCK_FILE)).unwrap();
+        assert_eq!(pid, std::process::id().to_string());
+
+        assert!(InstanceLock::acquire(dir.path()).unwrap().is_none());
+        drop(lock);
+        assert!(InstanceLock::acquire(dir.path()).unwrap().is_some());
+    }
+}
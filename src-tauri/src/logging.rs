@@ -0,0 +1,92 @@
+use anyhow::Result;
+use log::LevelFilter;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Level from TIMEBLOC_LOG (error, warn, info, debug, trace); info by default
+const LEVEL_ENV: &str = "TIMEBLOC_LOG";
+// Set to "off" to log to stderr only
+const FILE_ENV: &str = "TIMEBLOC_LOG_FILE";
+const LOG_FILE: &str = "timebloc.log";
+// The log is rotated to timebloc.log.1 at startup once it grows past this
+const MAX_LOG_BYTES: u64 = 5 * 1024 * 1024;
+pub const DEFAULT_RECENT_LINES: usize = 200;
+pub const MAX_RECENT_LINES: usize = 5000;
+
+// The file logs are written to, if any, for get_recent_logs
+pub struct LogFile(pub Option<PathBuf>);
+
+fn level_from_env() -> LevelFilter {
+    std::env::var(LEVEL_ENV).ok()
+        .and_then(|level| level.trim().parse().ok())
+        .unwrap_or(LevelFilter::Info)
+}
+
+// Log to stderr and, unless turned off, to logs/timebloc.log under `log_dir`.
+// Returns the log file path. Dependencies only log warnings and up.
+pub fn init(log_dir: &Path) -> Result<Option<PathBuf>> {
+    let level = level_from_env();
+    let mut dispatch = fern::Dispatch::new()
+        .format(|out, message, record| {
+            out.finish(format_args!(
+                "{} {:<5} {}: {}",
+                chrono::Local::now().format("%Y-%m-%d %H:%M:%S%.3f"),
+                record.level(),
+                record.target(),
+                message
+            ))
+        })
+        .level(LevelFilter::Warn)
+        .level_for("timebloc", level)
+        .chain(std::io::stderr());
+
+    let file_enabled = !std::env::var(FILE_ENV).is_ok_and(|value| value == "off");
+    let log_file = if file_enabled {
+        let dir = log_dir.join("logs");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join(LOG_FILE);
+        rotate(&path)?;
+        dispatch = dispatch.chain(fern::log_file(&path)?);
+        Some(path)
+    } else {
+        None
+    };
+
+    dispatch.apply()?;
+    Ok(log_file)
+}
+
+fn rotate(path: &Path) -> Result<()> {
+    if fs::metadata(path).is_ok_and(|metadata| metadata.len() > MAX_LOG_BYTES) {
+        fs::rename(path, path.with_extension("log.1"))?;
+    }
+    Ok(())
+}
+
+// The last `lines` lines of the log file, oldest first
+pub fn recent_lines(path: &Path, lines: usize) -> Result<Vec<String>> {
+    let content = match fs::read(path) {
+        Ok(content) => String::from_utf8_lossy(&content).into_owned(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let all: Vec<&str> = content.lines().collect();
+    let start = all.len().saturating_sub(lines);
+    Ok(all[start..].iter().map(|line| line.to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_recent_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(LOG_FILE);
+        assert!(recent_lines(&path, 10).unwrap().is_empty());
+
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        assert_eq!(recent_lines(&path, 2).unwrap(), vec!["two", "three"]);
+        assert_eq!(recent_lines(&path, 10).unwrap().len(), 3);
+    }
+}
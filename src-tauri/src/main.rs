@@ -7,26 +7,66 @@ mod services;
 mod commands;
 mod calendar;
 mod crypto;
-
-use rusqlite::{Connection, Result as SqlResult};
-use std::sync::{Arc, Mutex};
-use tauri::{State, Manager};
+mod reminders;
+mod export;
+mod quick_add;
+mod scheduling;
+mod undo;
+mod media;
+mod vault;
+mod deep_link;
+mod archive;
+mod ocr;
+mod markdown;
+mod error;
+mod oauth;
+mod settings;
+mod instance;
+mod logging;
+mod templates;
+
+use rusqlite::{Connection, OptionalExtension, Result as SqlResult};
+use r2d2_sqlite::SqliteConnectionManager;
+use std::sync::Arc;
+use tauri::{AppHandle, Manager, State};
 use anyhow::Result;
+use log::{debug, error, info, warn};
 
 use models::*;
 use search::SearchService;
 use services::FileService;
 use commands::*;
+use error::{AppError, AppResult};
 use calendar::CalendarService;
+use reminders::ReminderScheduler;
+
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+pub type DbConnection = r2d2::PooledConnection<SqliteConnectionManager>;
 
 // Application state
 pub struct AppState {
-    pub db: Arc<Mutex<Connection>>,
+    pub db: DbPool,
     pub search: Arc<SearchService>,
     pub files: Arc<FileService>,
     pub calendar: Arc<CalendarService>,
 }
 
+// Check out a pooled connection for a command. Reads run concurrently under WAL;
+// writers are serialized by SQLite itself, waiting up to the busy timeout.
+pub fn db_conn(pool: &DbPool) -> AppResult<DbConnection> {
+    pool.get().map_err(AppError::from)
+}
+
+// Per-connection pragmas; these aren't persisted in the database file
+fn configure_connection(conn: &Connection) -> SqlResult<()> {
+    // WAL lets reads proceed alongside a writer and is more robust to crashes
+    conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get::<_, String>(0))?;
+    // SQLite ships with foreign keys off, which silently disables ON DELETE CASCADE
+    conn.pragma_update(None, "foreign_keys", "ON")?;
+    conn.busy_timeout(std::time::Duration::from_millis(5000))?;
+    Ok(())
+}
+
 fn init_database(conn: &Connection) -> SqlResult<()> {
     // Enhanced time blocks with flexible intervals
     conn.execute(
@@ -145,193 +185,311 @@ fn init_database(conn: &Connection) -> SqlResult<()> {
         [],
     )?;
 
+    // Google nextSyncToken per calendar, for incremental syncs
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS calendar_sync_tokens (
+            connection_id INTEGER NOT NULL,
+            calendar_id TEXT NOT NULL,
+            sync_token TEXT NOT NULL,
+            PRIMARY KEY(connection_id, calendar_id),
+            FOREIGN KEY(connection_id) REFERENCES calendar_connections(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Reversible operations for undo, newest last
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS operation_log (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            scope TEXT NOT NULL,  -- e.g. 'time_block'
+            operation TEXT NOT NULL,
+            payload TEXT NOT NULL,  -- JSON of the previous state
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+
+    // Working hours overriding work_hours_start/end, keyed by a YYYY-MM-DD date
+    // or a lowercase weekday name ('monday'); a date beats its weekday
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS day_overrides (
+            key TEXT PRIMARY KEY,
+            start_minutes INTEGER NOT NULL,
+            end_minutes INTEGER NOT NULL
+        )",
+        [],
+    )?;
+
+    // Normalized tags; time_blocks.tags stays as a denormalized JSON copy
+    let needs_tag_backfill = !table_exists(conn, "block_tags")?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS block_tags (
+            time_block_id INTEGER NOT NULL,
+            tag_id INTEGER NOT NULL,
+            PRIMARY KEY (time_block_id, tag_id),
+            FOREIGN KEY(time_block_id) REFERENCES time_blocks(id) ON DELETE CASCADE,
+            FOREIGN KEY(tag_id) REFERENCES tags(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    // Recurring weekly structure, stamped onto a week by apply_week_template
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS week_templates (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS week_template_blocks (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            template_id INTEGER NOT NULL,
+            weekday TEXT NOT NULL,  -- 'monday' .. 'sunday'
+            start_minutes INTEGER NOT NULL,
+            duration_minutes INTEGER NOT NULL,
+            title TEXT NOT NULL,
+            color TEXT,
+            tags TEXT DEFAULT '[]',
+            reminder_minutes INTEGER,
+            FOREIGN KEY(template_id) REFERENCES week_templates(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+
+    // Extra named notes sections; a block's own notes_file is its "default" section
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS block_notes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            time_block_id INTEGER NOT NULL,
+            section_name TEXT NOT NULL,
+            file_path TEXT NOT NULL,
+            updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+            UNIQUE(time_block_id, section_name),
+            FOREIGN KEY(time_block_id) REFERENCES time_blocks(id) ON DELETE CASCADE
+        )",
+        [],
+    )?;
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_block_tags_tag ON block_tags(tag_id)",
+        [],
+    )?;
+
+    // Column migrations for databases created by older versions
+    add_column_if_missing(conn, "time_blocks", "reminder_minutes", "INTEGER")?;
+    add_column_if_missing(conn, "time_blocks", "completed", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "time_blocks", "actual_duration_minutes", "INTEGER")?;
+    add_column_if_missing(conn, "time_blocks", "priority_id", "INTEGER")?;
+    add_column_if_missing(conn, "time_blocks", "is_placeholder", "INTEGER NOT NULL DEFAULT 0")?;
+    add_column_if_missing(conn, "attachments", "content_hash", "TEXT")?;
+    add_column_if_missing(conn, "attachments", "duration_seconds", "REAL")?;
+    add_column_if_missing(conn, "attachments", "thumbnail_path", "TEXT")?;
+    add_column_if_missing(conn, "attachments", "ocr_text", "TEXT")?;
+    add_column_if_missing(conn, "attachments", "mime_type", "TEXT")?;
+    add_column_if_missing(conn, "calendar_connections", "status", "TEXT NOT NULL DEFAULT 'valid'")?;
+    add_column_if_missing(conn, "calendar_connections", "last_error", "TEXT")?;
+    add_column_if_missing(conn, "calendar_events", "color_id", "TEXT")?;
+    add_column_if_missing(conn, "calendar_sync_tokens", "window_end", "TEXT")?;
+
+    conn.execute(
+        "CREATE INDEX IF NOT EXISTS idx_attachments_content_hash ON attachments(content_hash)",
+        [],
+    )?;
+
+    if needs_tag_backfill {
+        backfill_block_tags(conn)?;
+    }
+
     // Insert default settings
     conn.execute(
         "INSERT OR IGNORE INTO settings (key, value) VALUES 
         ('default_time_interval', '30'),
+        ('snap_minutes', '5'),
         ('available_intervals', '[5, 15, 30, 60]'),
+        ('duration_presets', '[15, 30, 45, 60, 90, 120]'),
         ('work_hours_start', '480'),
         ('work_hours_end', '1020'),
-        ('calendar_sync_interval', '5')",
+        ('week_start', 'monday'),
+        ('tag_colors', '{}'),
+        ('event_colors', '{}'),
+        ('time_format', '24h'),
+        ('default_block_title', 'Untitled'),
+        ('max_attachment_size_mb', '100'),
+        ('calendar_sync_interval', '5'),
+        ('calendar_max_retries', '5'),
+        ('calendar_sync_parallelism', '2'),
+        ('calendar_event_retention_days', '90'),
+        ('encryption_key_storage', 'file')",
         [],
     )?;
 
     Ok(())
 }
 
-// Keep existing brain dump and priorities functions for now
-#[tauri::command]
-fn get_priorities(date: String, state: State<AppState>) -> Result<Vec<Priority>, String> {
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn.prepare(
-        "SELECT id, date, content, completed, priority_order FROM priorities 
-         WHERE date = ?1 ORDER BY priority_order"
-    ).map_err(|e| e.to_string())?;
-    
-    let priorities_iter = stmt.query_map([date], |row| {
-        Ok(Priority {
-            id: Some(row.get(0)?),
-            date: row.get(1)?,
-            content: row.get(2)?,
-            completed: row.get(3)?,
-            priority_order: row.get(4).unwrap_or(0),
-            created_at: None,
-        })
-    }).map_err(|e| e.to_string())?;
+fn table_exists(conn: &Connection, table: &str) -> SqlResult<bool> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = ?1",
+        [table],
+        |row| row.get::<_, i64>(0),
+    ).map(|count| count > 0)
+}
 
-    let mut priorities = Vec::new();
-    for priority in priorities_iter {
-        priorities.push(priority.map_err(|e| e.to_string())?);
+// Add a column to an existing table if an older database lacks it
+fn add_column_if_missing(conn: &Connection, table: &str, column: &str, definition: &str) -> SqlResult<()> {
+    let mut stmt = conn.prepare(&format!("PRAGMA table_info({})", table))?;
+    let exists = stmt.query_map([], |row| row.get::<_, String>(1))?
+        .filter_map(|name| name.ok())
+        .any(|name| name == column);
+    
+    if !exists {
+        conn.execute(&format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition), [])?;
     }
     
-    Ok(priorities)
+    Ok(())
 }
 
+// Keep existing brain dump and priorities functions for now
 #[tauri::command]
-fn get_time_blocks(date: String, state: State<AppState>) -> Result<Vec<TimeBlock>, String> {
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn.prepare(
-        "SELECT id, date, start_minutes, duration_minutes, title, notes_file, color, tags, created_at, updated_at 
-         FROM time_blocks WHERE date = ?1 ORDER BY start_minutes"
-    ).map_err(|e| e.to_string())?;
-    
-    let blocks_iter = stmt.query_map([date], |row| {
-        let tags_str: String = row.get(7).unwrap_or_default();
-        let tags: Vec<String> = if tags_str.is_empty() {
-            Vec::new()
-        } else {
-            serde_json::from_str(&tags_str).unwrap_or_default()
-        };
-        
-        Ok(TimeBlock {
-            id: Some(row.get(0)?),
-            date: row.get(1)?,
-            start_minutes: row.get(2)?,
-            duration_minutes: row.get(3)?,
-            title: row.get(4)?,
-            notes_file: row.get(5)?,
-            color: row.get(6).unwrap_or_else(|_| "#3b82f6".to_string()),
-            tags,
-            created_at: row.get(8)?,
-            updated_at: row.get(9)?,
-        })
-    }).map_err(|e| e.to_string())?;
+fn get_priorities(date: String, state: State<AppState>) -> AppResult<Vec<Priority>> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    load_priorities(&conn, &date).map_err(AppError::from)
+}
 
-    let mut blocks = Vec::new();
-    for block in blocks_iter {
-        blocks.push(block.map_err(|e| e.to_string())?);
+// With include_notes, each block's notes come back inline instead of needing a load_notes call per block.
+// With apply_tag_colors, blocks are returned in their tag's color from the tag_colors setting.
+#[tauri::command]
+fn get_time_blocks(
+    date: String,
+    include_notes: Option<bool>,
+    apply_tag_colors: Option<bool>,
+    state: State<AppState>
+) -> AppResult<Vec<TimeBlock>> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    let mut blocks = load_time_blocks(&conn, &date)?;
+    
+    if apply_tag_colors.unwrap_or(false) {
+        commands::apply_tag_colors(&mut blocks, &tag_colors(&conn)?);
+    }
+    
+    if include_notes.unwrap_or(false) {
+        for block in &mut blocks {
+            // A missing or unreadable notes file shouldn't keep the day from loading
+            block.notes_content = block.notes_file.as_ref()
+                .and_then(|notes_file| state.files.load_notes(notes_file).ok());
+        }
     }
     
     Ok(blocks)
 }
 
+// One block by id, e.g. to refresh it after save_time_block returns its id
 #[tauri::command]
-fn get_brain_dump(date: String, state: State<AppState>) -> Result<String, String> {
-    println!("🦀 RUST: Getting brain dump for date: {}", date);
-    let conn = state.db.lock().unwrap();
-    let mut stmt = conn.prepare("SELECT content FROM brain_dumps WHERE date = ?1 ORDER BY updated_at DESC LIMIT 1")
-        .map_err(|e| e.to_string())?;
+fn get_time_block(id: i64, include_notes: Option<bool>, state: State<AppState>) -> AppResult<TimeBlock> {
+    let conn = db_conn(&state.db)?;
+    let mut block = load_time_block(&conn, id)?
+        .ok_or_else(|| AppError::NotFound(format!("Time block not found: {}", id)))?;
     
-    match stmt.query_row([&date], |row| {
-        Ok(row.get::<_, String>(0)?)
-    }) {
-        Ok(content) => {
-            println!("🦀 RUST: Found content: {}", content);
-            Ok(content)
-        },
-        Err(rusqlite::Error::QueryReturnedNoRows) => {
-            println!("🦀 RUST: No content found for date {}", date);
-            Ok(String::new())
-        },
-        Err(e) => {
-            println!("🦀 RUST: Error getting brain dump: {}", e);
-            Err(e.to_string())
-        },
+    if include_notes.unwrap_or(false) {
+        block.notes_content = block.notes_file.as_ref()
+            .and_then(|notes_file| state.files.load_notes(notes_file).ok());
     }
+    
+    Ok(block)
 }
 
 #[tauri::command]
-fn save_brain_dump(date: String, content: String, state: State<AppState>) -> Result<(), String> {
-    println!("🦀 RUST: Saving brain dump for date: {}, content length: {}, content: {}", date, content.len(), content);
-    let conn = state.db.lock().unwrap();
-    
-    // Delete existing brain dump for the date
-    conn.execute("DELETE FROM brain_dumps WHERE date = ?1", [&date])
-        .map_err(|e| e.to_string())?;
+fn get_brain_dump(date: String, state: State<AppState>) -> AppResult<String> {
+    let date = normalize_date(&date)?;
+    let conn = db_conn(&state.db)?;
+    let content = conn.query_row(
+        "SELECT content FROM brain_dumps WHERE date = ?1 ORDER BY updated_at DESC, id DESC LIMIT 1",
+        [&date],
+        |row| row.get::<_, String>(0)
+    ).optional()?;
+    Ok(content.unwrap_or_default())
+}
+
+#[tauri::command]
+fn save_brain_dump(date: String, content: String, state: State<AppState>) -> AppResult<()> {
+    let date = normalize_date(&date)?;
+    debug!("Saving brain dump for {} ({} bytes)", date, content.len());
+    let conn = db_conn(&state.db)?;
+    upsert_brain_dump(&conn, &date, &content)?;
+    Ok(())
+}
+
+// Autosave runs on every pause in typing, so update the day's row in place: created_at
+// keeps the first save and only updated_at moves. Clearing the text keeps the row,
+// emptied; readers treat an empty dump as none.
+fn upsert_brain_dump(conn: &Connection, date: &str, content: &str) -> SqlResult<()> {
+    let updated = conn.execute(
+        "UPDATE brain_dumps SET content = ?2, updated_at = CURRENT_TIMESTAMP
+         WHERE id = (SELECT id FROM brain_dumps WHERE date = ?1 ORDER BY updated_at DESC, id DESC LIMIT 1)",
+        (date, content),
+    )?;
     
-    // Insert new content if not empty
-    if !content.is_empty() {
-        println!("🦀 RUST: Inserting content into database");
+    if updated == 0 && !content.is_empty() {
         conn.execute(
             "INSERT INTO brain_dumps (date, content) VALUES (?1, ?2)",
             (date, content),
-        ).map_err(|e| e.to_string())?;
-        println!("🦀 RUST: Content inserted successfully");
-    } else {
-        println!("🦀 RUST: Content is empty, skipping insert");
+        )?;
     }
-    
     Ok(())
 }
 
 // Calendar commands
 #[tauri::command]
-fn get_google_auth_url(client_id: String, redirect_uri: String, state: State<AppState>) -> Result<String, String> {
+fn get_google_auth_url(client_id: String, redirect_uri: String, state: State<AppState>) -> AppResult<String> {
     Ok(state.calendar.get_google_auth_url(&client_id, &redirect_uri))
 }
 
-#[tauri::command]
-fn start_google_oauth(
-    client_id: String,
-    _client_secret: String,
-    state: State<AppState>
-) -> Result<String, String> {
-    // For now, let's use the out-of-band flow which is simpler
-    let redirect_uri = "urn:ietf:wg:oauth:2.0:oob";
-    
-    // Get the OAuth URL
-    let auth_url = state.calendar.get_google_auth_url(&client_id, redirect_uri);
-    
-    // Open browser with OAuth URL (platform-specific)
+// Open the system browser on a URL
+fn open_in_browser(url: &str) {
     #[cfg(target_os = "macos")]
     let _ = std::process::Command::new("open")
-        .arg(&auth_url)
+        .arg(url)
         .spawn();
     
     #[cfg(target_os = "windows")]
     let _ = std::process::Command::new("cmd")
-        .args(["/C", "start", &auth_url])
+        .args(["/C", "start", "", url])
         .spawn();
     
     #[cfg(target_os = "linux")]
     let _ = std::process::Command::new("xdg-open")
-        .arg(&auth_url)
+        .arg(url)
         .spawn();
-    
-    // Return the auth URL so the frontend knows the process started
-    Ok(auth_url)
 }
 
-#[tauri::command]
-async fn complete_google_oauth(
-    authorization_code: String,
-    client_id: String,
-    client_secret: String,
-    state: State<'_, AppState>
-) -> Result<String, String> {
-    let redirect_uri = "urn:ietf:wg:oauth:2.0:oob";
-    
-    // Exchange code for tokens
-    let (access_token, refresh_token) = state.calendar
-        .exchange_code_for_tokens(&authorization_code, &client_id, &client_secret, redirect_uri)
+// Exchange an authorization code and save the resulting Google connection.
+// Returns the account name.
+async fn finish_google_oauth(
+    calendar: &CalendarService,
+    code: &str,
+    client_id: &str,
+    client_secret: &str,
+    redirect_uri: &str,
+    code_verifier: Option<&str>
+) -> AppResult<String> {
+    let (access_token, refresh_token) = calendar
+        .exchange_code_for_tokens(code, client_id, client_secret, redirect_uri, code_verifier)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::Auth(e.to_string()))?;
 
     // Get user info
-    let account_name = state.calendar
+    let account_name = calendar
         .get_google_user_info(&access_token)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| AppError::Auth(e.to_string()))?;
 
     // Save connection
     let connection = CalendarConnection {
@@ -344,15 +502,79 @@ async fn complete_google_oauth(
         last_sync: None,
         enabled: true,
         created_at: None,
+        status: ConnectionStatus::Valid,
+        last_error: None,
+        next_sync: None,
+        event_count: 0,
     };
 
-    state.calendar
-        .save_connection(&connection)
-        .map_err(|e| e.to_string())?;
+    calendar.save_connection(&connection)?;
 
     Ok(account_name)
 }
 
+// Sign in with Google through a loopback redirect: a short-lived server on 127.0.0.1
+// receives the authorization code and the connection is saved without the user
+// pasting anything. Returns the auth URL right away; the outcome arrives as a
+// "google-oauth-complete" event with the account name or "google-oauth-failed" with
+// an AppError. Starting again cancels an earlier attempt that's still waiting.
+#[tauri::command]
+async fn start_google_oauth(
+    client_id: String,
+    client_secret: String,
+    app: AppHandle,
+    state: State<'_, AppState>,
+    pending: State<'_, oauth::PendingOAuth>
+) -> AppResult<String> {
+    let (listener, redirect_uri) = oauth::bind_loopback().await?;
+    let csrf_state = uuid::Uuid::new_v4().to_string();
+    let pkce = oauth::Pkce::new()?;
+    let auth_url = format!(
+        "{}&state={}&code_challenge={}&code_challenge_method=S256",
+        state.calendar.get_google_auth_url(&client_id, &redirect_uri),
+        csrf_state,
+        pkce.challenge
+    );
+    
+    let cancel = pending.begin();
+    let calendar = state.calendar.clone();
+    tauri::async_runtime::spawn(async move {
+        let result: AppResult<String> = async {
+            let code = oauth::wait_for_code(listener, &csrf_state, cancel, oauth::LOOPBACK_TIMEOUT).await?;
+            finish_google_oauth(&calendar, &code, &client_id, &client_secret, &redirect_uri, Some(&pkce.verifier)).await
+        }.await;
+        
+        let emitted = match result {
+            Ok(account_name) => app.emit_all("google-oauth-complete", account_name),
+            Err(e) => app.emit_all("google-oauth-failed", e),
+        };
+        if let Err(e) = emitted {
+            error!("Failed to emit OAuth result: {}", e);
+        }
+    });
+    
+    open_in_browser(&auth_url);
+    Ok(auth_url)
+}
+
+// Stop waiting for a sign-in started with start_google_oauth, e.g. when the user closes the dialog
+#[tauri::command]
+fn cancel_google_oauth(pending: State<oauth::PendingOAuth>) {
+    pending.cancel();
+}
+
+// Finish a sign-in manually with a code the user copied, for a redirect the app doesn't serve
+#[tauri::command]
+async fn complete_google_oauth(
+    authorization_code: String,
+    client_id: String,
+    client_secret: String,
+    redirect_uri: String,
+    state: State<'_, AppState>
+) -> AppResult<String> {
+    finish_google_oauth(&state.calendar, &authorization_code, &client_id, &client_secret, &redirect_uri, None).await
+}
+
 #[tauri::command]
 async fn exchange_google_code(
     code: String,
@@ -360,78 +582,93 @@ async fn exchange_google_code(
     client_secret: String,
     redirect_uri: String,
     state: State<'_, AppState>
-) -> Result<String, String> {
-    let (access_token, refresh_token) = state.calendar
-        .exchange_code_for_tokens(&code, &client_id, &client_secret, &redirect_uri)
-        .await
-        .map_err(|e| e.to_string())?;
+) -> AppResult<String> {
+    finish_google_oauth(&state.calendar, &code, &client_id, &client_secret, &redirect_uri, None).await
+}
 
-    // Get user info
-    let account_name = state.calendar
-        .get_google_user_info(&access_token)
-        .await
-        .map_err(|e| e.to_string())?;
+#[tauri::command]
+fn get_calendar_connections(state: State<AppState>) -> AppResult<Vec<CalendarConnection>> {
+    state.calendar.get_connections().map_err(AppError::from)
+}
 
-    // Save connection
-    let connection = CalendarConnection {
-        id: None,
-        provider: "google".to_string(),
-        account_name: account_name.clone(),
-        access_token,
-        refresh_token,
-        calendar_list: vec!["primary".to_string()], // Default to primary calendar
-        last_sync: None,
-        enabled: true,
-        created_at: None,
-    };
+// The tail of the log file, for the diagnostics view
+#[tauri::command]
+fn get_recent_logs(lines: Option<usize>, log_file: State<logging::LogFile>) -> AppResult<Vec<String>> {
+    let lines = lines.unwrap_or(logging::DEFAULT_RECENT_LINES).min(logging::MAX_RECENT_LINES);
+    match &log_file.0 {
+        Some(path) => logging::recent_lines(path, lines).map_err(AppError::from),
+        None => Ok(Vec::new()),
+    }
+}
 
+#[tauri::command]
+fn get_calendar_events(date: String, state: State<AppState>) -> AppResult<Vec<CalendarEvent>> {
+    let date = normalize_date(&date)?;
+    // Get events for the specific date
     state.calendar
-        .save_connection(&connection)
-        .map_err(|e| e.to_string())?;
+        .get_events_for_date_range(&date, &date)
+        .map_err(AppError::from)
+}
 
-    Ok(account_name)
+#[tauri::command]
+fn get_security_status(state: State<AppState>) -> AppResult<SecurityStatus> {
+    state.calendar.security_status().map_err(AppError::from)
 }
 
 #[tauri::command]
-fn get_calendar_connections(state: State<AppState>) -> Result<Vec<CalendarConnection>, String> {
-    println!("🔥 Rust: get_calendar_connections called");
-    match state.calendar.get_connections() {
-        Ok(connections) => {
-            println!("🔥 Rust: Found {} connections in database", connections.len());
-            for conn in &connections {
-                println!("🔥 Rust: Connection - ID: {:?}, Provider: {}, Account: {}", 
-                    conn.id, conn.provider, conn.account_name);
-            }
-            Ok(connections)
-        },
-        Err(e) => {
-            println!("🔥 Rust: Error getting connections: {}", e);
-            Err(e.to_string())
-        }
-    }
+fn reencrypt_plaintext_tokens(state: State<AppState>) -> AppResult<usize> {
+    state.calendar.reencrypt_plaintext_tokens().map_err(AppError::from)
 }
 
 #[tauri::command]
-fn get_calendar_events(date: String, state: State<AppState>) -> Result<Vec<CalendarEvent>, String> {
-    // Get events for the specific date
+fn migrate_key_to_keychain(state: State<AppState>) -> AppResult<SecurityStatus> {
+    state.calendar.migrate_key_to_keychain()?;
+    state.calendar.security_status().map_err(AppError::from)
+}
+
+// "Test connection" before saving: nothing is stored, the account name comes back on success
+#[tauri::command]
+async fn test_connection(provider: String, params: ConnectionParams, state: State<'_, AppState>) -> AppResult<String> {
     state.calendar
-        .get_events_for_date_range(&date, &date)
-        .map_err(|e| e.to_string())
+        .test_connection(&provider, &params)
+        .await
+        .map_err(AppError::from)
 }
 
+// Check a connection's token with Google and record the result on the connection
 #[tauri::command]
-async fn sync_calendars(state: State<'_, AppState>) -> Result<i32, String> {
+async fn validate_connection(connection_id: i64, state: State<'_, AppState>) -> AppResult<ConnectionStatus> {
+    state.calendar
+        .validate_connection(connection_id)
+        .await
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn sync_calendars(state: State<'_, AppState>) -> AppResult<i32> {
     state.calendar
         .sync_all_calendars()
         .await
-        .map_err(|e| e.to_string())
+        .map_err(AppError::from)
 }
 
+// Drop synced events past the retention window now rather than after the next sync
 #[tauri::command]
-async fn save_firebase_calendar_connection(connection: serde_json::Value, state: State<'_, AppState>) -> Result<(), String> {
-    println!("🔥 Rust: save_firebase_calendar_connection called");
-    println!("🔥 Rust: Raw connection data: {}", connection);
-    
+fn purge_calendar_events(state: State<AppState>) -> AppResult<usize> {
+    state.calendar.purge_all_old_events().map_err(AppError::from)
+}
+
+// Per-connection report of a sync, for the sync results view
+#[tauri::command]
+async fn sync_calendars_detailed(state: State<'_, AppState>) -> AppResult<Vec<ConnectionSyncResult>> {
+    state.calendar
+        .sync_calendars_detailed()
+        .await
+        .map_err(AppError::from)
+}
+
+#[tauri::command]
+async fn save_firebase_calendar_connection(connection: serde_json::Value, state: State<'_, AppState>) -> AppResult<()> {
     let firebase_connection = CalendarConnection {
         id: None,
         provider: connection["provider"].as_str().unwrap_or("").to_string(),
@@ -442,29 +679,19 @@ async fn save_firebase_calendar_connection(connection: serde_json::Value, state:
         last_sync: None,
         enabled: true,
         created_at: None,
+        status: ConnectionStatus::Valid,
+        last_error: None,
+        next_sync: None,
+        event_count: 0,
     };
 
-    println!("🔥 Rust: Parsed connection - provider: {}, account: {}, token_length: {}", 
-        firebase_connection.provider, 
-        firebase_connection.account_name,
-        firebase_connection.access_token.len()
-    );
-
-    match state.calendar.save_connection(&firebase_connection) {
-        Ok(id) => {
-            println!("🔥 Rust: Connection saved successfully with ID: {}", id);
-            Ok(())
-        },
-        Err(e) => {
-            println!("🔥 Rust: Failed to save connection: {}", e);
-            Err(e.to_string())
-        }
-    }
+    state.calendar.save_connection(&firebase_connection)?;
+    Ok(())
 }
 
 #[tauri::command]
-async fn remove_calendar_connection(connection_id: String, state: State<'_, AppState>) -> Result<(), String> {
-    let conn = state.db.lock().unwrap();
+async fn remove_calendar_connection(connection_id: String, state: State<'_, AppState>) -> AppResult<()> {
+    let conn = db_conn(&state.db)?;
     
     // Parse connection ID to extract the numeric ID if needed
     let numeric_id = if let Ok(id) = connection_id.parse::<i64>() {
@@ -473,16 +700,16 @@ async fn remove_calendar_connection(connection_id: String, state: State<'_, AppS
         // If it's in format "provider-uid", we need to find it in the database
         let mut stmt = conn.prepare(
             "SELECT id FROM calendar_connections WHERE provider || '-' || id = ?1"
-        ).map_err(|e| e.to_string())?;
+        )?;
         
         let mut rows = stmt.query_map([&connection_id], |row| {
             Ok(row.get::<_, i64>(0)?)
-        }).map_err(|e| e.to_string())?;
+        })?;
         
         if let Some(row) = rows.next() {
-            row.map_err(|e| e.to_string())?
+            row?
         } else {
-            return Err(format!("Connection not found: {}", connection_id));
+            return Err(AppError::NotFound(format!("Connection not found: {}", connection_id)));
         }
     };
     
@@ -490,87 +717,283 @@ async fn remove_calendar_connection(connection_id: String, state: State<'_, AppS
     let affected = conn.execute(
         "DELETE FROM calendar_connections WHERE id = ?1",
         [numeric_id]
-    ).map_err(|e| e.to_string())?;
+    )?;
     
     if affected == 0 {
-        return Err(format!("Connection not found: {}", connection_id));
+        return Err(AppError::NotFound(format!("Connection not found: {}", connection_id)));
     }
     
     Ok(())
 }
 
+// Let convertFileSrc URLs reach the attachments of a (possibly custom) data directory
+pub fn allow_attachment_assets(app: &AppHandle, data_dir: &std::path::Path) {
+    if let Err(e) = app.asset_protocol_scope().allow_directory(data_dir.join("attachments"), true) {
+        warn!("Failed to allow attachment previews from {:?}: {}", data_dir, e);
+    }
+}
+
 fn main() {
+    // Must run before the builder so a second launch hands its link to this instance
+    tauri_plugin_deep_link::prepare("com.timeblock.react.app");
+    
     tauri::Builder::default()
         .setup(|app| {
-            // Get data directory
-            let data_dir = app.path_resolver()
+            // Get data directory, honoring a user-chosen vault location
+            let default_data_dir = app.path_resolver()
                 .app_data_dir()
                 .expect("Failed to get app data directory");
+            // Logs stay in the app's own directory whichever vault is open
+            let log_file = match logging::init(&default_data_dir) {
+                Ok(log_file) => log_file,
+                Err(e) => {
+                    eprintln!("Failed to set up logging: {}", e);
+                    None
+                }
+            };
+            app.manage(logging::LogFile(log_file));
             
-            println!("🔥 App data directory: {:?}", data_dir);
+            let data_dir = vault::resolve_data_dir(&default_data_dir);
+            info!("Data directory: {:?}", data_dir);
             
             std::fs::create_dir_all(&data_dir)?;
             
+            // deep_link::prepare normally hands a second launch to the running instance;
+            // this catches the cases it can't, before the database or index are opened
+            let instance_lock = match instance::InstanceLock::acquire(&data_dir)? {
+                Some(lock) => lock,
+                None => {
+                    warn!("TimeBloc is already running with {:?}; exiting", data_dir);
+                    std::process::exit(0);
+                }
+            };
+            app.manage(instance_lock);
+            
             // Initialize database
-            let db_path = data_dir.join("timeblock.db");
-            println!("🔥 Database path: {:?}", db_path);
+            let db_path = data_dir.join(vault::DATABASE_FILE);
+            debug!("Database path: {:?}", db_path);
             
-            let conn = Connection::open(&db_path)
+            let manager = SqliteConnectionManager::file(&db_path)
+                .with_init(|conn| configure_connection(conn));
+            let db_pool = r2d2::Pool::new(manager)
                 .expect("Failed to open database");
-            init_database(&conn)
+            init_database(&db_pool.get().expect("Failed to open database"))
                 .expect("Failed to initialize database");
             
-            println!("🔥 Database initialized successfully");
-            
-            // Wrap database connection for sharing
-            let db_arc = Arc::new(Mutex::new(conn));
-            
             // Initialize services
             let search_service = SearchService::new(&data_dir)
                 .expect("Failed to initialize search service");
             let file_service = FileService::new(data_dir.clone())
                 .expect("Failed to initialize file service");
-            let calendar_service = CalendarService::new(db_arc.clone(), data_dir.clone());
+            // assetScope in tauri.conf.json only covers the default data directory
+            allow_attachment_assets(&app.handle(), &data_dir);
+            let calendar_service = CalendarService::new(db_pool.clone(), data_dir.clone());
+            
+            if search_service.was_rebuilt() {
+                let conn = db_pool.get().expect("Failed to open database");
+                match reindex_search(&conn, &file_service, &search_service) {
+                    Ok(count) => info!("Rebuilt search index with {} blocks", count),
+                    Err(e) => error!("Failed to rebuild search index: {}", e),
+                }
+            }
             
             // Setup application state
             let app_state = AppState {
-                db: db_arc,
+                db: db_pool,
                 search: Arc::new(search_service),
                 files: Arc::new(file_service),
                 calendar: Arc::new(calendar_service),
             };
             
+            // Start desktop reminders for today's blocks
+            let scheduler = ReminderScheduler::start(app.handle(), app_state.db.clone());
+            app.listen_global("blocks-changed", move |_| scheduler.reload());
+            
             app.manage(app_state);
+            app.manage(oauth::PendingOAuth::default());
+            
+            // timebloc://date/YYYY-MM-DD links
+            app.manage(deep_link::PendingNavigation(std::sync::Mutex::new(deep_link::launch_date())));
+            deep_link::register(app.handle());
             
             Ok(())
         })
         .invoke_handler(tauri::generate_handler![
             get_time_blocks,
+            get_time_block,
             save_time_block,
+            save_time_blocks,
+            move_time_block,
             delete_time_block,
+            undo_last,
+            mark_block_complete,
+            set_block_color,
+            schedule_priority,
+            split_block_pomodoro,
+            merge_blocks,
+            save_week_template,
+            list_week_templates,
+            delete_week_template,
+            apply_week_template,
+            copy_day,
+            repeat_block_this_week,
+            get_day_summary,
+            get_populated_dates,
+            get_adjacent_populated_date,
+            get_variance_report,
             get_priorities,
             save_priorities,
             get_brain_dump,
             save_brain_dump,
             search_content,
+            search_in_date,
+            search_by_tag,
+            search_suggest,
+            archive_before,
+            search_archive,
+            get_stats,
+            reindex_all,
+            set_day_hours,
+            reocr_attachment,
+            get_effective_hours,
+            get_week_dates,
             get_settings,
+            take_pending_navigation,
+            get_data_directory,
+            set_data_directory,
+            list_all_tags,
+            suggest_tags,
+            rename_tag,
+            delete_tag,
+            get_blocks_by_tag,
             update_setting,
+            export_settings,
+            import_settings,
             get_available_intervals,
+            get_duration_presets,
+            get_palette,
+            save_palette,
             load_notes,
             save_attachment,
+            save_attachment_from_path,
+            save_attachment_data_url,
+            delete_attachment,
+            rename_attachment,
+            move_attachment_to_block,
+            get_storage_stats,
+            get_attachment_metadata,
+            export_day_markdown,
+            export_day_html,
+            export_range_html,
+            export_csv,
+            export_block_bundle,
+            export_freebusy_ics,
+            parse_quick_add,
+            snap_start_minutes,
+            find_free_slots,
+            get_block_conflicts,
+            get_calendar_events_as_blocks,
             get_attachments,
+            get_day_attachments,
             load_attachment,
+            get_attachment_path,
+            load_thumbnail,
+            backfill_thumbnails,
             get_time_block_notes,
+            save_block_notes,
+            save_block_note_section,
+            list_block_note_sections,
+            load_block_note_section,
+            render_notes_html,
             get_google_auth_url,
             exchange_google_code,
             start_google_oauth,
+            cancel_google_oauth,
             complete_google_oauth,
             get_calendar_connections,
             get_calendar_events,
+            purge_calendar_events,
             sync_calendars,
+            sync_calendars_detailed,
+            validate_connection,
+            test_connection,
+            get_security_status,
+            health_check,
+            get_recent_logs,
+            verify_integrity,
+            reencrypt_plaintext_tokens,
+            migrate_key_to_keychain,
             save_firebase_calendar_connection,
             remove_calendar_connection
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_deleting_block_cascades_to_attachments() {
+        let conn = Connection::open_in_memory().unwrap();
+        configure_connection(&conn).unwrap();
+        init_database(&conn).unwrap();
+        
+        conn.execute(
+            "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title) VALUES ('2025-06-01', 540, 30, 'Focus')",
+            [],
+        ).unwrap();
+        let block_id = conn.last_insert_rowid();
+        conn.execute(
+            "INSERT INTO attachments (time_block_id, file_path, file_name, file_type) VALUES (?1, 'a/b.png', 'b.png', 'image')",
+            [block_id],
+        ).unwrap();
+        
+        conn.execute("DELETE FROM time_blocks WHERE id = ?1", [block_id]).unwrap();
+        
+        let remaining: i64 = conn.query_row("SELECT COUNT(*) FROM attachments", [], |row| row.get(0)).unwrap();
+        assert_eq!(remaining, 0);
+    }
+    
+    #[test]
+    fn test_panicking_holder_returns_connection_to_pool() {
+        // A single in-memory connection, so the read below must reuse the one the panicking thread held
+        let pool = r2d2::Pool::builder()
+            .max_size(1)
+            .build(SqliteConnectionManager::memory().with_init(|conn| configure_connection(conn)))
+            .unwrap();
+        init_database(&pool.get().unwrap()).unwrap();
+        
+        let holder = pool.clone();
+        let result = std::thread::spawn(move || {
+            let _conn = holder.get().unwrap();
+            panic!("command failed while holding a connection");
+        }).join();
+        assert!(result.is_err());
+        
+        let conn = db_conn(&pool).unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM time_blocks", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 0);
+    }
+    
+    #[test]
+    fn test_brain_dump_upsert_keeps_created_at() {
+        let conn = Connection::open_in_memory().unwrap();
+        init_database(&conn).unwrap();
+        
+        upsert_brain_dump(&conn, "2025-06-01", "first").unwrap();
+        conn.execute("UPDATE brain_dumps SET created_at = '2025-06-01 08:00:00'", []).unwrap();
+        upsert_brain_dump(&conn, "2025-06-01", "first thoughts").unwrap();
+        upsert_brain_dump(&conn, "2025-06-02", "").unwrap();
+        
+        let rows: Vec<(String, String, String)> = conn.prepare("SELECT date, content, created_at FROM brain_dumps").unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?))).unwrap()
+            .collect::<SqlResult<_>>().unwrap();
+        assert_eq!(rows, vec![(
+            "2025-06-01".to_string(),
+            "first thoughts".to_string(),
+            "2025-06-01 08:00:00".to_string(),
+        )]);
+    }
+}
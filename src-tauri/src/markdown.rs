@@ -0,0 +1,53 @@
+use pulldown_cmark::{html, Options, Parser};
+
+// Render notes Markdown to HTML that's safe to put in the page. Raw HTML pasted into
+// notes survives only as far as ammonia's allowlist goes: no scripts, styles,
+// on* handlers or javascript: links.
+pub fn render_html(markdown: &str) -> String {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TASKLISTS;
+    let mut unsanitized = String::new();
+    html::push_html(&mut unsanitized, Parser::new_ext(markdown, options));
+
+    // Task list items render as inputs; allow them only as disabled checkboxes
+    ammonia::Builder::default()
+        .add_tags(&["input"])
+        .add_tag_attributes("input", &["checked"])
+        .set_tag_attribute_value("input", "type", "checkbox")
+        .set_tag_attribute_value("input", "disabled", "")
+        .clean(&unsanitized)
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_renders_markdown() {
+        let html = render_html("# Plan\n\n- **ship** it\n- ~~later~~");
+        assert!(html.contains("<h1>Plan</h1>"));
+        assert!(html.contains("<strong>ship</strong>"));
+        assert!(html.contains("<del>later</del>"));
+    }
+
+    #[test]
+    fn test_task_lists_keep_checkboxes() {
+        let html = render_html("- [x] done\n- [ ] todo\n\n<input type=\"text\" name=\"password\">");
+        assert!(html.contains("checked"));
+        assert_eq!(html.matches("type=\"checkbox\"").count(), 3);
+        assert!(!html.contains("type=\"text\""));
+        assert!(!html.contains("password"));
+    }
+
+    #[test]
+    fn test_strips_scripts_and_handlers() {
+        let html = render_html(
+            "Hi <script>alert(1)</script><img src=\"a.png\" onerror=\"alert(2)\"> [x](javascript:alert(3))",
+        );
+        assert!(!html.contains("<script"));
+        assert!(!html.contains("alert(1)"));
+        assert!(!html.contains("onerror"));
+        assert!(!html.contains("javascript:"));
+        assert!(html.contains("<img src=\"a.png\">"));
+    }
+}
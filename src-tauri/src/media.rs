@@ -0,0 +1,274 @@
+// Lightweight header parsing for attachment metadata and previews. Unrecognized
+// content returns None rather than an error.
+
+// Placeholder stored as a thumbnail_path when a preview can't be rendered
+pub const GENERIC_DOCUMENT_THUMBNAIL: &str = "builtin:document";
+
+pub const GENERIC_DOCUMENT_SVG: &str = r##"<svg xmlns="http://www.w3.org/2000/svg" width="96" height="128" viewBox="0 0 96 128"><path d="M8 4h56l24 24v92a4 4 0 0 1-4 4H8a4 4 0 0 1-4-4V8a4 4 0 0 1 4-4z" fill="#f3f4f6" stroke="#9ca3af" stroke-width="4"/><path d="M64 4v24h24" fill="none" stroke="#9ca3af" stroke-width="4"/><text x="48" y="88" font-family="sans-serif" font-size="22" font-weight="bold" text-anchor="middle" fill="#dc2626">PDF</text></svg>"##;
+
+fn is_pdf(filename: &str, data: &[u8]) -> bool {
+    filename.to_ascii_lowercase().ends_with(".pdf") && data.starts_with(b"%PDF-")
+}
+
+// Placeholder thumbnail for a document attachment: PDFs whose first page couldn't
+// be rendered (see pdf_thumbnail) get the generic icon; other documents get none.
+pub fn document_thumbnail(filename: &str, data: &[u8]) -> Option<String> {
+    if is_pdf(filename, data) {
+        Some(GENERIC_DOCUMENT_THUMBNAIL.to_string())
+    } else {
+        None
+    }
+}
+
+// MIME type and attachment category ('image', 'audio', 'document') sniffed from the
+// file's magic bytes. Err carries the MIME type of recognized content we don't accept
+// (executables, archives, video...); Ok(None) means the bytes weren't recognized.
+pub fn detect_file_type(data: &[u8]) -> Result<Option<(&'static str, &'static str)>, &'static str> {
+    let kind = match infer::get(data) {
+        Some(kind) => kind,
+        None => return Ok(None),
+    };
+    let mime_type = kind.mime_type();
+
+    let category = match kind.matcher_type() {
+        infer::MatcherType::Image => "image",
+        infer::MatcherType::Audio => "audio",
+        infer::MatcherType::Doc => "document",
+        _ if mime_type == "application/pdf" || mime_type == "application/rtf" => "document",
+        _ => return Err(mime_type),
+    };
+    Ok(Some((mime_type, category)))
+}
+
+// Longest edge of a generated image thumbnail, in pixels
+const THUMBNAIL_SIZE: u32 = 256;
+
+// PNG preview of an image attachment, or None if the format isn't supported
+pub fn image_thumbnail(data: &[u8]) -> Option<Vec<u8>> {
+    let image = image::load_from_memory(data).ok()?;
+    let mut png = Vec::new();
+    image.thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+        .ok()?;
+    Some(png)
+}
+
+pub fn pdf_rendering_available() -> bool {
+    cfg!(feature = "pdf-thumbnails")
+}
+
+// PNG of a PDF's first page, scaled like image thumbnails. Needs the `pdf-thumbnails`
+// cargo feature and the Pdfium library at runtime; None means fall back to
+// document_thumbnail.
+#[cfg(feature = "pdf-thumbnails")]
+pub fn pdf_thumbnail(filename: &str, data: &[u8]) -> Option<Vec<u8>> {
+    use pdfium_render::prelude::*;
+
+    if !is_pdf(filename, data) {
+        return None;
+    }
+    let pdfium = Pdfium::new(Pdfium::bind_to_system_library().ok()?);
+    let document = pdfium.load_pdf_from_byte_slice(data, None).ok()?;
+    let page = document.pages().first().ok()?;
+    let bitmap = page.render_with_config(&PdfRenderConfig::new()
+        .set_target_width(THUMBNAIL_SIZE as i32)
+        .set_maximum_height(THUMBNAIL_SIZE as i32)).ok()?;
+
+    let rendered = image::RgbaImage::from_raw(bitmap.width() as u32, bitmap.height() as u32, bitmap.as_rgba_bytes())?;
+    let mut png = Vec::new();
+    rendered.write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png).ok()?;
+    Some(png)
+}
+
+#[cfg(not(feature = "pdf-thumbnails"))]
+pub fn pdf_thumbnail(_filename: &str, _data: &[u8]) -> Option<Vec<u8>> {
+    None
+}
+
+// Only WAV and MP3 are recognized
+pub fn audio_duration_seconds(data: &[u8]) -> Option<f64> {
+    wav_duration(data).or_else(|| mp3_duration(data))
+}
+
+fn u32_le(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+// RIFF/WAVE: duration is the data chunk size divided by the fmt chunk's byte rate
+fn wav_duration(data: &[u8]) -> Option<f64> {
+    if data.get(0..4)? != b"RIFF" || data.get(8..12)? != b"WAVE" {
+        return None;
+    }
+
+    let mut byte_rate = None;
+    let mut offset = 12;
+    while offset + 8 <= data.len() {
+        let chunk_id = &data[offset..offset + 4];
+        let chunk_size = u32_le(data, offset + 4)? as usize;
+        let body = offset + 8;
+
+        if chunk_id == b"fmt " {
+            // audio_format(2) channels(2) sample_rate(4) byte_rate(4)
+            byte_rate = Some(u32_le(data, body + 8)?);
+        } else if chunk_id == b"data" {
+            let rate = byte_rate.filter(|&rate| rate > 0)?;
+            // Streams written before their length is known report a bogus size
+            let size = chunk_size.min(data.len() - body);
+            return Some(size as f64 / rate as f64);
+        }
+
+        // Chunks are padded to an even length
+        offset = body + chunk_size + (chunk_size & 1);
+    }
+
+    None
+}
+
+// MPEG audio Layer III. Uses the Xing/Info frame count when present (VBR),
+// otherwise estimates from the first frame's bitrate (CBR).
+fn mp3_duration(data: &[u8]) -> Option<f64> {
+    const BITRATES_V1: [u32; 15] = [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320];
+    const BITRATES_V2: [u32; 15] = [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160];
+
+    // Skip an ID3v2 tag; its size is a 28-bit syncsafe integer
+    let mut offset = 0;
+    if data.get(0..3)? == b"ID3" {
+        let size = data.get(6..10)?
+            .iter()
+            .fold(0usize, |acc, &b| (acc << 7) | (b & 0x7f) as usize);
+        offset = 10 + size;
+    }
+
+    // Find the first frame sync
+    while offset + 4 <= data.len() && !(data[offset] == 0xff && data[offset + 1] & 0xe0 == 0xe0) {
+        offset += 1;
+    }
+    let header = data.get(offset..offset + 4)?;
+
+    let version = (header[1] >> 3) & 0x03;  // 0 = MPEG 2.5, 2 = MPEG 2, 3 = MPEG 1
+    let layer = (header[1] >> 1) & 0x03;    // 1 = Layer III
+    let bitrate_index = (header[2] >> 4) as usize;
+    let sample_rate_index = ((header[2] >> 2) & 0x03) as usize;
+    let mono = header[3] >> 6 == 3;
+
+    if version == 1 || layer != 1 || bitrate_index == 0 || bitrate_index >= 15 || sample_rate_index == 3 {
+        return None;
+    }
+
+    let (bitrate_kbps, sample_rate, samples_per_frame) = match version {
+        3 => (BITRATES_V1[bitrate_index], [44100, 48000, 32000][sample_rate_index], 1152),
+        2 => (BITRATES_V2[bitrate_index], [22050, 24000, 16000][sample_rate_index], 576),
+        _ => (BITRATES_V2[bitrate_index], [11025, 12000, 8000][sample_rate_index], 576),
+    };
+
+    // The Xing/Info header sits after the side information in the first frame
+    let side_info = match (version == 3, mono) {
+        (true, false) => 32,
+        (true, true) => 17,
+        (false, false) => 17,
+        (false, true) => 9,
+    };
+    let xing = offset + 4 + side_info;
+    if matches!(data.get(xing..xing + 4), Some(b"Xing") | Some(b"Info")) {
+        let flags = u32_be(data, xing + 4)?;
+        if flags & 0x01 != 0 {
+            let frames = u32_be(data, xing + 8)?;
+            return Some(frames as f64 * samples_per_frame as f64 / sample_rate as f64);
+        }
+    }
+
+    let audio_bytes = (data.len() - offset) as f64;
+    Some(audio_bytes * 8.0 / (bitrate_kbps as f64 * 1000.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wav(byte_rate: u32, data_len: usize) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&((36 + data_len) as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        bytes.extend_from_slice(b"fmt ");
+        bytes.extend_from_slice(&16u32.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());      // PCM
+        bytes.extend_from_slice(&1u16.to_le_bytes());      // Mono
+        bytes.extend_from_slice(&8000u32.to_le_bytes());   // Sample rate
+        bytes.extend_from_slice(&byte_rate.to_le_bytes());
+        bytes.extend_from_slice(&2u16.to_le_bytes());      // Block align
+        bytes.extend_from_slice(&16u16.to_le_bytes());     // Bits per sample
+        bytes.extend_from_slice(b"data");
+        bytes.extend_from_slice(&(data_len as u32).to_le_bytes());
+        bytes.resize(bytes.len() + data_len, 0);
+        bytes
+    }
+
+    #[test]
+    fn test_wav_duration() {
+        let duration = audio_duration_seconds(&wav(16000, 40000)).unwrap();
+        assert!((duration - 2.5).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cbr_mp3_duration() {
+        // MPEG 1 Layer III, 128 kbps, 44.1 kHz, stereo; 32000 bytes is two seconds
+        let mut bytes = vec![0xff, 0xfb, 0x90, 0x00];
+        bytes.resize(32000, 0);
+        let duration = audio_duration_seconds(&bytes).unwrap();
+        assert!((duration - 2.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_vbr_mp3_uses_xing_frame_count() {
+        let mut bytes = vec![0xff, 0xfb, 0x90, 0x00];
+        bytes.resize(4 + 32, 0);
+        bytes.extend_from_slice(b"Xing");
+        bytes.extend_from_slice(&1u32.to_be_bytes());
+        bytes.extend_from_slice(&100u32.to_be_bytes());
+        bytes.resize(2000, 0);
+
+        let duration = audio_duration_seconds(&bytes).unwrap();
+        assert!((duration - 100.0 * 1152.0 / 44100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_pdf_gets_generic_thumbnail() {
+        assert_eq!(document_thumbnail("Report.PDF", b"%PDF-1.7\n..."), Some(GENERIC_DOCUMENT_THUMBNAIL.to_string()));
+        assert_eq!(document_thumbnail("report.pdf", b"not really a pdf"), None);
+        assert_eq!(document_thumbnail("notes.docx", b"PK\x03\x04"), None);
+        // Not a PDF at all, so nothing to render whether or not the feature is on
+        assert_eq!(pdf_thumbnail("report.pdf", b"not really a pdf"), None);
+    }
+
+    #[test]
+    fn test_image_thumbnail_fits_size() {
+        let mut png = Vec::new();
+        image::RgbImage::new(1024, 512)
+            .write_to(&mut std::io::Cursor::new(&mut png), image::ImageFormat::Png)
+            .unwrap();
+        
+        let thumbnail = image::load_from_memory(&image_thumbnail(&png).unwrap()).unwrap();
+        assert_eq!((thumbnail.width(), thumbnail.height()), (256, 128));
+        assert_eq!(image_thumbnail(b"not an image"), None);
+    }
+
+    #[test]
+    fn test_unrecognized_audio() {
+        assert_eq!(audio_duration_seconds(b"not audio at all"), None);
+        assert_eq!(audio_duration_seconds(&[]), None);
+    }
+
+    #[test]
+    fn test_detect_file_type() {
+        assert_eq!(detect_file_type(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), Ok(Some(("image/png", "image"))));
+        assert_eq!(detect_file_type(b"%PDF-1.7\n..."), Ok(Some(("application/pdf", "document"))));
+        assert_eq!(detect_file_type(&wav(16000, 100)), Ok(Some(("audio/x-wav", "audio"))));
+        assert_eq!(detect_file_type(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0"), Err("application/x-executable"));
+        assert_eq!(detect_file_type(b"Just some plain text notes"), Ok(None));
+    }
+}
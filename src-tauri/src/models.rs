@@ -1,3 +1,4 @@
+use rusqlite::Row;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -12,6 +13,16 @@ pub struct TimeBlock {
     pub tags: Vec<String>,
     pub created_at: Option<String>,
     pub updated_at: Option<String>,
+    pub reminder_minutes: Option<i32>,  // Notify this many minutes before start
+    #[serde(default)]
+    pub completed: bool,
+    pub actual_duration_minutes: Option<i32>,  // Time actually spent, if it differed from the plan
+    // Notes file contents, only filled in when asked for (get_time_blocks with include_notes); never stored
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notes_content: Option<String>,
+    // Saved without a title and given the default one; the UI can style it as a free slot
+    #[serde(default)]
+    pub is_placeholder: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -23,6 +34,92 @@ pub struct Attachment {
     pub file_type: String,  // 'image', 'document', 'audio'
     pub file_size: Option<i64>,
     pub created_at: Option<String>,
+    pub content_hash: Option<String>,  // SHA-256 of the file contents
+    pub duration_seconds: Option<f64>,  // Audio only, when the header could be read
+    pub thumbnail_path: Option<String>,  // Relative path, or a "builtin:" placeholder
+    #[serde(default)]
+    pub mime_type: Option<String>,  // Detected from the file's contents; None for older uploads
+}
+
+// What an upload was stored as, after checking the content against the claimed type
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SavedAttachment {
+    pub file_path: String,
+    pub file_type: String,
+    pub mime_type: String,
+}
+
+// A named notes section of a block. "default" is the block's own notes_file.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct NoteSection {
+    pub section_name: String,
+    pub file_path: String,
+    pub updated_at: Option<String>,
+}
+
+pub const DEFAULT_NOTE_SECTION: &str = "default";
+
+// One page of a block's attachments, newest first, with the block's total count
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttachmentPage {
+    pub attachments: Vec<Attachment>,
+    pub total: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct ThumbnailBackfill {
+    pub generated: usize,
+    pub unsupported: usize,     // Still without a thumbnail, e.g. non-PDF documents
+    pub missing: Vec<String>,   // Attachment files no longer on disk, candidates for cleanup
+}
+
+// A database row whose file is no longer on disk
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct MissingFile {
+    pub id: i64,             // Time block or attachment id
+    pub time_block_id: i64,
+    pub path: String,
+}
+
+// Result of verify_integrity. With repair, the missing references listed have been removed.
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct IntegrityReport {
+    pub notes_checked: usize,
+    pub attachments_checked: usize,
+    pub missing_notes: Vec<MissingFile>,
+    pub missing_attachments: Vec<MissingFile>,
+    pub dangling_attachments: Vec<i64>,  // Attachment rows whose time block no longer exists
+    pub repaired: bool,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct BackfillProgress {
+    pub processed: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Thumbnail {
+    pub mime_type: String,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AttachmentMetadata {
+    pub id: i64,
+    pub file_name: String,
+    pub file_type: String,
+    pub file_size: Option<i64>,
+    pub duration_seconds: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StorageStats {
+    pub attachment_count: i64,
+    pub unique_files: i64,
+    pub total_bytes: i64,   // Sum of all attachment sizes
+    pub stored_bytes: i64,  // Bytes actually on disk after deduplication
+    pub bytes_saved: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -55,6 +152,161 @@ pub struct SearchResult {
     pub tags: Vec<String>,
     pub score: f32,
     pub highlights: Vec<String>,
+    #[serde(default)]
+    pub section: Option<String>,  // Set when the best match was in a named notes section
+    #[serde(default)]
+    pub color: String,  // The block's current color, as on the calendar
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct PaletteColor {
+    pub name: String,
+    pub hex: String,  // Normalized #rrggbb
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct TimeRange {
+    pub start_minutes: i32,  // Inclusive, minutes from midnight
+    pub end_minutes: i32,    // Exclusive, minutes from midnight
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct BlockConflict {
+    pub block_id: Option<i64>,
+    pub block_title: String,
+    pub event_id: Option<i64>,
+    pub event_title: String,
+    pub overlap: TimeRange,  // The overlapping part of the block and event
+}
+
+// A calendar event positioned on one day's grid, in local minutes from midnight
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct EventBlock {
+    pub event_id: Option<i64>,
+    pub start_minutes: i32,
+    pub end_minutes: i32,
+    pub title: String,
+    pub is_all_day: bool,  // Rendered in a separate lane; spans the whole day
+    pub location: Option<String>,
+    pub color: String,  // Hex, from the event's Google color
+}
+
+// A block a week template creates on each matching weekday
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WeekTemplateBlock {
+    pub weekday: String,  // Lowercase name, e.g. "monday"
+    pub start_minutes: i32,
+    pub duration_minutes: i32,
+    pub title: String,
+    #[serde(default)]
+    pub color: String,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub reminder_minutes: Option<i32>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct WeekTemplate {
+    pub id: Option<i64>,
+    pub name: String,
+    pub blocks: Vec<WeekTemplateBlock>,
+}
+
+// Outcome of apply_week_template
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct AppliedTemplate {
+    pub ids: Vec<i64>,  // Blocks created, by date then start time
+    pub skipped: usize,  // Already on the calendar with the same start time and title
+}
+
+// Outcome of save_time_blocks; ids are in the order the blocks were given
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct BatchSaveResult {
+    pub ids: Vec<i64>,
+    pub inserted: usize,
+    pub updated: usize,
+}
+
+// Runtime probe of the backend's services, for the frontend's diagnostics panel
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HealthStatus {
+    pub db_ok: bool,
+    pub search_ok: bool,
+    pub crypto_ok: bool,  // Calendar tokens are encrypted at rest
+    pub data_dir: String,
+    pub problems: Vec<String>,
+}
+
+// Outcome of copy_day. Nothing is copied (skipped) if the target day already
+// has blocks and overwrite wasn't asked for.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Default)]
+pub struct CopyDayResult {
+    pub blocks: usize,
+    pub notes: usize,
+    pub priorities: usize,
+    pub replaced_blocks: usize,
+    pub skipped: bool,
+}
+
+// Planned vs actual time for one day
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct DaySummary {
+    pub date: String,
+    pub total_blocks: i64,
+    pub completed_blocks: i64,
+    pub planned_minutes: i64,
+    pub actual_minutes: i64,  // Completed blocks only; falls back to the planned duration
+    pub working_minutes: i64,  // Length of the day's effective working hours
+}
+
+// Planned vs actual minutes over a set of completed blocks, one bar of the variance chart
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct VarianceBar {
+    pub label: String,             // The tag, or "overall"
+    pub block_count: i64,
+    pub planned_minutes: i64,
+    pub actual_minutes: i64,
+    pub delta_minutes: i64,        // Actual minus planned; positive means it ran over
+    pub delta_percent: Option<f64>,  // Delta relative to planned; None if nothing was planned
+}
+
+// Planning accuracy over a date range. Only completed blocks with an actual
+// duration are measured; a block with several tags counts toward each of them.
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct VarianceReport {
+    pub overall: VarianceBar,
+    pub by_tag: Vec<VarianceBar>,  // Largest planned time first
+    pub unmeasured: i64,           // Completed blocks without an actual duration
+}
+
+// A day with anything planned on it, for shading the month view
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct PopulatedDate {
+    pub date: String,
+    pub block_count: i64,
+    pub scheduled_minutes: i64,
+    pub has_priorities: bool,
+    pub has_brain_dump: bool,
+}
+
+// Storage and index numbers for the diagnostics screen
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+pub struct Stats {
+    pub time_blocks: i64,
+    pub priorities: i64,
+    pub brain_dumps: i64,
+    pub attachments: i64,
+    pub calendar_events: i64,
+    pub notes_bytes: u64,
+    pub search_documents: u64,  // Should match time_blocks with notes; a gap means index drift
+    pub search_index_bytes: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct TagCount {
+    pub tag: String,
+    pub count: i64,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -63,6 +315,65 @@ pub struct TimeInterval {
     pub label: String,  // "5 min", "15 min", "30 min", "1 hour"
 }
 
+// How one connection fared in a sync, for the sync report
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct ConnectionSyncResult {
+    pub account_name: String,
+    pub provider: String,
+    pub synced: i32,  // Events saved
+    pub deleted: usize,  // Cancelled upstream or past the retention window
+    pub error: Option<String>,
+    pub duration_ms: u64,
+}
+
+// Credentials to try before a connection is saved; which fields apply depends on the provider
+#[derive(Debug, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct ConnectionParams {
+    pub access_token: Option<String>,  // Google
+    pub url: Option<String>,  // CalDAV collection or principal URL
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SecurityStatus {
+    pub encryption_active: bool,
+    pub key_file_path: String,
+    pub key_storage: String,  // "file" or "keychain"
+    pub plaintext_connections: i64,  // Connections with at least one token stored unencrypted
+    pub has_plaintext_tokens: bool,
+}
+
+// Whether a connection's stored token still works, stored in calendar_connections.status
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConnectionStatus {
+    #[default]
+    Valid,
+    NeedsRefresh,  // The access token expired; a refresh token is available
+    Revoked,       // The user has to reconnect
+}
+
+impl ConnectionStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConnectionStatus::Valid => "valid",
+            ConnectionStatus::NeedsRefresh => "needs_refresh",
+            ConnectionStatus::Revoked => "revoked",
+        }
+    }
+
+    // Unknown values read as valid so a bad row never blocks syncing outright
+    pub fn parse(value: &str) -> Self {
+        match value {
+            "needs_refresh" => ConnectionStatus::NeedsRefresh,
+            "revoked" => ConnectionStatus::Revoked,
+            _ => ConnectionStatus::Valid,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct CalendarConnection {
     pub id: Option<i64>,
@@ -74,6 +385,14 @@ pub struct CalendarConnection {
     pub last_sync: Option<String>,     // Last successful sync timestamp
     pub enabled: bool,
     pub created_at: Option<String>,
+    #[serde(default)]
+    pub status: ConnectionStatus,
+    #[serde(default)]
+    pub last_error: Option<String>,    // Why the last validation or sync failed
+    #[serde(default)]
+    pub next_sync: Option<String>,     // last_sync plus calendar_sync_interval, read-only
+    #[serde(default)]
+    pub event_count: i64,              // Events currently stored, read-only
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -88,22 +407,144 @@ pub struct CalendarEvent {
     pub description: Option<String>,
     pub location: Option<String>,
     pub is_all_day: bool,
-    pub attendees: Vec<String>,  // JSON array of attendee emails
+    pub attendees: Vec<Attendee>,  // JSON array of attendees
     pub last_updated: String,    // When this event was last updated
+    #[serde(default)]
+    pub color_id: Option<String>,  // Google's event colorId ("1"-"11"); None falls back to the default gray
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct Attendee {
+    pub email: String,
+    pub name: Option<String>,
+    pub response_status: Option<String>,  // 'accepted', 'declined', 'tentative', 'needsAction'
+}
+
+// Rows synced before attendee details were captured store plain email strings
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredAttendee {
+    Detailed(Attendee),
+    Email(String),
+}
+
+// Parse the attendees JSON column, tolerating the old array-of-emails format
+pub fn parse_attendees(json: &str) -> Vec<Attendee> {
+    if json.is_empty() {
+        return Vec::new();
+    }
+    
+    let stored: Vec<StoredAttendee> = serde_json::from_str(json).unwrap_or_default();
+    stored.into_iter().map(|attendee| match attendee {
+        StoredAttendee::Detailed(attendee) => attendee,
+        StoredAttendee::Email(email) => Attendee { email, name: None, response_status: None },
+    }).collect()
 }
 
+pub const CALENDAR_CONNECTION_COLUMNS: &str =
+    "id, provider, account_name, access_token, refresh_token, calendar_list, last_sync, enabled, created_at, status, last_error,
+     (SELECT COUNT(*) FROM calendar_events WHERE calendar_events.connection_id = calendar_connections.id)";
+
+pub const CALENDAR_EVENT_COLUMNS: &str =
+    "id, connection_id, external_id, calendar_id, title, start_time, end_time, description, location, is_all_day, attendees, last_updated, color_id";
+
+// Parse a JSON array column, treating NULL, empty or malformed values as empty
+fn json_list(row: &Row, index: usize) -> rusqlite::Result<Vec<String>> {
+    let json: Option<String> = row.get(index)?;
+    Ok(json.filter(|json| !json.is_empty())
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+fn timestamp(row: &Row, index: usize) -> rusqlite::Result<Option<String>> {
+    Ok(row.get::<_, Option<String>>(index)?.map(|ts| to_rfc3339(&ts)))
+}
+
+impl CalendarConnection {
+    // Map a row selected with CALENDAR_CONNECTION_COLUMNS. Tokens are returned as
+    // stored (possibly encrypted) and next_sync is left for the caller to fill in.
+    pub fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(CalendarConnection {
+            id: Some(row.get(0)?),
+            provider: row.get(1)?,
+            account_name: row.get(2)?,
+            access_token: row.get(3)?,
+            refresh_token: row.get(4)?,
+            calendar_list: json_list(row, 5)?,
+            last_sync: timestamp(row, 6)?,
+            enabled: row.get(7)?,
+            created_at: timestamp(row, 8)?,
+            status: ConnectionStatus::parse(&row.get::<_, Option<String>>(9)?.unwrap_or_default()),
+            last_error: row.get(10)?,
+            next_sync: None,
+            event_count: row.get(11)?,
+        })
+    }
+}
+
+impl CalendarEvent {
+    // Map a row selected with CALENDAR_EVENT_COLUMNS
+    pub fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(CalendarEvent {
+            id: Some(row.get(0)?),
+            connection_id: row.get(1)?,
+            external_id: row.get(2)?,
+            calendar_id: row.get(3)?,
+            title: row.get(4)?,
+            start_time: row.get(5)?,
+            end_time: row.get(6)?,
+            description: row.get(7)?,
+            location: row.get(8)?,
+            is_all_day: row.get::<_, Option<bool>>(9)?.unwrap_or(false),
+            attendees: parse_attendees(&row.get::<_, Option<String>>(10)?.unwrap_or_default()),
+            last_updated: row.get(11)?,
+            color_id: row.get(12)?,
+        })
+    }
+}
+
+pub const TIME_BLOCK_COLUMNS: &str =
+    "id, date, start_minutes, duration_minutes, title, notes_file, color, tags, created_at, updated_at, reminder_minutes, completed, actual_duration_minutes, is_placeholder";
+
 impl TimeBlock {
+    // Map a row selected with TIME_BLOCK_COLUMNS. notes_content is left for callers that load notes.
+    pub fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(TimeBlock {
+            id: Some(row.get(0)?),
+            date: row.get(1)?,
+            start_minutes: row.get(2)?,
+            duration_minutes: row.get(3)?,
+            title: row.get(4)?,
+            notes_file: row.get(5)?,
+            color: row.get::<_, Option<String>>(6)?
+                .filter(|color| !color.is_empty())
+                .unwrap_or_else(|| DEFAULT_BLOCK_COLOR.to_string()),
+            tags: json_list(row, 7)?,
+            created_at: timestamp(row, 8)?,
+            updated_at: timestamp(row, 9)?,
+            reminder_minutes: row.get(10)?,
+            completed: row.get::<_, Option<bool>>(11)?.unwrap_or(false),
+            actual_duration_minutes: row.get(12)?,
+            notes_content: None,
+            // NULL when read from an archive that predates the column
+            is_placeholder: row.get::<_, Option<bool>>(13)?.unwrap_or(false),
+        })
+    }
+
     pub fn start_time_formatted(&self) -> String {
-        let hours = self.start_minutes / 60;
-        let minutes = self.start_minutes % 60;
-        format!("{:02}:{:02}", hours, minutes)
+        self.start_time_formatted_with(false)
     }
     
     pub fn end_time_formatted(&self) -> String {
-        let end_minutes = self.start_minutes + self.duration_minutes;
-        let hours = end_minutes / 60;
-        let minutes = end_minutes % 60;
-        format!("{:02}:{:02}", hours, minutes)
+        self.end_time_formatted_with(false)
+    }
+    
+    pub fn start_time_formatted_with(&self, twelve_hour: bool) -> String {
+        format_minutes(self.start_minutes, twelve_hour)
+    }
+    
+    pub fn end_time_formatted_with(&self, twelve_hour: bool) -> String {
+        format_minutes(self.start_minutes + self.duration_minutes, twelve_hour)
     }
     
     pub fn duration_formatted(&self) -> String {
@@ -121,6 +562,75 @@ impl TimeBlock {
     }
 }
 
+pub const DEFAULT_BLOCK_COLOR: &str = "#3b82f6";
+
+// Events without a colorId, unless the event_colors setting maps "default"
+pub const DEFAULT_EVENT_COLOR: &str = "#9ca3af";
+
+// Google Calendar's standard event colors by colorId (Lavender through Tomato)
+const GOOGLE_EVENT_COLORS: [(&str, &str); 11] = [
+    ("1", "#a4bdfc"),
+    ("2", "#7ae7bf"),
+    ("3", "#dbadff"),
+    ("4", "#ff887c"),
+    ("5", "#fbd75b"),
+    ("6", "#ffb878"),
+    ("7", "#46d6db"),
+    ("8", "#e1e1e1"),
+    ("9", "#5484ed"),
+    ("10", "#51b749"),
+    ("11", "#dc2127"),
+];
+
+// Hex color for an event's colorId. `overrides` (the event_colors setting) wins over
+// Google's table; its "default" entry colors events without a colorId.
+pub fn event_color(color_id: Option<&str>, overrides: &std::collections::HashMap<String, String>) -> String {
+    let key = color_id.unwrap_or("default");
+    if let Some(hex) = overrides.get(key) {
+        return hex.clone();
+    }
+    color_id
+        .and_then(|id| GOOGLE_EVENT_COLORS.iter().find(|(known, _)| *known == id))
+        .map_or(DEFAULT_EVENT_COLOR, |(_, hex)| *hex)
+        .to_string()
+}
+
+pub fn default_palette() -> Vec<PaletteColor> {
+    [
+        ("Blue", "#3b82f6"),
+        ("Green", "#22c55e"),
+        ("Amber", "#f59e0b"),
+        ("Red", "#ef4444"),
+        ("Purple", "#8b5cf6"),
+        ("Gray", "#6b7280"),
+    ]
+    .iter()
+    .map(|(name, hex)| PaletteColor { name: name.to_string(), hex: hex.to_string() })
+    .collect()
+}
+
+// Parse a #rrggbb or shorthand #rgb color into lowercase #rrggbb form
+pub fn parse_hex_color(input: &str) -> Option<String> {
+    let hex = input.trim().strip_prefix('#')?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    
+    match hex.len() {
+        6 => Some(format!("#{}", hex.to_ascii_lowercase())),
+        3 => {
+            let expanded: String = hex.chars().flat_map(|c| [c, c]).collect();
+            Some(format!("#{}", expanded.to_ascii_lowercase()))
+        }
+        _ => None,
+    }
+}
+
+// Normalize a stored block color, falling back to the default on invalid input
+pub fn normalize_color(input: &str) -> String {
+    parse_hex_color(input).unwrap_or_else(|| DEFAULT_BLOCK_COLOR.to_string())
+}
+
 // Utility functions for time conversion
 pub fn time_string_to_minutes(time_str: &str) -> Result<i32, String> {
     let parts: Vec<&str> = time_str.split(':').collect();
@@ -138,8 +648,205 @@ pub fn time_string_to_minutes(time_str: &str) -> Result<i32, String> {
     Ok(hours * 60 + minutes)
 }
 
+// Parse "14:30", "2pm", "2:30pm" or "12am" into minutes from midnight.
+// A bare hour without am/pm is ambiguous and rejected.
+pub fn parse_clock_time(input: &str) -> Result<i32, String> {
+    let lower = input.trim().to_ascii_lowercase();
+    
+    if let Some(time) = lower.strip_suffix("pm") {
+        twelve_hour_to_minutes(time.trim(), true)
+    } else if let Some(time) = lower.strip_suffix("am") {
+        twelve_hour_to_minutes(time.trim(), false)
+    } else if lower.contains(':') {
+        time_string_to_minutes(&lower)
+    } else {
+        Err(format!("Ambiguous time '{}': add am/pm or use HH:MM", input.trim()))
+    }
+}
+
+pub fn twelve_hour_to_minutes(time: &str, pm: bool) -> Result<i32, String> {
+    let (hours, minutes) = match time.split_once(':') {
+        Some((hours, minutes)) if minutes.len() == 2 => (hours, minutes),
+        Some(_) => return Err("Invalid minute".to_string()),
+        None => (time, "0"),
+    };
+    
+    let hours: i32 = hours.parse().map_err(|_| "Invalid hour")?;
+    let minutes: i32 = minutes.parse().map_err(|_| "Invalid minute")?;
+    
+    if !(1..=12).contains(&hours) || !(0..=59).contains(&minutes) {
+        return Err("Time out of range".to_string());
+    }
+    
+    let hours = match (hours, pm) {
+        (12, false) => 0,
+        (12, true) => 12,
+        (hours, true) => hours + 12,
+        (hours, false) => hours,
+    };
+    
+    Ok(hours * 60 + minutes)
+}
+
 pub fn minutes_to_time_string(minutes: i32) -> String {
+    format_minutes(minutes, false)
+}
+
+// Minutes from midnight as "13:05", or "1:05 PM" on a 12-hour clock.
+// The end of the day (1440) is "24:00" / "12:00 AM".
+pub fn format_minutes(minutes: i32, twelve_hour: bool) -> String {
     let hours = minutes / 60;
     let mins = minutes % 60;
-    format!("{:02}:{:02}", hours, mins)
-}
\ No newline at end of file
+    if !twelve_hour {
+        return format!("{:02}:{:02}", hours, mins);
+    }
+
+    let period = if hours % 24 < 12 { "AM" } else { "PM" };
+    let display_hours = match hours % 12 {
+        0 => 12,
+        hours => hours,
+    };
+    format!("{}:{:02} {}", display_hours, mins, period)
+}
+
+/// Timestamps are always returned to the frontend as RFC 3339 in UTC, e.g.
+/// "2025-06-01T09:30:00Z". SQLite's CURRENT_TIMESTAMP ("2025-06-01 09:30:00",
+/// already UTC) and RFC 3339 strings with an offset are converted; anything
+/// unrecognized is passed through unchanged.
+pub fn to_rfc3339(sqlite_ts: &str) -> String {
+    use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+    
+    let trimmed = sqlite_ts.trim();
+    let utc = NaiveDateTime::parse_from_str(trimmed, "%Y-%m-%d %H:%M:%S%.f")
+        .map(|naive| naive.and_utc())
+        .or_else(|_| DateTime::parse_from_rfc3339(trimmed).map(|dt| dt.with_timezone(&Utc)));
+    
+    match utc {
+        Ok(utc) => utc.to_rfc3339_opts(SecondsFormat::AutoSi, true),
+        Err(_) => sqlite_ts.to_string(),
+    }
+}
+
+// Human-readable duration: "45 min", "1 hour", "2 hours", "1h 30m"
+pub fn duration_label(minutes: i32) -> String {
+    if minutes >= 60 {
+        let hours = minutes / 60;
+        let remaining_minutes = minutes % 60;
+        if remaining_minutes == 0 {
+            format!("{} hour{}", hours, if hours > 1 { "s" } else { "" })
+        } else {
+            format!("{}h {}m", hours, remaining_minutes)
+        }
+    } else {
+        format!("{} min", minutes)
+    }
+}
+
+impl TimeInterval {
+    pub fn new(minutes: i32) -> Self {
+        TimeInterval { minutes, label: duration_label(minutes) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_format_minutes() {
+        assert_eq!(format_minutes(0, false), "00:00");
+        assert_eq!(format_minutes(0, true), "12:00 AM");
+        assert_eq!(format_minutes(12 * 60, false), "12:00");
+        assert_eq!(format_minutes(12 * 60, true), "12:00 PM");
+        assert_eq!(format_minutes(13 * 60 + 5, false), "13:05");
+        assert_eq!(format_minutes(13 * 60 + 5, true), "1:05 PM");
+        assert_eq!(format_minutes(24 * 60, false), "24:00");
+        assert_eq!(format_minutes(24 * 60, true), "12:00 AM");
+    }
+    
+    #[test]
+    fn test_to_rfc3339() {
+        assert_eq!(to_rfc3339("2025-06-01 09:30:00"), "2025-06-01T09:30:00Z");
+        assert_eq!(to_rfc3339("2025-06-01T11:30:00+02:00"), "2025-06-01T09:30:00Z");
+        assert_eq!(to_rfc3339("2025-06-01T09:30:00Z"), "2025-06-01T09:30:00Z");
+        assert_eq!(to_rfc3339("not a timestamp"), "not a timestamp");
+    }
+    
+    #[test]
+    fn test_duration_label() {
+        assert_eq!(duration_label(45), "45 min");
+        assert_eq!(duration_label(60), "1 hour");
+        assert_eq!(duration_label(120), "2 hours");
+        assert_eq!(duration_label(90), "1h 30m");
+    }
+    
+    #[test]
+    fn test_normalize_color() {
+        assert_eq!(normalize_color("#3B82F6"), "#3b82f6");
+        assert_eq!(normalize_color("#abc"), "#aabbcc");
+        assert_eq!(normalize_color(" #FfF "), "#ffffff");
+        assert_eq!(normalize_color("red"), DEFAULT_BLOCK_COLOR);
+        assert_eq!(normalize_color("#12345"), DEFAULT_BLOCK_COLOR);
+        assert_eq!(normalize_color("#ggg"), DEFAULT_BLOCK_COLOR);
+        assert_eq!(normalize_color(""), DEFAULT_BLOCK_COLOR);
+    }
+    
+    #[test]
+    fn test_parse_attendees_formats() {
+        let detailed = parse_attendees(
+            r#"[{"email":"a@example.com","name":"Ann","response_status":"accepted"}]"#
+        );
+        assert_eq!(detailed, vec![Attendee {
+            email: "a@example.com".to_string(),
+            name: Some("Ann".to_string()),
+            response_status: Some("accepted".to_string()),
+        }]);
+        
+        let legacy = parse_attendees(r#"["b@example.com"]"#);
+        assert_eq!(legacy, vec![Attendee {
+            email: "b@example.com".to_string(),
+            name: None,
+            response_status: None,
+        }]);
+        
+        assert!(parse_attendees("").is_empty());
+        assert!(parse_attendees("not json").is_empty());
+    }
+    
+    #[test]
+    fn test_parse_clock_time() {
+        assert_eq!(parse_clock_time("14:30"), Ok(870));
+        assert_eq!(parse_clock_time("2pm"), Ok(840));
+        assert_eq!(parse_clock_time("2:30PM"), Ok(870));
+        assert_eq!(parse_clock_time("12am"), Ok(0));
+        assert_eq!(parse_clock_time("12pm"), Ok(720));
+        assert_eq!(parse_clock_time("12:15am"), Ok(15));
+        assert!(parse_clock_time("13pm").is_err());
+        assert!(parse_clock_time("2:5pm").is_err());
+        assert!(parse_clock_time("3").unwrap_err().contains("Ambiguous"));
+    }
+
+    #[test]
+    fn test_time_block_from_row_defaults() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        let block = conn.query_row(
+            "SELECT 1, '2025-06-01', 540, 30, 'Focus', NULL, '', 'not json', '2025-06-01 09:30:00', NULL, NULL, NULL, NULL, NULL",
+            [],
+            TimeBlock::from_row
+        ).unwrap();
+        assert_eq!(block.color, DEFAULT_BLOCK_COLOR);
+        assert!(block.tags.is_empty());
+        assert_eq!(block.created_at.as_deref(), Some("2025-06-01T09:30:00Z"));
+        assert!(!block.completed);
+        assert!(!block.is_placeholder);
+
+        let event = conn.query_row(
+            "SELECT 2, 1, 'ext', 'primary', 'Standup', '2025-06-01T09:00:00Z', '2025-06-01T09:15:00Z', NULL, NULL, NULL, '[\"a@example.com\"]', '2025-06-01', '5'",
+            [],
+            CalendarEvent::from_row
+        ).unwrap();
+        assert!(!event.is_all_day);
+        assert_eq!(event.attendees[0].email, "a@example.com");
+        assert_eq!(event.color_id.as_deref(), Some("5"));
+    }
+}
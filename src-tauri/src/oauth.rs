@@ -0,0 +1,216 @@
+use anyhow::{anyhow, Result};
+use base64::Engine;
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, oneshot};
+
+use crate::error::AppError;
+
+// How long the loopback server waits for the user to finish signing in
+pub const LOOPBACK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+// How long one connection gets to send its request, so idle sockets (e.g. a browser
+// preconnect) are dropped
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+const SUCCESS_PAGE: &str = "<html><body><h3>Signed in. You can close this tab and return to TimeBloc.</h3></body></html>";
+const FAILURE_PAGE: &str = "<html><body><h3>Sign-in failed. Return to TimeBloc and try again.</h3></body></html>";
+
+// The sign-in currently waiting on its loopback server. Starting another one or
+// cancelling drops the old sender, which shuts that server down.
+#[derive(Default)]
+pub struct PendingOAuth(pub Mutex<Option<oneshot::Sender<()>>>);
+
+impl PendingOAuth {
+    // Register a new sign-in, cancelling any earlier one. The receiver fires on cancellation.
+    pub fn begin(&self) -> oneshot::Receiver<()> {
+        let (sender, receiver) = oneshot::channel();
+        if let Some(previous) = self.0.lock().unwrap_or_else(|e| e.into_inner()).replace(sender) {
+            let _ = previous.send(());
+        }
+        receiver
+    }
+
+    pub fn cancel(&self) {
+        if let Some(pending) = self.0.lock().unwrap_or_else(|e| e.into_inner()).take() {
+            let _ = pending.send(());
+        }
+    }
+}
+
+// PKCE (RFC 7636) pair for one sign-in: the challenge goes in the auth URL and the
+// verifier with the code exchange, so an intercepted code is useless on its own
+pub struct Pkce {
+    pub verifier: String,
+    pub challenge: String,
+}
+
+impl Pkce {
+    pub fn new() -> Result<Self> {
+        let mut random = [0u8; 32];
+        SystemRandom::new().fill(&mut random)
+            .map_err(|_| anyhow!("Failed to generate PKCE verifier"))?;
+        let encode = |bytes: &[u8]| base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(bytes);
+        let verifier = encode(&random);
+        let challenge = encode(digest(&SHA256, verifier.as_bytes()).as_ref());
+        Ok(Self { verifier, challenge })
+    }
+}
+
+// Listen on a random local port. Returns the listener and the redirect URI to give Google.
+pub async fn bind_loopback() -> Result<(TcpListener, String)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let redirect_uri = format!("http://127.0.0.1:{}", listener.local_addr()?.port());
+    Ok((listener, redirect_uri))
+}
+
+// Serve the redirect until Google sends the authorization code, the user cancels or
+// the timeout passes. Each connection is handled on its own task, so one that never
+// sends a request can't hold up the callback. The listener is dropped on return,
+// closing the server.
+pub async fn wait_for_code(
+    listener: TcpListener,
+    expected_state: &str,
+    cancel: oneshot::Receiver<()>,
+    timeout: Duration
+) -> Result<String> {
+    let (results, mut received) = mpsc::channel(1);
+    let serve = async {
+        loop {
+            tokio::select! {
+                accepted = listener.accept() => {
+                    let (stream, _) = accepted?;
+                    let (results, expected_state) = (results.clone(), expected_state.to_string());
+                    tokio::spawn(async move {
+                        if let Some(result) = handle_request(stream, &expected_state).await {
+                            let _ = results.send(result).await;
+                        }
+                    });
+                }
+                Some(result) = received.recv() => return result,
+            }
+        }
+    };
+
+    tokio::select! {
+        result = serve => result,
+        _ = cancel => Err(AppError::Auth("Sign-in was cancelled".to_string()).into()),
+        _ = tokio::time::sleep(timeout) => Err(AppError::Auth("Timed out waiting for sign-in".to_string()).into()),
+    }
+}
+
+// Answer one connection. Returns the callback's outcome, or None for other requests
+// (browsers also ask for things like /favicon.ico) and connections that time out.
+async fn handle_request(mut stream: TcpStream, expected_state: &str) -> Option<Result<String>> {
+    let mut buffer = vec![0u8; 8192];
+    let read = match tokio::time::timeout(REQUEST_TIMEOUT, stream.read(&mut buffer)).await {
+        Ok(Ok(read)) => read,
+        _ => return None,
+    };
+    let request = String::from_utf8_lossy(&buffer[..read]);
+
+    match parse_callback(&request, expected_state) {
+        Some(result) => {
+            let page = if result.is_ok() { SUCCESS_PAGE } else { FAILURE_PAGE };
+            respond(&mut stream, "200 OK", page).await;
+            Some(result)
+        }
+        None => {
+            respond(&mut stream, "404 Not Found", "").await;
+            None
+        }
+    }
+}
+
+async fn respond(stream: &mut TcpStream, status: &str, body: &str) {
+    let response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        body.len(),
+        body
+    );
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.shutdown().await;
+}
+
+// The outcome of a redirect request ("GET /?code=...&state=... HTTP/1.1"), or None
+// if the request isn't the OAuth callback
+fn parse_callback(request: &str, expected_state: &str) -> Option<Result<String>> {
+    let target = request.lines().next()?.strip_prefix("GET ")?.split_whitespace().next()?;
+    let url = url::Url::parse(&format!("http://127.0.0.1{}", target)).ok()?;
+    if url.path() != "/" {
+        return None;
+    }
+
+    let param = |name: &str| url.query_pairs().find(|(key, _)| key == name).map(|(_, value)| value.into_owned());
+    if let Some(error) = param("error") {
+        return Some(Err(AppError::Auth(format!("Sign-in was denied: {}", error)).into()));
+    }
+    let code = param("code")?;
+    if param("state").as_deref() != Some(expected_state) {
+        return Some(Err(AppError::Auth("Sign-in response didn't match this request".to_string()).into()));
+    }
+    Some(Ok(code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Read, Write};
+
+    #[test]
+    fn test_parse_callback() {
+        let request = "GET /?state=abc&code=4%2F0Ad&scope=calendar HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n";
+        assert_eq!(parse_callback(request, "abc").unwrap().unwrap(), "4/0Ad");
+
+        assert!(parse_callback("GET /favicon.ico HTTP/1.1\r\n\r\n", "abc").is_none());
+        assert!(parse_callback("GET /?state=other&code=x HTTP/1.1\r\n\r\n", "abc").unwrap().is_err());
+
+        let denied = parse_callback("GET /?error=access_denied&state=abc HTTP/1.1\r\n\r\n", "abc").unwrap();
+        assert_eq!(AppError::from(denied.unwrap_err()).code(), "auth");
+    }
+
+    #[test]
+    fn test_pkce_challenge_is_s256_of_verifier() {
+        let pkce = Pkce::new().unwrap();
+        assert_eq!(pkce.verifier.len(), 43);
+        let expected = base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(digest(&SHA256, pkce.verifier.as_bytes()).as_ref());
+        assert_eq!(pkce.challenge, expected);
+        assert_ne!(Pkce::new().unwrap().verifier, pkce.verifier);
+    }
+
+    #[test]
+    fn test_loopback_receives_code_and_handles_cancel() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        runtime.block_on(async {
+            let (listener, redirect_uri) = bind_loopback().await.unwrap();
+            let address = redirect_uri.trim_start_matches("http://").to_string();
+            // A preconnected socket that never sends anything doesn't block the callback
+            let idle = std::net::TcpStream::connect(&address).unwrap();
+            let browser = std::thread::spawn(move || {
+                let mut stream = std::net::TcpStream::connect(address).unwrap();
+                stream.write_all(b"GET /?code=abc123&state=s1 HTTP/1.1\r\nHost: 127.0.0.1\r\n\r\n").unwrap();
+                let mut response = String::new();
+                stream.read_to_string(&mut response).unwrap();
+                response
+            });
+
+            let pending = PendingOAuth::default();
+            let code = wait_for_code(listener, "s1", pending.begin(), Duration::from_secs(5)).await.unwrap();
+            assert_eq!(code, "abc123");
+            assert!(browser.join().unwrap().starts_with("HTTP/1.1 200 OK"));
+            drop(idle);
+
+            // A newer sign-in cancels the one still waiting
+            let (listener, _) = bind_loopback().await.unwrap();
+            let first = pending.begin();
+            let _second = pending.begin();
+            let error = wait_for_code(listener, "s2", first, Duration::from_secs(5)).await.unwrap_err();
+            assert_eq!(AppError::from(error).message(), "Sign-in was cancelled");
+        });
+    }
+}
@@ -0,0 +1,25 @@
+use anyhow::Result;
+
+// Text recognition for image attachments. Needs the `ocr` cargo feature, which
+// links the system Tesseract and Leptonica libraries; without it OCR is reported
+// as unavailable and images are simply not indexed.
+
+pub fn is_available() -> bool {
+    cfg!(feature = "ocr")
+}
+
+/// Recognized text in an encoded image (PNG, JPEG, ...), or None if there is none.
+#[cfg(feature = "ocr")]
+pub fn extract_text(image_data: &[u8]) -> Result<Option<String>> {
+    let mut tesseract = tesseract::Tesseract::new(None, Some("eng"))?
+        .set_image_from_mem(image_data)?;
+    let text = tesseract.get_text()?;
+
+    let text = text.trim();
+    Ok(if text.is_empty() { None } else { Some(text.to_string()) })
+}
+
+#[cfg(not(feature = "ocr"))]
+pub fn extract_text(_image_data: &[u8]) -> Result<Option<String>> {
+    Err(anyhow::anyhow!("OCR is not available in this build"))
+}
@@ -0,0 +1,238 @@
+use crate::models::{parse_clock_time, twelve_hour_to_minutes, TimeBlock, DEFAULT_BLOCK_COLOR};
+
+const MINUTES_PER_DAY: i32 = 24 * 60;
+
+// Parse quick-add text like "Lunch 12:30-1pm #break" into an unsaved draft block.
+//
+// Supported time forms: "12:30-13:00", "12:30-1pm", "2pm for 45m", "14:00 30m".
+// Input that can't be read unambiguously is rejected with an explanation.
+pub fn parse_quick_add(text: &str, date: &str) -> Result<TimeBlock, String> {
+    let normalized = text.replace(" - ", "-");
+    let tokens: Vec<&str> = normalized.split_whitespace().collect();
+
+    let mut title_words = Vec::new();
+    let mut tags = Vec::new();
+    let mut range: Option<(i32, i32)> = None;
+    let mut start: Option<i32> = None;
+    let mut duration: Option<i32> = None;
+
+    for (i, token) in tokens.iter().enumerate() {
+        if let Some(tag) = token.strip_prefix('#').filter(|tag| !tag.is_empty()) {
+            tags.push(tag.to_string());
+        } else if let Some((from, to)) = token.split_once('-').filter(|(from, to)| looks_like_time(from) && looks_like_time(to)) {
+            if range.is_some() || start.is_some() {
+                return Err(format!("Found more than one time in '{}'", text.trim()));
+            }
+            range = Some(parse_range(from, to)?);
+        } else if looks_like_time(token) && (has_meridiem(token).is_some() || token.contains(':')) {
+            if range.is_some() || start.is_some() {
+                return Err(format!("Found more than one time in '{}'", text.trim()));
+            }
+            start = Some(parse_clock_time(token)?);
+        } else if let Some(minutes) = parse_duration(token) {
+            if duration.is_some() {
+                return Err(format!("Found more than one duration in '{}'", text.trim()));
+            }
+            duration = Some(minutes);
+        } else if token.eq_ignore_ascii_case("for") && tokens.get(i + 1).and_then(|t| parse_duration(t)).is_some() {
+            // "2pm for 45m"
+        } else {
+            title_words.push(*token);
+        }
+    }
+
+    let (start_minutes, end_minutes) = match (range, start, duration) {
+        (Some(_), _, Some(_)) => return Err("Give either an end time or a duration, not both".to_string()),
+        (Some(range), _, None) => range,
+        (None, Some(start), Some(duration)) => (start, start + duration),
+        (None, Some(_), None) => return Err("Add an end time or duration, e.g. '2pm for 45m'".to_string()),
+        (None, None, Some(_)) => return Err("Add a start time, e.g. '14:00 30m'".to_string()),
+        (None, None, None) => return Err("No time found, e.g. 'Lunch 12:30-13:00'".to_string()),
+    };
+
+    if end_minutes <= start_minutes {
+        return Err("End time must be after the start time".to_string());
+    }
+    if end_minutes > MINUTES_PER_DAY {
+        return Err("Block can't run past midnight".to_string());
+    }
+
+    let title = title_words.join(" ");
+    if title.is_empty() {
+        return Err("Missing a title".to_string());
+    }
+
+    Ok(TimeBlock {
+        id: None,
+        date: date.to_string(),
+        start_minutes,
+        duration_minutes: end_minutes - start_minutes,
+        title,
+        notes_file: None,
+        color: DEFAULT_BLOCK_COLOR.to_string(),
+        tags,
+        created_at: None,
+        updated_at: None,
+        reminder_minutes: None,
+        completed: false,
+        actual_duration_minutes: None,
+        notes_content: None,
+        is_placeholder: false,
+    })
+}
+
+// Digits with an optional ":MM" and am/pm suffix
+fn looks_like_time(token: &str) -> bool {
+    let lower = token.to_ascii_lowercase();
+    let digits = lower.trim_end_matches("am").trim_end_matches("pm");
+    lower.starts_with(|c: char| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == ':')
+}
+
+// Some(true) for pm, Some(false) for am
+fn has_meridiem(token: &str) -> Option<bool> {
+    let lower = token.to_ascii_lowercase();
+    if lower.ends_with("pm") {
+        Some(true)
+    } else if lower.ends_with("am") {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+// Parse "from-to", borrowing am/pm from the other side when only one side has it
+fn parse_range(from: &str, to: &str) -> Result<(i32, i32), String> {
+    let candidates = |token: &str| -> Option<(i32, i32)> {
+        Some((twelve_hour_to_minutes(token, false).ok()?, twelve_hour_to_minutes(token, true).ok()?))
+    };
+
+    match (has_meridiem(from), has_meridiem(to)) {
+        (None, Some(_)) if candidates(from).is_some() => {
+            // "11-1pm": the latest reading of the start that's still before the end
+            let end = parse_clock_time(to)?;
+            let (am, pm) = candidates(from).unwrap();
+            let start = if pm < end { pm } else { am };
+            Ok((start, end))
+        }
+        (Some(_), None) if candidates(to).is_some() => {
+            // "9am-10": the earliest reading of the end that's after the start
+            let start = parse_clock_time(from)?;
+            let (am, pm) = candidates(to).unwrap();
+            let end = if am > start { am } else { pm };
+            Ok((start, end))
+        }
+        _ => Ok((parse_clock_time(from)?, parse_clock_time(to)?)),
+    }
+}
+
+// "45m", "45min", "1h", "2hrs", "1h30m" or "1h30"
+fn parse_duration(token: &str) -> Option<i32> {
+    let lower = token.to_ascii_lowercase();
+
+    let (hours, rest) = match lower.find('h') {
+        Some(idx) => {
+            let hours = lower[..idx].parse::<i32>().ok()?;
+            let after = &lower[idx..];
+            let unit_len = after.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(after.len());
+            if !["h", "hr", "hrs", "hour", "hours"].contains(&&after[..unit_len]) {
+                return None;
+            }
+            (hours, &after[unit_len..])
+        }
+        None => (0, lower.as_str()),
+    };
+
+    let minutes = if rest.is_empty() {
+        0
+    } else {
+        let digits = rest.strip_suffix("mins")
+            .or_else(|| rest.strip_suffix("min"))
+            .or_else(|| rest.strip_suffix('m'));
+        match digits {
+            Some(digits) => digits.parse::<i32>().ok()?,
+            // Trailing minutes without a unit are only allowed after hours ("1h30")
+            None if hours > 0 => rest.parse::<i32>().ok()?,
+            None => return None,
+        }
+    };
+
+    let total = hours * 60 + minutes;
+    if total > 0 { Some(total) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> TimeBlock {
+        parse_quick_add(text, "2025-06-01").unwrap()
+    }
+
+    #[test]
+    fn test_range_with_tags() {
+        let block = parse("Lunch 12:30-13:00 #break");
+        assert_eq!(block.title, "Lunch");
+        assert_eq!(block.start_minutes, 750);
+        assert_eq!(block.duration_minutes, 30);
+        assert_eq!(block.tags, vec!["break"]);
+        assert_eq!(block.date, "2025-06-01");
+        assert_eq!(block.id, None);
+    }
+
+    #[test]
+    fn test_mixed_twelve_hour_range() {
+        let block = parse("Call 12:30-1pm");
+        assert_eq!((block.start_minutes, block.duration_minutes), (750, 30));
+
+        let block = parse("Deep work 11-1pm");
+        assert_eq!((block.start_minutes, block.duration_minutes), (660, 120));
+
+        let block = parse("Standup 9am-10");
+        assert_eq!((block.start_minutes, block.duration_minutes), (540, 60));
+
+        let block = parse("Sync 2:00 - 3pm");
+        assert_eq!((block.start_minutes, block.duration_minutes), (840, 60));
+    }
+
+    #[test]
+    fn test_start_with_duration() {
+        let block = parse("Gym 2pm for 45m");
+        assert_eq!(block.title, "Gym");
+        assert_eq!((block.start_minutes, block.duration_minutes), (840, 45));
+
+        let block = parse("Write 14:00 30m #deep #work");
+        assert_eq!(block.title, "Write");
+        assert_eq!((block.start_minutes, block.duration_minutes), (840, 30));
+        assert_eq!(block.tags, vec!["deep", "work"]);
+
+        let block = parse("Focus 1h30m 9am");
+        assert_eq!((block.start_minutes, block.duration_minutes), (540, 90));
+    }
+
+    #[test]
+    fn test_title_keeps_plain_words_and_numbers() {
+        let block = parse("Read 3 chapters for follow-up 10am 1h");
+        assert_eq!(block.title, "Read 3 chapters for follow-up");
+        assert_eq!(block.duration_minutes, 60);
+
+        let block = parse("Study 5th edition 9am 2hrs");
+        assert_eq!(block.title, "Study 5th edition");
+        assert_eq!(block.duration_minutes, 120);
+    }
+
+    #[test]
+    fn test_ambiguous_input_is_rejected() {
+        let err = |text: &str| parse_quick_add(text, "2025-06-01").unwrap_err();
+
+        assert!(err("Meet 9-10").contains("Ambiguous"));
+        assert!(err("Focus 2pm").contains("end time or duration"));
+        assert!(err("Focus 45m").contains("start time"));
+        assert!(err("Nap").contains("No time"));
+        assert!(err("Review 14:00-13:00").contains("after the start"));
+        assert!(err("12:00-13:00 #lunch").contains("title"));
+        assert!(err("Twice 9am-10am 11am-12pm").contains("more than one time"));
+        assert!(err("Both 9am-10am 30m").contains("not both"));
+        assert!(err("Late 11pm for 2h").contains("midnight"));
+    }
+}
@@ -0,0 +1,180 @@
+use chrono::{Local, Timelike};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+use tauri::api::notification::Notification;
+use tauri::AppHandle;
+use log::error;
+
+use crate::commands::{load_time_blocks, uses_twelve_hour_clock};
+use crate::DbPool;
+use crate::models::{format_minutes, TimeBlock};
+
+const MINUTES_PER_DAY: i32 = 24 * 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reminder {
+    pub fire_minutes: i32,  // Minutes from midnight when the notification is due
+    pub title: String,
+    pub start_minutes: i32,
+}
+
+// Reminders for the given blocks that fall due strictly after `after_minutes`
+pub fn pending_reminders(blocks: &[TimeBlock], after_minutes: i32) -> Vec<Reminder> {
+    let mut reminders: Vec<Reminder> = blocks
+        .iter()
+        .filter_map(|block| {
+            let lead = block.reminder_minutes?;
+            let fire_minutes = block.start_minutes - lead.max(0);
+            if fire_minutes <= after_minutes || fire_minutes < 0 {
+                return None;
+            }
+            Some(Reminder {
+                fire_minutes,
+                title: block.title.clone(),
+                start_minutes: block.start_minutes,
+            })
+        })
+        .collect();
+
+    reminders.sort_by_key(|reminder| reminder.fire_minutes);
+    reminders
+}
+
+/// Background thread that shows a desktop notification before each of today's
+/// blocks with a `reminder_minutes` set.
+pub struct ReminderScheduler {
+    reload_requested: Mutex<bool>,
+    wakeup: Condvar,
+}
+
+impl ReminderScheduler {
+    pub fn start(app: AppHandle, db: DbPool) -> Arc<Self> {
+        let scheduler = Arc::new(ReminderScheduler {
+            reload_requested: Mutex::new(false),
+            wakeup: Condvar::new(),
+        });
+
+        let worker = scheduler.clone();
+        thread::spawn(move || worker.run(app, db));
+
+        scheduler
+    }
+
+    /// Re-read today's blocks, e.g. after a block was saved or deleted.
+    pub fn reload(&self) {
+        let mut requested = self.reload_requested.lock().unwrap();
+        *requested = true;
+        self.wakeup.notify_one();
+    }
+
+    fn run(&self, app: AppHandle, db: DbPool) {
+        let mut current_date = String::new();
+        // Everything up to this minute has already fired (or was in the past on startup)
+        let mut fired_through = 0;
+
+        loop {
+            let now = Local::now();
+            let today = now.format("%Y-%m-%d").to_string();
+            let now_minutes = (now.hour() * 60 + now.minute()) as i32;
+
+            if today != current_date {
+                // Don't fire reminders that were already due before startup or midnight
+                fired_through = if current_date.is_empty() { now_minutes - 1 } else { -1 };
+                current_date = today.clone();
+            }
+
+            let (blocks, twelve_hour) = {
+                match db.get() {
+                    Ok(conn) => (
+                        load_time_blocks(&conn, &today).unwrap_or_default(),
+                        uses_twelve_hour_clock(&conn).unwrap_or(false),
+                    ),
+                    Err(_) => (Vec::new(), false),
+                }
+            };
+
+            let reminders = pending_reminders(&blocks, fired_through);
+            for reminder in reminders.iter().filter(|r| r.fire_minutes <= now_minutes) {
+                self.notify(&app, reminder, twelve_hour);
+            }
+            fired_through = fired_through.max(now_minutes);
+
+            // Sleep until the next reminder is due, midnight, or a reload request
+            let next_minutes = reminders
+                .iter()
+                .map(|r| r.fire_minutes)
+                .find(|&minutes| minutes > now_minutes)
+                .unwrap_or(MINUTES_PER_DAY);
+            let seconds_now = now.num_seconds_from_midnight() as i64;
+            let wait_seconds = (next_minutes as i64 * 60 - seconds_now).max(1);
+
+            let requested = self.reload_requested.lock().unwrap();
+            let (mut requested, _) = self.wakeup
+                .wait_timeout_while(requested, Duration::from_secs(wait_seconds as u64), |r| !*r)
+                .unwrap();
+            *requested = false;
+        }
+    }
+
+    fn notify(&self, app: &AppHandle, reminder: &Reminder, twelve_hour: bool) {
+        let body = format!(
+            "{} starts at {}",
+            reminder.title,
+            format_minutes(reminder.start_minutes, twelve_hour)
+        );
+
+        let result = Notification::new(&app.config().tauri.bundle.identifier)
+            .title("Upcoming time block")
+            .body(body)
+            .show();
+
+        if let Err(e) = result {
+            error!("Failed to show reminder notification: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(title: &str, start_minutes: i32, reminder_minutes: Option<i32>) -> TimeBlock {
+        TimeBlock {
+            id: None,
+            date: "2025-06-01".to_string(),
+            start_minutes,
+            duration_minutes: 30,
+            title: title.to_string(),
+            notes_file: None,
+            color: "#3b82f6".to_string(),
+            tags: vec![],
+            created_at: None,
+            updated_at: None,
+            reminder_minutes,
+            completed: false,
+            actual_duration_minutes: None,
+            notes_content: None,
+            is_placeholder: false,
+        }
+    }
+
+    #[test]
+    fn test_pending_reminders_skips_past_and_unset() {
+        let blocks = vec![
+            block("Standup", 540, Some(10)),  // fires at 530
+            block("Lunch", 720, None),
+            block("Review", 600, Some(5)),    // fires at 595
+            block("Early", 5, Some(15)),      // would fire the previous day
+        ];
+
+        let reminders = pending_reminders(&blocks, 535);
+        assert_eq!(reminders.len(), 1);
+        assert_eq!(reminders[0].title, "Review");
+        assert_eq!(reminders[0].fire_minutes, 595);
+
+        let all = pending_reminders(&blocks, -1);
+        let titles: Vec<&str> = all.iter().map(|r| r.title.as_str()).collect();
+        assert_eq!(titles, vec!["Standup", "Review"]);
+    }
+}
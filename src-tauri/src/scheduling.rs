@@ -0,0 +1,368 @@
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, Weekday};
+use std::collections::HashMap;
+
+use crate::models::{event_color, BlockConflict, CalendarEvent, EventBlock, TimeBlock, TimeRange};
+
+const MINUTES_PER_DAY: i32 = 24 * 60;
+
+// Sort ranges and merge any that overlap or touch
+pub fn merge_ranges(mut ranges: Vec<TimeRange>) -> Vec<TimeRange> {
+    ranges.retain(|r| r.end_minutes > r.start_minutes);
+    ranges.sort_by_key(|r| (r.start_minutes, r.end_minutes));
+
+    let mut merged: Vec<TimeRange> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start_minutes <= last.end_minutes => {
+                last.end_minutes = last.end_minutes.max(range.end_minutes);
+            }
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+// Gaps within `window` not covered by `busy` that are at least `min_duration` long
+pub fn free_slots(window: TimeRange, busy: Vec<TimeRange>, min_duration: i32) -> Vec<TimeRange> {
+    let mut slots = Vec::new();
+    let mut cursor = window.start_minutes;
+
+    for range in merge_ranges(busy) {
+        if range.end_minutes <= cursor {
+            continue;
+        }
+        if range.start_minutes >= window.end_minutes {
+            break;
+        }
+        if range.start_minutes > cursor {
+            slots.push(TimeRange { start_minutes: cursor, end_minutes: range.start_minutes });
+        }
+        cursor = cursor.max(range.end_minutes);
+    }
+
+    if cursor < window.end_minutes {
+        slots.push(TimeRange { start_minutes: cursor, end_minutes: window.end_minutes });
+    }
+
+    slots.retain(|slot| slot.end_minutes - slot.start_minutes >= min_duration.max(1));
+    slots
+}
+
+pub fn block_range(block: &TimeBlock) -> TimeRange {
+    TimeRange {
+        start_minutes: block.start_minutes,
+        end_minutes: block.start_minutes + block.duration_minutes,
+    }
+}
+
+pub fn overlap(a: TimeRange, b: TimeRange) -> Option<TimeRange> {
+    let start_minutes = a.start_minutes.max(b.start_minutes);
+    let end_minutes = a.end_minutes.min(b.end_minutes);
+    if end_minutes > start_minutes {
+        Some(TimeRange { start_minutes, end_minutes })
+    } else {
+        None
+    }
+}
+
+// Every (block, event) pair on `date` whose times overlap
+pub fn find_conflicts(blocks: &[TimeBlock], events: &[CalendarEvent], date: &str) -> Vec<BlockConflict> {
+    let event_ranges: Vec<(&CalendarEvent, TimeRange)> = events
+        .iter()
+        .filter_map(|event| Some((event, event_minute_range(event, date)?)))
+        .collect();
+
+    let mut conflicts = Vec::new();
+    for block in blocks {
+        for (event, event_range) in &event_ranges {
+            if let Some(overlap) = overlap(block_range(block), *event_range) {
+                conflicts.push(BlockConflict {
+                    block_id: block.id,
+                    block_title: block.title.clone(),
+                    event_id: event.id,
+                    event_title: event.title.clone(),
+                    overlap,
+                });
+            }
+        }
+    }
+    conflicts
+}
+
+// Event times are RFC 3339 from Google; all-day events are stored without an offset
+fn parse_event_time(value: &str) -> Option<NaiveDateTime> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(value) {
+        return Some(date_time.with_timezone(&Local).naive_local());
+    }
+    NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S").ok()
+}
+
+// Whether an event overlaps the local days `from` through `to`. All-day events end at
+// the start of their end date, which Google gives as exclusive. Events whose times
+// can't be read are kept rather than silently dropped.
+pub fn event_overlaps(event: &CalendarEvent, from: NaiveDate, to: NaiveDate) -> bool {
+    let (Some(start), Some(end)) = (parse_event_time(&event.start_time), parse_event_time(&event.end_time)) else {
+        return true;
+    };
+    let end = if event.is_all_day {
+        end.date().and_hms_opt(0, 0, 0).unwrap_or(end).max(start + chrono::Duration::days(1))
+    } else {
+        end
+    };
+    let range_start = from.and_hms_opt(0, 0, 0).unwrap_or_default();
+    let range_end = (to + chrono::Duration::days(1)).and_hms_opt(0, 0, 0).unwrap_or_default();
+    start < range_end && end > range_start
+}
+
+// The part of a timed event that falls on `date`, in local minutes from midnight.
+// All-day events don't occupy a time range and return None.
+pub fn event_minute_range(event: &CalendarEvent, date: &str) -> Option<TimeRange> {
+    if event.is_all_day {
+        return None;
+    }
+
+    let day_start = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0)?;
+    let start = parse_event_time(&event.start_time)?;
+    let end = parse_event_time(&event.end_time)?;
+
+    let clamp = |minutes: i64| minutes.clamp(0, MINUTES_PER_DAY as i64) as i32;
+    let start_minutes = clamp((start - day_start).num_minutes());
+    let end_minutes = clamp((end - day_start).num_minutes());
+
+    if end_minutes > start_minutes {
+        Some(TimeRange { start_minutes, end_minutes })
+    } else {
+        None
+    }
+}
+
+// Events laid out for `date`, all-day events first, then timed events by start.
+// `colors` is the event_colors setting (see `event_color`).
+pub fn event_blocks(events: &[CalendarEvent], date: &str, colors: &HashMap<String, String>) -> Vec<EventBlock> {
+    let mut blocks: Vec<EventBlock> = events
+        .iter()
+        .filter_map(|event| {
+            let range = if event.is_all_day {
+                TimeRange { start_minutes: 0, end_minutes: MINUTES_PER_DAY }
+            } else {
+                event_minute_range(event, date)?
+            };
+            Some(EventBlock {
+                event_id: event.id,
+                start_minutes: range.start_minutes,
+                end_minutes: range.end_minutes,
+                title: event.title.clone(),
+                is_all_day: event.is_all_day,
+                location: event.location.clone(),
+                color: event_color(event.color_id.as_deref(), colors),
+            })
+        })
+        .collect();
+
+    blocks.sort_by_key(|block| (!block.is_all_day, block.start_minutes));
+    blocks
+}
+
+// The seven days of the week containing `date`, starting on `week_start`
+pub fn week_dates(date: NaiveDate, week_start: Weekday) -> Vec<NaiveDate> {
+    let week = date.week(week_start);
+    week.first_day().iter_days().take(7).collect()
+}
+
+// Snap units must tile an hour evenly, so every hour starts on the grid
+pub fn is_valid_snap(snap_minutes: i32) -> bool {
+    snap_minutes > 0 && 60 % snap_minutes == 0
+}
+
+// Round a start time to the nearest multiple of `snap_minutes`, staying within the day
+// and leaving room for `duration_minutes` before midnight
+pub fn snap_start(start_minutes: i32, duration_minutes: i32, snap_minutes: i32) -> i32 {
+    if !is_valid_snap(snap_minutes) {
+        return start_minutes;
+    }
+    let nearest = (start_minutes + snap_minutes / 2) / snap_minutes * snap_minutes;
+    let latest = (MINUTES_PER_DAY - duration_minutes.max(0)) / snap_minutes * snap_minutes;
+    nearest.clamp(0, latest.max(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snap_start() {
+        assert_eq!(snap_start(542, 30, 5), 540);
+        assert_eq!(snap_start(543, 30, 5), 545);
+        assert_eq!(snap_start(552, 30, 15), 555);
+        // Never past the last slot the block still fits in
+        assert_eq!(snap_start(1438, 30, 5), 1410);
+        assert!(is_valid_snap(20));
+        assert!(!is_valid_snap(25));
+        assert!(!is_valid_snap(0));
+    }
+
+    fn range(start_minutes: i32, end_minutes: i32) -> TimeRange {
+        TimeRange { start_minutes, end_minutes }
+    }
+
+    fn event(start_time: &str, end_time: &str, is_all_day: bool) -> CalendarEvent {
+        CalendarEvent {
+            id: None,
+            connection_id: 1,
+            external_id: "evt".to_string(),
+            calendar_id: "primary".to_string(),
+            title: "Meeting".to_string(),
+            start_time: start_time.to_string(),
+            end_time: end_time.to_string(),
+            description: None,
+            location: None,
+            is_all_day,
+            attendees: vec![],
+            last_updated: String::new(),
+            color_id: None,
+        }
+    }
+
+    #[test]
+    fn test_week_dates_cross_year_boundary() {
+        let date = NaiveDate::from_ymd_opt(2025, 1, 1).unwrap();  // A Wednesday
+        let dates = |start| week_dates(date, start).iter().map(|d| d.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(dates(Weekday::Mon), vec![
+            "2024-12-30", "2024-12-31", "2025-01-01", "2025-01-02", "2025-01-03", "2025-01-04", "2025-01-05",
+        ]);
+        assert_eq!(dates(Weekday::Sun)[0], "2024-12-29");
+        assert_eq!(dates(Weekday::Sun)[6], "2025-01-04");
+
+        // A week start day is the first day of its own week
+        let sunday = NaiveDate::from_ymd_opt(2025, 1, 5).unwrap();
+        assert_eq!(week_dates(sunday, Weekday::Sun)[0], sunday);
+        assert_eq!(week_dates(sunday, Weekday::Mon)[6], sunday);
+    }
+
+    #[test]
+    fn test_merge_ranges() {
+        let merged = merge_ranges(vec![range(600, 660), range(540, 570), range(570, 600), range(650, 700), range(800, 800)]);
+        assert_eq!(merged, vec![range(540, 700)]);
+
+        let merged = merge_ranges(vec![range(600, 630), range(540, 570)]);
+        assert_eq!(merged, vec![range(540, 570), range(600, 630)]);
+    }
+
+    #[test]
+    fn test_free_slots() {
+        let window = range(480, 1020);
+        let busy = vec![range(540, 600), range(590, 630), range(720, 780), range(1000, 1100), range(300, 400)];
+
+        assert_eq!(free_slots(window, busy.clone(), 30), vec![
+            range(480, 540),
+            range(630, 720),
+            range(780, 1000),
+        ]);
+        assert_eq!(free_slots(window, busy, 120), vec![range(780, 1000)]);
+        assert_eq!(free_slots(window, vec![], 60), vec![window]);
+        assert!(free_slots(window, vec![range(0, 1440)], 5).is_empty());
+    }
+
+    #[test]
+    fn test_find_conflicts() {
+        let block = |id: i64, title: &str, start_minutes: i32, duration_minutes: i32| TimeBlock {
+            id: Some(id),
+            date: "2025-06-01".to_string(),
+            start_minutes,
+            duration_minutes,
+            title: title.to_string(),
+            notes_file: None,
+            color: "#3b82f6".to_string(),
+            tags: vec![],
+            created_at: None,
+            updated_at: None,
+            reminder_minutes: None,
+            completed: false,
+            actual_duration_minutes: None,
+            notes_content: None,
+            is_placeholder: false,
+        };
+        let blocks = vec![block(1, "Focus", 540, 60), block(2, "Lunch", 720, 30)];
+        let events = vec![
+            event("2025-06-01T09:30:00", "2025-06-01T10:30:00", false),
+            event("2025-06-01T10:00:00", "2025-06-01T11:00:00", false),  // Touches Focus's end only
+            event("2025-06-01T00:00:00", "2025-06-01T23:59:59", true),
+        ];
+
+        let conflicts = find_conflicts(&blocks, &events, "2025-06-01");
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].block_id, Some(1));
+        assert_eq!(conflicts[0].event_title, "Meeting");
+        assert_eq!(conflicts[0].overlap, range(570, 600));
+    }
+
+    #[test]
+    fn test_event_minute_range() {
+        let timed = event("2025-06-01T09:30:00", "2025-06-01T10:15:00", false);
+        assert_eq!(event_minute_range(&timed, "2025-06-01"), Some(range(570, 615)));
+
+        let overnight = event("2025-06-01T23:00:00", "2025-06-02T01:00:00", false);
+        assert_eq!(event_minute_range(&overnight, "2025-06-01"), Some(range(1380, 1440)));
+        assert_eq!(event_minute_range(&overnight, "2025-06-02"), Some(range(0, 60)));
+
+        let all_day = event("2025-06-01T00:00:00", "2025-06-01T23:59:59", true);
+        assert_eq!(event_minute_range(&all_day, "2025-06-01"), None);
+        assert_eq!(event_minute_range(&timed, "2025-06-03"), None);
+    }
+
+    #[test]
+    fn test_event_blocks() {
+        let events = vec![
+            event("2025-06-01T13:00:00", "2025-06-01T14:00:00", false),
+            event("2025-06-01T00:00:00", "2025-06-02T00:00:00", true),
+            event("2025-05-31T22:00:00", "2025-06-01T08:30:00", false),
+            event("2025-06-02T09:00:00", "2025-06-02T10:00:00", false),
+        ];
+
+        let blocks = event_blocks(&events, "2025-06-01", &HashMap::new());
+        let ranges: Vec<(i32, i32, bool)> = blocks
+            .iter()
+            .map(|b| (b.start_minutes, b.end_minutes, b.is_all_day))
+            .collect();
+        assert_eq!(ranges, vec![(0, 1440, true), (0, 510, false), (780, 840, false)]);
+        assert!(blocks.iter().all(|b| b.color == crate::models::DEFAULT_EVENT_COLOR));
+    }
+
+    #[test]
+    fn test_event_overlaps() {
+        let day = |d: &str| NaiveDate::parse_from_str(d, "%Y-%m-%d").unwrap();
+        let overnight = event("2025-05-31T22:00:00", "2025-06-01T08:30:00", false);
+        assert!(event_overlaps(&overnight, day("2025-06-01"), day("2025-06-01")));
+        assert!(!event_overlaps(&overnight, day("2025-06-02"), day("2025-06-03")));
+
+        // Stored from Google's exclusive end date, so it covers June 1-2 only
+        let trip = event("2025-06-01T00:00:00", "2025-06-03T23:59:59", true);
+        assert!(event_overlaps(&trip, day("2025-06-02"), day("2025-06-02")));
+        assert!(!event_overlaps(&trip, day("2025-06-03"), day("2025-06-03")));
+        let single = event("2025-06-01T00:00:00", "2025-06-01T23:59:59", true);
+        assert!(event_overlaps(&single, day("2025-06-01"), day("2025-06-01")));
+
+        let ends_at_midnight = event("2025-06-01T23:00:00", "2025-06-02T00:00:00", false);
+        assert!(!event_overlaps(&ends_at_midnight, day("2025-06-02"), day("2025-06-02")));
+    }
+
+    #[test]
+    fn test_event_block_colors() {
+        let mut tomato = event("2025-06-01T13:00:00", "2025-06-01T14:00:00", false);
+        tomato.color_id = Some("11".to_string());
+        let mut sage = event("2025-06-01T15:00:00", "2025-06-01T16:00:00", false);
+        sage.color_id = Some("2".to_string());
+        let plain = event("2025-06-01T17:00:00", "2025-06-01T18:00:00", false);
+
+        let overrides = HashMap::from([
+            ("2".to_string(), "#00ff00".to_string()),
+            ("default".to_string(), "#123456".to_string()),
+        ]);
+        let colors: Vec<String> = event_blocks(&[tomato, sage, plain], "2025-06-01", &overrides)
+            .into_iter()
+            .map(|b| b.color)
+            .collect();
+        assert_eq!(colors, vec!["#dc2127", "#00ff00", "#123456"]);
+    }
+}
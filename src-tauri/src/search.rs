@@ -1,18 +1,94 @@
 use tantivy::schema::*;
 use tantivy::{Index, IndexReader, ReloadPolicy, Term, IndexWriter};
 use tantivy::collector::TopDocs;
-use tantivy::query::QueryParser;
+use tantivy::query::{BooleanQuery, Occur, Query, QueryParser, TermQuery};
+use tantivy::{Searcher, SnippetGenerator};
 use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::fs;
 use anyhow::Result;
-use crate::models::SearchResult;
+use log::warn;
+use crate::models::{SearchResult, TimeBlock, DEFAULT_BLOCK_COLOR};
+
+/// Ordering applied to search results.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SearchSort {
+    Relevance,
+    DateDesc,
+    DateAsc,
+}
+
+impl SearchSort {
+    pub fn parse(value: &str) -> Result<Self> {
+        match value {
+            "relevance" => Ok(SearchSort::Relevance),
+            "date_desc" => Ok(SearchSort::DateDesc),
+            "date_asc" => Ok(SearchSort::DateAsc),
+            other => Err(anyhow::anyhow!("Unknown sort order: {}", other)),
+        }
+    }
+}
+
+// Relevance weights per field, so a match in a block's title ranks above one buried in its notes
+const TITLE_BOOST: f32 = 3.0;
+const TAGS_BOOST: f32 = 2.0;
+const CONTENT_BOOST: f32 = 1.0;
+const FILE_NAME_BOOST: f32 = 2.0;
+// Highlight limits; callers ask for more or less within these bounds
+const DEFAULT_HIGHLIGHT_FRAGMENTS: usize = 1;
+const DEFAULT_HIGHLIGHT_CHARS: usize = 150;
+const MAX_HIGHLIGHT_FRAGMENTS: usize = 10;
+const MIN_HIGHLIGHT_CHARS: usize = 20;
+const MAX_HIGHLIGHT_CHARS: usize = 1000;
+// Words this short get too many near misses to be worth correcting
+const MIN_SUGGEST_WORD_LEN: usize = 3;
+
+/// How many matching fragments of a result's notes to return, and how long each may be.
+/// Fragments are HTML with matched words wrapped in <b>.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HighlightOptions {
+    pub fragments: usize,
+    pub max_chars: usize,
+}
+
+impl HighlightOptions {
+    // Out-of-range values are clamped rather than rejected; zero fragments turns highlighting off
+    pub fn new(fragments: Option<usize>, max_chars: Option<usize>) -> Self {
+        HighlightOptions {
+            fragments: fragments.unwrap_or(DEFAULT_HIGHLIGHT_FRAGMENTS).min(MAX_HIGHLIGHT_FRAGMENTS),
+            max_chars: max_chars.unwrap_or(DEFAULT_HIGHLIGHT_CHARS).clamp(MIN_HIGHLIGHT_CHARS, MAX_HIGHLIGHT_CHARS),
+        }
+    }
+}
+
+impl Default for HighlightOptions {
+    fn default() -> Self {
+        HighlightOptions::new(None, None)
+    }
+}
+
+// An attachment as indexed: found by its file name or extracted text
+pub struct AttachmentDocument {
+    pub attachment_id: i64,
+    pub time_block: TimeBlock,
+    pub file_name: String,
+    pub text: String,  // Empty when no text was extracted
+}
+
+// A named notes section as indexed, returned under its block with the section name
+pub struct NoteSectionDocument {
+    pub section_id: i64,
+    pub time_block: TimeBlock,
+    pub section_name: String,
+    pub content: String,
+}
 
 pub struct SearchService {
     index: Index,
     schema: Schema,
     reader: IndexReader,
     query_parser: QueryParser,
+    rebuilt: bool,
 }
 
 impl SearchService {
@@ -26,19 +102,44 @@ impl SearchService {
         
         let title = schema_builder.add_text_field("title", TEXT | STORED);
         let content = schema_builder.add_text_field("content", TEXT | STORED);
-        let tags = schema_builder.add_text_field("tags", TEXT | STORED);
-        let _date = schema_builder.add_text_field("date", TEXT | STORED);
+        // Tags words for free-text search, plus each whole tag for exact matches (tag:"deep work")
+        let tags = schema_builder.add_text_field("tags", TEXT);
+        let _tag = schema_builder.add_text_field("tag", STRING | STORED);
+        // Attachment documents only: the file name, split into words ("budget-q3.xlsx" -> budget q3 xlsx)
+        let file_name = schema_builder.add_text_field("file_name", TEXT);
+        // Section documents only: the notes section name, searchable as section:agenda
+        let _section = schema_builder.add_text_field("section", TEXT | STORED);
+        // "block", "attachment" or "section"; either way results point at the owning block
+        let _doc_type = schema_builder.add_text_field("doc_type", STRING | STORED);
+        // Untokenized so a day can be matched exactly
+        let _date = schema_builder.add_text_field("date", STRING | STORED);
         let _start_minutes = schema_builder.add_i64_field("start_minutes", INDEXED | STORED);
         let _duration_minutes = schema_builder.add_i64_field("duration_minutes", INDEXED | STORED);
         let _time_block_id = schema_builder.add_i64_field("time_block_id", INDEXED | STORED);
+        // "block:{id}", "attachment:{id}" or "section:{id}"; a block can own several documents
+        let _doc_key = schema_builder.add_text_field("doc_key", STRING);
         
         let schema = schema_builder.build();
         
-        // Create or open index
-        let index = if search_dir.join("meta.json").exists() {
-            Index::open_in_dir(&search_dir)?
+        // Create or open index. An index written with an older schema, or one that
+        // can't be read, is discarded; the caller is expected to repopulate it
+        // (see `was_rebuilt`).
+        let existing = if search_dir.join("meta.json").exists() {
+            Some(open_existing(&search_dir))
         } else {
-            Index::create_in_dir(&search_dir, schema.clone())?
+            None
+        };
+        let (index, rebuilt) = match existing {
+            Some(Ok(index)) if index.schema() == schema => (index, false),
+            Some(Ok(outdated)) => {
+                drop(outdated);
+                (recreate_index(&search_dir, &schema)?, true)
+            }
+            Some(Err(e)) => {
+                warn!("Search index is corrupt, recreating it: {}", e);
+                (recreate_index(&search_dir, &schema)?, true)
+            }
+            None => (Index::create_in_dir(&search_dir, schema.clone())?, false),
         };
         
         let reader = index
@@ -47,31 +148,125 @@ impl SearchService {
             .try_into()?;
         
         // Create query parser
-        let query_parser = QueryParser::for_index(&index, vec![title, content, tags]);
+        let mut query_parser = QueryParser::for_index(&index, vec![title, content, tags, file_name]);
+        query_parser.set_field_boost(title, TITLE_BOOST);
+        query_parser.set_field_boost(tags, TAGS_BOOST);
+        query_parser.set_field_boost(content, CONTENT_BOOST);
+        query_parser.set_field_boost(file_name, FILE_NAME_BOOST);
         
         Ok(SearchService {
             index,
             schema,
             reader,
             query_parser,
+            rebuilt,
         })
     }
     
-    pub fn index_time_block(&self, time_block: &crate::models::TimeBlock, content: &str) -> Result<()> {
+    /// True if an outdated index was discarded on startup and needs reindexing.
+    pub fn was_rebuilt(&self) -> bool {
+        self.rebuilt
+    }
+    
+    /// Reload the reader and count documents, failing if the index can't be read.
+    pub fn check(&self) -> Result<u64> {
+        self.reader.reload()?;
+        Ok(self.reader.searcher().num_docs())
+    }
+    
+    /// Number of documents visible to searches.
+    pub fn doc_count(&self) -> u64 {
+        self.reader.searcher().num_docs()
+    }
+    
+    pub fn index_time_block(&self, time_block: &TimeBlock, content: &str) -> Result<()> {
+        self.index_time_blocks(&[(time_block.clone(), content.to_string())])
+    }
+    
+    /// Index several blocks with a single commit, replacing any existing documents for them.
+    pub fn index_time_blocks(&self, blocks: &[(TimeBlock, String)]) -> Result<()> {
         let mut writer: IndexWriter<BTreeMap<Field, OwnedValue>> = self.index.writer(50_000_000)?;
+        for (time_block, content) in blocks {
+            let key = time_block.id.map(|id| format!("block:{}", id));
+            self.add_document(&writer, key, "block", time_block, None, content)?;
+        }
+        writer.commit()?;
+        self.reader.reload()?;
         
+        Ok(())
+    }
+    
+    /// Index attachments by file name and any text extracted from them (e.g. by OCR),
+    /// each as its own document that searches return under the owning block.
+    /// Replaces earlier documents for them.
+    pub fn index_attachments(&self, attachments: &[AttachmentDocument]) -> Result<()> {
+        let mut writer: IndexWriter<BTreeMap<Field, OwnedValue>> = self.index.writer(50_000_000)?;
+        for attachment in attachments {
+            let key = format!("attachment:{}", attachment.attachment_id);
+            self.add_document(&writer, Some(key), "attachment", &attachment.time_block, Some(("file_name", &attachment.file_name)), &attachment.text)?;
+        }
+        writer.commit()?;
+        self.reader.reload()?;
+        
+        Ok(())
+    }
+    
+    /// Index named notes sections, each as its own document returned under the owning block.
+    /// Replaces earlier documents for them.
+    pub fn index_note_sections(&self, sections: &[NoteSectionDocument]) -> Result<()> {
+        let mut writer: IndexWriter<BTreeMap<Field, OwnedValue>> = self.index.writer(50_000_000)?;
+        for section in sections {
+            let key = format!("section:{}", section.section_id);
+            self.add_document(&writer, Some(key), "section", &section.time_block, Some(("section", &section.section_name)), &section.content)?;
+        }
+        writer.commit()?;
+        self.reader.reload()?;
+        
+        Ok(())
+    }
+    
+    pub fn delete_attachment(&self, attachment_id: i64) -> Result<()> {
+        let mut writer: IndexWriter<BTreeMap<Field, OwnedValue>> = self.index.writer(50_000_000)?;
+        let doc_key = self.schema.get_field("doc_key").unwrap();
+        
+        writer.delete_term(Term::from_field_text(doc_key, &format!("attachment:{}", attachment_id)));
+        writer.commit()?;
+        self.reader.reload()?;
+        
+        Ok(())
+    }
+    
+    fn add_document(
+        &self,
+        writer: &IndexWriter<BTreeMap<Field, OwnedValue>>,
+        key: Option<String>,
+        doc_type: &str,
+        time_block: &TimeBlock,
+        label: Option<(&str, &str)>,  // e.g. ("file_name", "budget.xlsx")
+        content: &str
+    ) -> Result<()> {
         let title = self.schema.get_field("title").unwrap();
         let content_field = self.schema.get_field("content").unwrap();
         let tags = self.schema.get_field("tags").unwrap();
+        let tag = self.schema.get_field("tag").unwrap();
         let date = self.schema.get_field("date").unwrap();
         let start_minutes = self.schema.get_field("start_minutes").unwrap();
         let duration_minutes = self.schema.get_field("duration_minutes").unwrap();
         let time_block_id = self.schema.get_field("time_block_id").unwrap();
+        let doc_key = self.schema.get_field("doc_key").unwrap();
+        let doc_type_field = self.schema.get_field("doc_type").unwrap();
         
         let mut doc = BTreeMap::new();
+        doc.insert(doc_type_field, OwnedValue::Str(doc_type.to_string()));
+        if let Some((field, value)) = label {
+            doc.insert(self.schema.get_field(field)?, OwnedValue::Str(value.to_string()));
+        }
         doc.insert(title, OwnedValue::Str(time_block.title.clone()));
         doc.insert(content_field, OwnedValue::Str(content.to_string()));
         doc.insert(tags, OwnedValue::Str(time_block.tags.join(" ")));
+        doc.insert(tag, OwnedValue::Array(
+            time_block.tags.iter().map(|t| OwnedValue::Str(t.trim().to_string())).collect()
+        ));
         doc.insert(date, OwnedValue::Str(time_block.date.clone()));
         doc.insert(start_minutes, OwnedValue::I64(time_block.start_minutes as i64));
         doc.insert(duration_minutes, OwnedValue::I64(time_block.duration_minutes as i64));
@@ -79,44 +274,162 @@ impl SearchService {
         if let Some(id) = time_block.id {
             doc.insert(time_block_id, OwnedValue::I64(id));
         }
+        if let Some(key) = key {
+            // Re-indexing replaces the previous document with the same key
+            writer.delete_term(Term::from_field_text(doc_key, &key));
+            doc.insert(doc_key, OwnedValue::Str(key));
+        }
         
         writer.add_document(doc)?;
-        writer.commit()?;
         
         Ok(())
     }
     
-    pub fn search(&self, query_str: &str, limit: usize) -> Result<Vec<SearchResult>> {
-        let searcher = self.reader.searcher();
+    /// Search the index, returning at most `limit` results.
+    ///
+    /// Date sorting is applied to the top `limit` matches by relevance, so it
+    /// still respects the query filter; it only changes the order they're returned in.
+    pub fn search(&self, query_str: &str, limit: usize, sort: SearchSort) -> Result<Vec<SearchResult>> {
+        self.search_with_highlights(query_str, limit, sort, HighlightOptions::default())
+    }
+    
+    /// Like `search`, with control over the highlighted fragments returned per result.
+    pub fn search_with_highlights(
+        &self,
+        query_str: &str,
+        limit: usize,
+        sort: SearchSort,
+        highlights: HighlightOptions
+    ) -> Result<Vec<SearchResult>> {
+        let query = self.parse_query(query_str)?;
+        let mut results = self.collect_results(query.as_ref(), limit, highlights)?;
+        
+        match sort {
+            SearchSort::Relevance => {}
+            SearchSort::DateAsc => {
+                results.sort_by(|a, b| (&a.date, a.start_minutes).cmp(&(&b.date, b.start_minutes)));
+            }
+            SearchSort::DateDesc => {
+                results.sort_by(|a, b| (&b.date, b.start_minutes).cmp(&(&a.date, a.start_minutes)));
+            }
+        }
+        
+        Ok(results)
+    }
+    
+    /// Search within a single day, matching the indexed date exactly.
+    /// An empty query returns everything indexed for that day.
+    pub fn search_in_date(&self, date: &str, query_str: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        let date_field = self.schema.get_field("date").unwrap();
+        let date_query: Box<dyn Query> = Box::new(TermQuery::new(
+            Term::from_field_text(date_field, date),
+            IndexRecordOption::Basic,
+        ));
+        
+        if query_str.trim().is_empty() {
+            return self.collect_results(date_query.as_ref(), limit, HighlightOptions::default());
+        }
+        
+        let query = BooleanQuery::new(vec![
+            (Occur::Must, self.parse_query(query_str)?),
+            (Occur::Must, date_query),
+        ]);
+        self.collect_results(&query, limit, HighlightOptions::default())
+    }
+    
+    /// Search blocks carrying exactly `tag` (a whole tag, as stored), so "work" doesn't
+    /// match blocks that only mention work. An empty query returns everything with the tag.
+    pub fn search_by_tag(&self, tag: &str, query_str: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        let tag_field = self.schema.get_field("tag").unwrap();
+        let tag_query: Box<dyn Query> = Box::new(TermQuery::new(
+            Term::from_field_text(tag_field, tag.trim()),
+            IndexRecordOption::Basic,
+        ));
+        
+        if query_str.trim().is_empty() {
+            return self.collect_results(tag_query.as_ref(), limit, HighlightOptions::default());
+        }
+        
+        let query = BooleanQuery::new(vec![
+            (Occur::Must, self.parse_query(query_str)?),
+            (Occur::Must, tag_query),
+        ]);
+        self.collect_results(&query, limit, HighlightOptions::default())
+    }
+    
+    /// Parse a user query. Supports the QueryParser syntax over title, content and
+    /// tags (and attachment file names), including phrases ("weekly review") and field scopes (title:meeting).
+    /// `tag:` matches a whole tag exactly, e.g. tag:"deep work".
+    /// Queries that don't parse, e.g. with an unknown field or stray syntax, are
+    /// retried as plain words so a typo never fails the whole search.
+    fn parse_query(&self, query_str: &str) -> Result<Box<dyn Query>> {
+        if !query_str.chars().any(char::is_alphanumeric) {
+            return Err(anyhow::anyhow!("Search query is empty"));
+        }
         
-        let query = self.query_parser.parse_query(query_str)?;
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        match self.query_parser.parse_query(query_str) {
+            Ok(query) => Ok(query),
+            Err(_) => Ok(self.query_parser.parse_query(&self.plain_words(query_str))?),
+        }
+    }
+    
+    // Keep known field scopes and quotes, turn everything else into plain words
+    fn plain_words(&self, query_str: &str) -> String {
+        let known_field = |name: &str| matches!(name, "title" | "content" | "tags" | "tag" | "file_name" | "section");
+        
+        query_str.split_whitespace()
+            .map(|token| match token.split_once(':') {
+                Some((field, value)) if known_field(field) && !value.is_empty() => {
+                    format!("{}:{}", field, strip_syntax(value))
+                }
+                _ => strip_syntax(token),
+            })
+            .filter(|token| !token.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+    
+    fn collect_results(&self, query: &dyn Query, limit: usize, highlights: HighlightOptions) -> Result<Vec<SearchResult>> {
+        let searcher = self.reader.searcher();
+        let top_docs = searcher.search(query, &TopDocs::with_limit(limit))?;
+        let snippets = self.snippet_generator(&searcher, query, highlights)?;
         
         let title = self.schema.get_field("title").unwrap();
         let content_field = self.schema.get_field("content").unwrap();
-        let tags = self.schema.get_field("tags").unwrap();
+        let tag = self.schema.get_field("tag").unwrap();
         let date = self.schema.get_field("date").unwrap();
         let start_minutes = self.schema.get_field("start_minutes").unwrap();
         let duration_minutes = self.schema.get_field("duration_minutes").unwrap();
         let time_block_id = self.schema.get_field("time_block_id").unwrap();
+        let section = self.schema.get_field("section").unwrap();
         
         let mut results = Vec::new();
         
         for (score, doc_address) in top_docs {
             let doc: BTreeMap<Field, OwnedValue> = searcher.doc(doc_address)?;
             
+            // A block matching in both its notes and an attachment is listed once, at its best score
+            let id = doc.get(&time_block_id).and_then(|v| v.as_i64());
+            if id.is_some() && results.iter().any(|r: &SearchResult| Some(r.id) == id) {
+                continue;
+            }
+            
+            let content = doc.get(&content_field)
+                .and_then(|v| v.as_str())
+                .unwrap_or("")
+                .to_string();
+            let highlighted = match &snippets {
+                Some(generator) => fragments(generator, &content, highlights.fragments),
+                None => Vec::new(),
+            };
+            
             let result = SearchResult {
-                id: doc.get(&time_block_id)
-                    .and_then(|v| v.as_i64())
-                    .unwrap_or(0),
+                id: id.unwrap_or(0),
                 title: doc.get(&title)
                     .and_then(|v| v.as_str())
                     .unwrap_or("")
                     .to_string(),
-                content: doc.get(&content_field)
-                    .and_then(|v| v.as_str())
-                    .unwrap_or("")
-                    .to_string(),
+                content,
                 date: doc.get(&date)
                     .and_then(|v| v.as_str())
                     .unwrap_or("")
@@ -127,12 +440,18 @@ impl SearchService {
                 duration_minutes: doc.get(&duration_minutes)
                     .and_then(|v| v.as_i64())
                     .unwrap_or(0) as i32,
-                tags: doc.get(&tags)
-                    .and_then(|v| v.as_str())
-                    .map(|t| t.split_whitespace().map(String::from).collect())
-                    .unwrap_or_default(),
+                tags: match doc.get(&tag) {
+                    Some(OwnedValue::Array(values)) => values.iter()
+                        .filter_map(|v| v.as_str().map(String::from))
+                        .collect(),
+                    Some(OwnedValue::Str(value)) => vec![value.clone()],
+                    _ => Vec::new(),
+                },
                 score,
-                highlights: vec![], // TODO: Add highlighting
+                highlights: highlighted,
+                section: doc.get(&section).and_then(|v| v.as_str()).map(String::from),
+                // Colors aren't indexed; callers fill in the block's current one
+                color: DEFAULT_BLOCK_COLOR.to_string(),
             };
             
             results.push(result);
@@ -141,14 +460,419 @@ impl SearchService {
         Ok(results)
     }
     
+    // None when highlighting is off
+    fn snippet_generator(
+        &self,
+        searcher: &Searcher,
+        query: &dyn Query,
+        highlights: HighlightOptions
+    ) -> Result<Option<SnippetGenerator>> {
+        if highlights.fragments == 0 {
+            return Ok(None);
+        }
+        let content = self.schema.get_field("content").unwrap();
+        let mut generator = SnippetGenerator::create(searcher, query, content)?;
+        generator.set_max_num_chars(highlights.max_chars);
+        Ok(Some(generator))
+    }
+    
+    /// Spelling alternatives for a query, e.g. "meetnig notes" -> "meeting notes".
+    /// Each suggestion replaces one unknown word with an indexed term within edit
+    /// distance 1 (2 for longer words), preferring closer and more common terms.
+    pub fn suggest(&self, query_str: &str, limit: usize) -> Result<Vec<String>> {
+        let words: Vec<String> = self.plain_words(query_str)
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty() && !matches!(*word, "title" | "content" | "tags" | "tag" | "file_name" | "section"))
+            .map(|word| word.to_lowercase())
+            .collect();
+        if words.is_empty() {
+            return Ok(Vec::new());
+        }
+        
+        let terms = self.term_frequencies()?;
+        let mut candidates: Vec<(usize, String, usize, u64)> = Vec::new();  // (word index, term, distance, doc freq)
+        for (position, word) in words.iter().enumerate() {
+            let length = word.chars().count();
+            if length < MIN_SUGGEST_WORD_LEN || terms.contains_key(word) {
+                continue;
+            }
+            let max_distance = if length <= 5 { 1 } else { 2 };
+            for (term, &doc_freq) in &terms {
+                let distance = edit_distance(word, term);
+                if distance <= max_distance {
+                    candidates.push((position, term.clone(), distance, doc_freq));
+                }
+            }
+        }
+        candidates.sort_by(|a, b| a.2.cmp(&b.2).then(b.3.cmp(&a.3)).then(a.1.cmp(&b.1)));
+        
+        let mut suggestions: Vec<String> = Vec::new();
+        for (position, term, _, _) in candidates {
+            let mut corrected = words.clone();
+            corrected[position] = term;
+            let suggestion = corrected.join(" ");
+            if !suggestions.contains(&suggestion) {
+                suggestions.push(suggestion);
+            }
+            if suggestions.len() >= limit {
+                break;
+            }
+        }
+        Ok(suggestions)
+    }
+    
+    // Every term in the searchable text fields with the number of documents containing it
+    fn term_frequencies(&self) -> Result<BTreeMap<String, u64>> {
+        let searcher = self.reader.searcher();
+        let mut terms: BTreeMap<String, u64> = BTreeMap::new();
+        
+        for name in ["title", "content", "tags", "file_name"] {
+            let field = self.schema.get_field(name).unwrap();
+            for segment_reader in searcher.segment_readers() {
+                let inverted_index = segment_reader.inverted_index(field)?;
+                let mut stream = inverted_index.terms().stream()?;
+                while stream.advance() {
+                    if let Ok(term) = std::str::from_utf8(stream.key()) {
+                        *terms.entry(term.to_string()).or_default() += stream.value().doc_freq as u64;
+                    }
+                }
+            }
+        }
+        Ok(terms)
+    }
+    
     pub fn delete_time_block(&self, time_block_id: i64) -> Result<()> {
+        self.delete_time_blocks(&[time_block_id])
+    }
+    
+    /// Remove several blocks with a single commit.
+    pub fn delete_time_blocks(&self, time_block_ids: &[i64]) -> Result<()> {
         let mut writer: IndexWriter<BTreeMap<Field, OwnedValue>> = self.index.writer(50_000_000)?;
         let time_block_id_field = self.schema.get_field("time_block_id").unwrap();
         
-        let term = Term::from_field_i64(time_block_id_field, time_block_id);
-        writer.delete_term(term);
+        for &time_block_id in time_block_ids {
+            writer.delete_term(Term::from_field_i64(time_block_id_field, time_block_id));
+        }
         writer.commit()?;
+        self.reader.reload()?;
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// Up to `count` highlighted fragments of `text`, best first. Each pass takes the
+// best remaining fragment and cuts it out so the next pass finds a different one.
+fn fragments(generator: &SnippetGenerator, text: &str, count: usize) -> Vec<String> {
+    let mut remaining = text.to_string();
+    let mut fragments = Vec::new();
+    while fragments.len() < count {
+        let snippet = generator.snippet(&remaining);
+        if snippet.is_empty() {
+            break;
+        }
+        let Some(start) = remaining.find(snippet.fragment()) else {
+            break;
+        };
+        fragments.push(snippet.to_html());
+        remaining.replace_range(start..start + snippet.fragment().len(), "\n");
+    }
+    fragments
+}
+
+fn open_existing(search_dir: &Path) -> tantivy::Result<Index> {
+    let index = Index::open_in_dir(search_dir)?;
+    // Opening a reader loads every segment, so damaged segment files show up here too
+    index.reader()?;
+    Ok(index)
+}
+
+fn recreate_index(search_dir: &Path, schema: &Schema) -> Result<Index> {
+    fs::remove_dir_all(search_dir)?;
+    fs::create_dir_all(search_dir)?;
+    Ok(Index::create_in_dir(search_dir, schema.clone())?)
+}
+
+// Levenshtein distance between two words, by characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    
+    for (i, a_char) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// Replace query syntax characters with spaces, keeping quotes for phrases
+fn strip_syntax(token: &str) -> String {
+    token.chars()
+        .map(|c| if c.is_alphanumeric() || c == '"' { c } else { ' ' })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    fn setup() -> (SearchService, tempfile::TempDir) {
+        let dir = tempdir().unwrap();
+        let search = SearchService::new(&dir.path().to_path_buf()).unwrap();
+        (search, dir)
+    }
+    
+    fn ids(results: Vec<SearchResult>) -> Vec<i64> {
+        let mut ids: Vec<i64> = results.iter().map(|r| r.id).collect();
+        ids.sort();
+        ids
+    }
+    
+    fn block(id: i64, date: &str, title: &str) -> TimeBlock {
+        TimeBlock {
+            id: Some(id),
+            date: date.to_string(),
+            start_minutes: 540,
+            duration_minutes: 30,
+            title: title.to_string(),
+            notes_file: None,
+            color: "#3b82f6".to_string(),
+            tags: vec![],
+            created_at: None,
+            updated_at: None,
+            reminder_minutes: None,
+            completed: false,
+            actual_duration_minutes: None,
+            notes_content: None,
+            is_placeholder: false,
+        }
+    }
+    
+    #[test]
+    fn test_title_match_outranks_content_match() {
+        let (search, _dir) = setup();
+        search.index_time_blocks(&[
+            (block(1, "2025-06-01", "Planning"), "Went through the quarterly budget with finance".to_string()),
+            (block(2, "2025-06-02", "Budget review"), "Went through the numbers with finance".to_string()),
+        ]).unwrap();
+        
+        let results = search.search("budget", 10, SearchSort::Relevance).unwrap();
+        assert_eq!(results.iter().map(|r| r.id).collect::<Vec<_>>(), vec![2, 1]);
+    }
+    
+    #[test]
+    fn test_attachment_text_is_found_under_its_block() {
+        let (search, _dir) = setup();
+        let focus = block(1, "2025-06-01", "Focus");
+        search.index_time_block(&focus, "outline the chapter").unwrap();
+        search.index_attachments(&[AttachmentDocument {
+            attachment_id: 10,
+            time_block: focus.clone(),
+            file_name: "IMG_0042.png".to_string(),
+            text: "whiteboard sketch".to_string(),
+        }]).unwrap();
+        
+        // Re-indexing the block's notes keeps the attachment text
+        search.index_time_block(&focus, "outline the whiteboard chapter").unwrap();
+        assert_eq!(search.search("sketch", 10, SearchSort::Relevance).unwrap()[0].id, 1);
+        assert_eq!(ids(search.search("whiteboard", 10, SearchSort::Relevance).unwrap()), vec![1]);
+        
+        search.delete_attachment(10).unwrap();
+        assert!(search.search("sketch", 10, SearchSort::Relevance).unwrap().is_empty());
+        assert_eq!(search.doc_count(), 1);
+    }
+    
+    #[test]
+    fn test_corrupt_index_is_recreated() {
+        let dir = tempdir().unwrap();
+        let search = SearchService::new(&dir.path().to_path_buf()).unwrap();
+        search.index_time_block(&block(1, "2025-06-01", "Focus"), "").unwrap();
+        drop(search);
+        
+        fs::write(dir.path().join("search/meta.json"), b"{ not json").unwrap();
+        
+        let search = SearchService::new(&dir.path().to_path_buf()).unwrap();
+        assert!(search.was_rebuilt());
+        assert_eq!(search.doc_count(), 0);
+        search.index_time_block(&block(2, "2025-06-01", "Planning"), "").unwrap();
+        assert_eq!(ids(search.search("planning", 10, SearchSort::Relevance).unwrap()), vec![2]);
+    }
+    
+    #[test]
+    fn test_phrase_and_field_queries() {
+        let (search, _dir) = setup();
+        let mut tagged = block(2, "2025-06-01", "Planning");
+        tagged.tags = vec!["work".to_string()];
+        search.index_time_blocks(&[
+            (block(1, "2025-06-01", "Team meeting"), "agenda for the weekly review".to_string()),
+            (tagged, "review weekly goals".to_string()),
+            (block(3, "2025-06-02", "Lunch"), "meeting a friend".to_string()),
+        ]).unwrap();
+        
+        let search = |query: &str| ids(search.search(query, 10, SearchSort::Relevance).unwrap());
+        
+        assert_eq!(search("\"weekly review\""), vec![1]);
+        assert_eq!(search("title:meeting"), vec![1]);
+        assert_eq!(search("meeting"), vec![1, 3]);
+        assert_eq!(search("tags:work"), vec![2]);
+        assert_eq!(search("title:meeting tags:work \"weekly review\""), vec![1, 2]);
+        
+        // Unknown fields and stray syntax fall back to plain words instead of failing
+        assert_eq!(search("project:lunch"), vec![3]);
+        assert_eq!(search("lunch)"), vec![3]);
+    }
+    
+    #[test]
+    fn test_multi_word_tag_matches_exactly() {
+        let (search, _dir) = setup();
+        let mut deep = block(1, "2025-06-01", "Writing");
+        deep.tags = vec!["deep work".to_string(), "writing".to_string()];
+        let mut split = block(2, "2025-06-01", "Review");
+        split.tags = vec!["deep".to_string(), "work".to_string()];
+        search.index_time_blocks(&[(deep, String::new()), (split, String::new())]).unwrap();
+        
+        let search_ids = |query: &str| ids(search.search(query, 10, SearchSort::Relevance).unwrap());
+        assert_eq!(search_ids("tag:\"deep work\""), vec![1]);
+        assert_eq!(search_ids("tag:deep"), vec![2]);
+        assert_eq!(search_ids("deep"), vec![1, 2]);
+        
+        let results = search.search("writing", 10, SearchSort::Relevance).unwrap();
+        assert_eq!(results[0].tags, vec!["deep work", "writing"]);
+    }
+    
+    #[test]
+    fn test_empty_query_is_an_error() {
+        let (search, _dir) = setup();
+        assert!(search.search("", 10, SearchSort::Relevance).is_err());
+        assert!(search.search("  ()  ", 10, SearchSort::Relevance).is_err());
+    }
+    
+    #[test]
+    fn test_search_in_date_matches_exact_day() {
+        let (search, _dir) = setup();
+        
+        search.index_time_blocks(&[
+            (block(1, "2025-06-01", "Roadmap review"), "quarterly roadmap".to_string()),
+            (block(2, "2025-06-02", "Roadmap follow-up"), "roadmap actions".to_string()),
+            (block(3, "2025-06-01", "Lunch"), String::new()),
+        ]).unwrap();
+        
+        let results = search.search_in_date("2025-06-01", "roadmap", 10).unwrap();
+        assert_eq!(results.iter().map(|r| r.id).collect::<Vec<_>>(), vec![1]);
+        assert_eq!(search.search_in_date("2025-06-01", "", 10).unwrap().len(), 2);
+        // Partial dates don't match
+        assert!(search.search_in_date("2025-06", "roadmap", 10).unwrap().is_empty());
+        
+        // Re-indexing replaces the block's document instead of duplicating it
+        search.index_time_block(&block(1, "2025-06-01", "Roadmap review"), "updated roadmap").unwrap();
+        assert_eq!(search.search_in_date("2025-06-01", "roadmap", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_suggest_corrects_misspelled_words() {
+        let (search, _dir) = setup();
+        search.index_time_blocks(&[
+            (block(1, "2025-06-01", "Meeting with design"), "Agenda for the launch".to_string()),
+            (block(2, "2025-06-02", "Meeting prep"), String::new()),
+            (block(3, "2025-06-03", "Melting pot lunch"), String::new()),
+        ]).unwrap();
+        
+        let suggestions = search.suggest("meetnig agenda", 5).unwrap();
+        assert_eq!(suggestions, vec!["meeting agenda".to_string()]);
+        
+        // Known words need no correction
+        assert!(search.suggest("launch", 5).unwrap().is_empty());
+        assert_eq!(edit_distance("meting", "meeting"), 1);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn test_attachment_file_name_is_searchable() {
+        let (search, _dir) = setup();
+        let planning = block(3, "2025-06-03", "Planning");
+        search.index_time_block(&planning, "").unwrap();
+        search.index_attachments(&[AttachmentDocument {
+            attachment_id: 7,
+            time_block: planning.clone(),
+            file_name: "budget-q3.xlsx".to_string(),
+            text: String::new(),
+        }]).unwrap();
+        
+        assert_eq!(ids(search.search("budget-q3.xlsx", 10, SearchSort::Relevance).unwrap()), vec![3]);
+        assert_eq!(ids(search.search("q3", 10, SearchSort::Relevance).unwrap()), vec![3]);
+        assert_eq!(ids(search.search("file_name:budget", 10, SearchSort::Relevance).unwrap()), vec![3]);
+        
+        search.delete_attachment(7).unwrap();
+        assert!(search.search("budget", 10, SearchSort::Relevance).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_note_sections_are_found_under_their_block() {
+        let (search, _dir) = setup();
+        let standup = block(4, "2025-06-04", "Standup");
+        search.index_time_block(&standup, "daily sync").unwrap();
+        search.index_note_sections(&[NoteSectionDocument {
+            section_id: 1,
+            time_block: standup.clone(),
+            section_name: "Action items".to_string(),
+            content: "email the vendor".to_string(),
+        }]).unwrap();
+        
+        let results = search.search("vendor", 10, SearchSort::Relevance).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].id, 4);
+        assert_eq!(results[0].section.as_deref(), Some("Action items"));
+        assert_eq!(ids(search.search("section:action AND email", 10, SearchSort::Relevance).unwrap()), vec![4]);
+        assert_eq!(search.search("daily", 10, SearchSort::Relevance).unwrap()[0].section, None);
+        
+        // Deleting the block takes its sections with it
+        search.delete_time_block(4).unwrap();
+        assert!(search.search("vendor", 10, SearchSort::Relevance).unwrap().is_empty());
+    }
+    
+    #[test]
+    fn test_highlight_fragments() {
+        let (search, _dir) = setup();
+        let filler = "unrelated words about other things ".repeat(10);
+        let notes = format!("Budget draft is ready. {}Review the budget with finance. {}Final budget due Friday.", filler, filler);
+        search.index_time_block(&block(1, "2025-06-01", "Planning"), &notes).unwrap();
+        
+        let results = search.search("budget", 10, SearchSort::Relevance).unwrap();
+        assert_eq!(results[0].highlights.len(), 1);
+        assert!(results[0].highlights[0].contains("<b>budget</b>") || results[0].highlights[0].contains("<b>Budget</b>"));
+        
+        let options = HighlightOptions::new(Some(3), Some(40));
+        let results = search.search_with_highlights("budget", 10, SearchSort::Relevance, options).unwrap();
+        assert_eq!(results[0].highlights.len(), 3);
+        assert!(results[0].highlights.iter().all(|fragment| fragment.contains("<b>")));
+        
+        let off = HighlightOptions::new(Some(0), None);
+        assert!(search.search_with_highlights("budget", 10, SearchSort::Relevance, off).unwrap()[0].highlights.is_empty());
+        assert_eq!(HighlightOptions::new(Some(100), Some(1)), HighlightOptions { fragments: MAX_HIGHLIGHT_FRAGMENTS, max_chars: MIN_HIGHLIGHT_CHARS });
+    }
+    
+    #[test]
+    fn test_search_by_tag() {
+        let (search, _dir) = setup();
+        let mut project = block(1, "2025-06-01", "Kickoff meeting");
+        project.tags = vec!["project x".to_string()];
+        let mut other = block(2, "2025-06-01", "Team meeting");
+        other.tags = vec!["work".to_string()];
+        search.index_time_blocks(&[
+            (project, "work plan for project x".to_string()),
+            (other, String::new()),
+            (block(3, "2025-06-02", "Lunch"), String::new()),
+        ]).unwrap();
+        
+        assert_eq!(ids(search.search_by_tag("work", "meeting", 10).unwrap()), vec![2]);
+        assert_eq!(ids(search.search_by_tag("project x", "meeting", 10).unwrap()), vec![1]);
+        assert_eq!(ids(search.search_by_tag("project x", "", 10).unwrap()), vec![1]);
+        assert!(search.search_by_tag("work", "lunch", 10).unwrap().is_empty());
+    }
+}
@@ -1,8 +1,15 @@
 use std::fs;
-use std::path::PathBuf;
-use anyhow::Result;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
+use anyhow::{anyhow, Result};
+use ring::digest::{digest, Context, SHA256};
 use crate::models::TimeBlock;
 
+// Hex-encoded SHA-256 of file contents, used to deduplicate attachments
+pub fn content_hash(file_data: &[u8]) -> String {
+    hex::encode(digest(&SHA256, file_data).as_ref())
+}
+
 pub struct FileService {
     data_dir: PathBuf,
     notes_dir: PathBuf,
@@ -25,25 +32,83 @@ impl FileService {
         })
     }
     
+    // Resolve a relative path from the frontend or database, rejecting anything that
+    // could land outside the data directory ("..", absolute paths, escaping symlinks)
+    fn resolve(&self, relative: &str) -> Result<PathBuf> {
+        let path = Path::new(relative);
+        if path.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir)) {
+            return Err(anyhow!("Invalid path: {}", relative));
+        }
+        
+        let full_path = self.data_dir.join(path);
+        if full_path.exists() && !full_path.canonicalize()?.starts_with(self.data_dir.canonicalize()?) {
+            return Err(anyhow!("Invalid path: {}", relative));
+        }
+        Ok(full_path)
+    }
+    
+    // Relative notes path for a block: "notes/{date}/{start:04}-{id}.md". Blocks without
+    // an id yet get a unique temporary name, renamed by `rename_notes` once saved.
+    fn notes_path(time_block: &TimeBlock) -> String {
+        let suffix = match time_block.id {
+            Some(id) => id.to_string(),
+            None => format!("new-{}", uuid::Uuid::new_v4()),
+        };
+        format!("notes/{}/{:04}-{}.md", time_block.date, time_block.start_minutes, suffix)
+    }
+    
     pub fn save_notes(&self, time_block: &TimeBlock, content: &str) -> Result<String> {
         let date_dir = self.notes_dir.join(&time_block.date);
         fs::create_dir_all(&date_dir)?;
         
-        let filename = if let Some(id) = time_block.id {
-            format!("{:04}-{}.md", time_block.start_minutes, id)
-        } else {
-            format!("{:04}-new.md", time_block.start_minutes)
-        };
+        // Return relative path from data directory
+        let notes_file = Self::notes_path(time_block);
+        fs::write(self.resolve(&notes_file)?, content)?;
+        Ok(notes_file)
+    }
+    
+    // Move an existing notes file to the block's current name, e.g. after it got an id
+    // or its start time or date changed. Returns the (possibly unchanged) relative path.
+    pub fn rename_notes(&self, notes_file: &str, time_block: &TimeBlock) -> Result<String> {
+        if time_block.id.is_none() {
+            return Ok(notes_file.to_string());
+        }
         
-        let file_path = date_dir.join(&filename);
-        fs::write(&file_path, content)?;
+        let current = self.resolve(notes_file)?;
+        let target = Self::notes_path(time_block);
+        if target == notes_file || !current.exists() {
+            return Ok(target);
+        }
         
-        // Return relative path from data directory
-        Ok(format!("notes/{}/{}", time_block.date, filename))
+        fs::create_dir_all(self.notes_dir.join(&time_block.date))?;
+        fs::rename(current, self.resolve(&target)?)?;
+        Ok(target)
+    }
+    
+    // Write a named notes section, to `existing` if it already has a file. New sections
+    // go next to the block's notes as "{start:04}-{id}-{slug}.md", suffixed if taken.
+    pub fn save_note_section(&self, time_block: &TimeBlock, section: &str, existing: Option<&str>, content: &str) -> Result<String> {
+        let notes_file = match existing {
+            Some(existing) => existing.to_string(),
+            None => {
+                let id = time_block.id.ok_or_else(|| anyhow!("Notes sections need a saved block"))?;
+                fs::create_dir_all(self.notes_dir.join(&time_block.date))?;
+                let base = format!("notes/{}/{:04}-{}-{}", time_block.date, time_block.start_minutes, id, section_slug(section));
+                let mut candidate = format!("{}.md", base);
+                let mut counter = 2;
+                while self.resolve(&candidate)?.exists() {
+                    candidate = format!("{}-{}.md", base, counter);
+                    counter += 1;
+                }
+                candidate
+            }
+        };
+        fs::write(self.resolve(&notes_file)?, content)?;
+        Ok(notes_file)
     }
     
     pub fn load_notes(&self, notes_file: &str) -> Result<String> {
-        let file_path = self.data_dir.join(notes_file);
+        let file_path = self.resolve(notes_file)?;
         match fs::read_to_string(&file_path) {
             Ok(content) => Ok(content),
             Err(_) => Ok(String::new()), // Return empty if file doesn't exist
@@ -51,36 +116,470 @@ impl FileService {
     }
     
     pub fn delete_notes(&self, notes_file: &str) -> Result<()> {
-        let file_path = self.data_dir.join(notes_file);
+        let file_path = self.resolve(notes_file)?;
         if file_path.exists() {
             fs::remove_file(file_path)?;
         }
         Ok(())
     }
     
+    // Write a new attachment as "attachments/{date}/{time_block_id}_{filename}", picking a
+    // free name if that's taken since deduplicated rows may share the existing file
     pub fn save_attachment(&self, time_block_id: i64, date: &str, file_data: &[u8], filename: &str) -> Result<String> {
-        let date_dir = self.attachments_dir.join(date);
-        fs::create_dir_all(&date_dir)?;
+        let dir = self.attachment_dir(date)?;
+        check_file_name(filename)?;
+        let relative = self.free_attachment_path(Some(&dir), time_block_id, filename, "")?;
         
-        // Create unique filename with time_block_id prefix
-        let safe_filename = format!("{}_{}", time_block_id, filename);
-        let file_path = date_dir.join(&safe_filename);
+        let mut file = fs::OpenOptions::new().write(true).create_new(true).open(self.resolve(&relative)?)?;
+        if let Err(e) = file.write_all(file_data) {
+            drop(file);
+            let _ = fs::remove_file(self.resolve(&relative)?);
+            return Err(e.into());
+        }
+        Ok(relative)
+    }
+    
+    // Relative "attachments/{date}" directory for a date from the frontend, created if missing
+    fn attachment_dir(&self, date: &str) -> Result<String> {
+        let dir = format!("attachments/{}", crate::commands::normalize_date(date)?);
+        fs::create_dir_all(self.resolve(&dir)?)?;
+        Ok(dir)
+    }
+    
+    // Copy a file from anywhere on disk into attachments/{date}, hashing it on the way
+    // so it never has to be held in memory. Returns the relative path and content hash.
+    // The copy always gets a name of its own, so discarding it as a duplicate is safe.
+    pub fn copy_attachment(&self, time_block_id: i64, date: &str, source: &Path, filename: &str) -> Result<(String, String)> {
+        let dir = self.attachment_dir(date)?;
+        check_file_name(filename)?;
+        let mut reader = BufReader::new(fs::File::open(source)?);
         
-        fs::write(&file_path, file_data)?;
+        let relative = self.free_attachment_path(Some(&dir), time_block_id, filename, "")?;
+        let target = self.resolve(&relative)?;
+        let mut writer = BufWriter::new(fs::OpenOptions::new().write(true).create_new(true).open(&target)?);
+        let mut hasher = Context::new(&SHA256);
+        let mut buffer = [0u8; 64 * 1024];
+        let copied = (|| -> Result<()> {
+            loop {
+                let read = reader.read(&mut buffer)?;
+                if read == 0 {
+                    break;
+                }
+                hasher.update(&buffer[..read]);
+                writer.write_all(&buffer[..read])?;
+            }
+            writer.flush()?;
+            Ok(())
+        })();
         
-        // Return relative path from data directory
-        Ok(format!("attachments/{}/{}", date, safe_filename))
+        if let Err(e) = copied {
+            drop(writer);
+            let _ = fs::remove_file(&target);
+            return Err(e);
+        }
+        Ok((relative, hex::encode(hasher.finish().as_ref())))
+    }
+    
+    // Store a generated thumbnail next to its attachment as "{file}.thumb.png", so it
+    // keeps the "{time_block_id}_" prefix and moves with the block's other files
+    pub fn save_thumbnail(&self, file_path: &str, png: &[u8]) -> Result<String> {
+        let thumbnail_path = format!("{}.thumb.png", file_path);
+        fs::write(self.resolve(&thumbnail_path)?, png)?;
+        Ok(thumbnail_path)
+    }
+    
+    pub fn load_attachment(&self, file_path: &str) -> Result<Vec<u8>> {
+        Ok(fs::read(self.resolve(file_path)?)?)
     }
     
     pub fn delete_attachment(&self, file_path: &str) -> Result<()> {
-        let full_path = self.data_dir.join(file_path);
+        let full_path = self.resolve(file_path)?;
         if full_path.exists() {
             fs::remove_file(full_path)?;
         }
         Ok(())
     }
     
+    // Rename a stored attachment to "{time_block_id}_{new_name}" in the same directory,
+    // suffixing " (2)", " (3)", ... on collision. Returns the new relative path.
+    pub fn rename_attachment(&self, file_path: &str, time_block_id: i64, new_name: &str) -> Result<String> {
+        check_file_name(new_name)?;
+        let dir = file_path.rsplit_once('/').map(|(dir, _)| dir);
+        let new_path = self.free_attachment_path(dir, time_block_id, new_name, file_path)?;
+        self.move_attachment(file_path, &new_path)?;
+        Ok(new_path)
+    }
+    
+    // Move an attachment to another block and date, under that block's "{id}_" prefix.
+    // Returns the new relative path, suffixed like rename_attachment if the name is taken.
+    pub fn reassign_attachment(&self, file_path: &str, time_block_id: i64, date: &str) -> Result<String> {
+        let name = file_path.rsplit('/').next().unwrap_or(file_path);
+        let original = match name.split_once('_') {
+            Some((prefix, rest)) if prefix.parse::<i64>().is_ok() => rest,
+            _ => name,
+        };
+        
+        let dir = self.attachment_dir(date)?;
+        let new_path = self.free_attachment_path(Some(&dir), time_block_id, original, file_path)?;
+        self.move_attachment(file_path, &new_path)?;
+        Ok(new_path)
+    }
+    
+    // "{dir}/{id}_{name}", or "{id}_{stem} (2).{ext}" and so on if another file has it
+    fn free_attachment_path(&self, dir: Option<&str>, time_block_id: i64, name: &str, current: &str) -> Result<String> {
+        let (stem, extension) = match name.rsplit_once('.') {
+            Some((stem, extension)) if !stem.is_empty() => (stem, Some(extension)),
+            _ => (name, None),
+        };
+        
+        let mut copy = 1;
+        loop {
+            let file_name = match (copy, extension) {
+                (1, _) => format!("{}_{}", time_block_id, name),
+                (n, Some(extension)) => format!("{}_{} ({}).{}", time_block_id, stem, n, extension),
+                (n, None) => format!("{}_{} ({})", time_block_id, stem, n),
+            };
+            let candidate = match dir {
+                Some(dir) => format!("{}/{}", dir, file_name),
+                None => file_name,
+            };
+            if candidate == current || !self.resolve(&candidate)?.exists() {
+                return Ok(candidate);
+            }
+            copy += 1;
+        }
+    }
+    
+    pub fn move_attachment(&self, from: &str, to: &str) -> Result<()> {
+        if from != to {
+            fs::rename(self.resolve(from)?, self.resolve(to)?)?;
+        }
+        Ok(())
+    }
+    
+    // Move a block's attachment files ("{time_block_id}_*") from attachments/{old_date}
+    // to attachments/{new_date}. Returns the (old, new) relative path of each file moved;
+    // nothing is moved if any name is already taken in the new directory.
+    pub fn move_attachments(&self, time_block_id: i64, old_date: &str, new_date: &str) -> Result<Vec<(String, String)>> {
+        let old_dir = self.resolve(&format!("attachments/{}", old_date))?;
+        let new_dir = self.resolve(&format!("attachments/{}", new_date))?;
+        if old_date == new_date || !old_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+        
+        let prefix = format!("{}_", time_block_id);
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&old_dir)? {
+            let name = entry?.file_name().to_string_lossy().into_owned();
+            if name.starts_with(&prefix) {
+                names.push(name);
+            }
+        }
+        if let Some(taken) = names.iter().find(|name| new_dir.join(name).exists()) {
+            return Err(anyhow!("attachments/{}/{} already exists", new_date, taken));
+        }
+        
+        fs::create_dir_all(&new_dir)?;
+        let mut moved = Vec::new();
+        for name in names {
+            let from = format!("attachments/{}/{}", old_date, name);
+            let to = format!("attachments/{}/{}", new_date, name);
+            if let Err(e) = fs::rename(old_dir.join(&name), new_dir.join(&name)) {
+                // Put back what already moved so the block's files stay together
+                for (from, to) in &moved {
+                    let _ = self.move_attachment(to, from);
+                }
+                return Err(e.into());
+            }
+            moved.push((from, to));
+        }
+        Ok(moved)
+    }
+    
+    // Absolute path of a stored attachment, for the frontend's asset protocol.
+    // Rejects paths that resolve outside the attachments directory.
+    pub fn attachment_path(&self, file_path: &str) -> Result<PathBuf> {
+        let full_path = self.resolve(file_path)?.canonicalize()?;
+        if !full_path.starts_with(self.attachments_dir.canonicalize()?) {
+            return Err(anyhow!("Not an attachment: {}", file_path));
+        }
+        Ok(full_path)
+    }
+    
+    // Whether a stored relative path still points at a file. Invalid paths count as missing.
+    pub fn file_exists(&self, relative: &str) -> bool {
+        self.resolve(relative).is_ok_and(|path| path.is_file())
+    }
+    
     pub fn get_data_dir(&self) -> &PathBuf {
         &self.data_dir
     }
+    
+    // Total bytes of the files under a data directory subfolder, e.g. "notes".
+    // A missing folder counts as empty.
+    pub fn dir_size(&self, relative: &str) -> Result<u64> {
+        let path = self.resolve(relative)?;
+        if !path.exists() {
+            return Ok(0);
+        }
+        dir_size(&path)
+    }
+}
+
+// A bare file name with no directory parts, as stored after the "{id}_" prefix
+fn check_file_name(name: &str) -> Result<()> {
+    if name.trim().is_empty() || name.contains(['/', '\\']) || name == "." || name == ".." {
+        return Err(anyhow!("Invalid file name: {}", name));
+    }
+    Ok(())
+}
+
+fn dir_size(path: &Path) -> Result<u64> {
+    let mut total = 0;
+    for entry in fs::read_dir(path)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            total += dir_size(&entry.path())?;
+        } else if file_type.is_file() {
+            total += entry.metadata()?.len();
+        }
+    }
+    Ok(total)
+}
+
+// Lowercase words joined by '-' for a file name: "Follow ups!" -> "follow-ups"
+fn section_slug(section: &str) -> String {
+    let slug = section.to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    if slug.is_empty() { "section".to_string() } else { slug }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_dir_size_counts_nested_files() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        
+        files.save_notes(&block(Some(1), 540), "12345").unwrap();
+        
+        assert_eq!(files.dir_size("notes").unwrap(), 5);
+        assert_eq!(files.dir_size("missing").unwrap(), 0);
+        assert!(files.dir_size("../elsewhere").is_err());
+    }
+    
+    #[test]
+    fn test_copy_attachment_from_path() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().join("data")).unwrap();
+        let source = dir.path().join("recording.m4a");
+        fs::write(&source, b"audio bytes").unwrap();
+        
+        let (path, hash) = files.copy_attachment(4, "2025-06-01", &source, "recording.m4a").unwrap();
+        assert_eq!(path, "attachments/2025-06-01/4_recording.m4a");
+        assert_eq!(hash, content_hash(b"audio bytes"));
+        assert_eq!(files.load_attachment(&path).unwrap(), b"audio bytes");
+        
+        assert!(files.copy_attachment(4, "2025-06-01", &dir.path().join("missing"), "missing").is_err());
+        
+        // A second copy under the same name leaves the first (maybe shared) file alone
+        fs::write(&source, b"other audio").unwrap();
+        let (second, _) = files.copy_attachment(4, "2025-06-01", &source, "recording.m4a").unwrap();
+        assert_eq!(second, "attachments/2025-06-01/4_recording (2).m4a");
+        assert_eq!(files.load_attachment(&path).unwrap(), b"audio bytes");
+    }
+    
+    #[test]
+    fn test_save_attachment_never_overwrites() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        
+        let first = files.save_attachment(2, "2025/6/1", b"first", "scan.png").unwrap();
+        assert_eq!(first, "attachments/2025-06-01/2_scan.png");
+        let second = files.save_attachment(2, "2025-06-01", b"second", "scan.png").unwrap();
+        assert_eq!(second, "attachments/2025-06-01/2_scan (2).png");
+        assert_eq!(files.load_attachment(&first).unwrap(), b"first");
+        
+        assert!(files.save_attachment(2, "../x", b"data", "scan.png").is_err());
+        assert!(files.save_attachment(2, "2025-06-01", b"data", "../scan.png").is_err());
+        assert!(!dir.path().join("x").exists());
+    }
+        
+    #[test]
+    fn test_move_attachments_to_new_date() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        
+        let mine = files.save_attachment(1, "2025-06-01", b"mine", "a.png").unwrap();
+        let other = files.save_attachment(11, "2025-06-01", b"other", "b.png").unwrap();
+        
+        let moved = files.move_attachments(1, "2025-06-01", "2025-06-03").unwrap();
+        assert_eq!(moved, vec![(mine.clone(), "attachments/2025-06-03/1_a.png".to_string())]);
+        assert_eq!(files.load_attachment("attachments/2025-06-03/1_a.png").unwrap(), b"mine");
+        assert!(files.load_attachment(&mine).is_err());
+        assert_eq!(files.load_attachment(&other).unwrap(), b"other");
+        
+        // Name clashes leave everything in place
+        files.save_attachment(1, "2025-06-01", b"new", "a.png").unwrap();
+        assert!(files.move_attachments(1, "2025-06-01", "2025-06-03").is_err());
+        assert_eq!(files.load_attachment(&mine).unwrap(), b"new");
+        
+        assert!(files.move_attachments(1, "2025-06-01", "../escape").is_err());
+    }
+    
+    #[test]
+    fn test_attachment_path_stays_inside_attachments_dir() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        
+        let stored = files.save_attachment(1, "2025-06-01", b"data", "a.pdf").unwrap();
+        let path = files.attachment_path(&stored).unwrap();
+        assert!(path.is_absolute());
+        assert_eq!(fs::read(path).unwrap(), b"data");
+        
+        fs::write(dir.path().join("timeblock.db"), b"").unwrap();
+        assert!(files.attachment_path("timeblock.db").is_err());
+        assert!(files.attachment_path("attachments/../timeblock.db").is_err());
+        assert!(files.attachment_path("attachments/missing.pdf").is_err());
+    }
+    
+    fn block(id: Option<i64>, start_minutes: i32) -> TimeBlock {
+        TimeBlock {
+            id,
+            date: "2025-06-01".to_string(),
+            start_minutes,
+            duration_minutes: 30,
+            title: "Focus".to_string(),
+            notes_file: None,
+            color: "#3b82f6".to_string(),
+            tags: vec![],
+            created_at: None,
+            updated_at: None,
+            reminder_minutes: None,
+            completed: false,
+            actual_duration_minutes: None,
+            notes_content: None,
+            is_placeholder: false,
+        }
+    }
+    
+    #[test]
+    fn test_same_start_blocks_keep_separate_notes() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        
+        let first = files.save_notes(&block(None, 540), "first").unwrap();
+        let second = files.save_notes(&block(None, 540), "second").unwrap();
+        assert_ne!(first, second);
+        assert_eq!(files.load_notes(&first).unwrap(), "first");
+        assert_eq!(files.load_notes(&second).unwrap(), "second");
+        
+        // Once saved, the temporary name is replaced by the id-based one
+        let renamed = files.rename_notes(&first, &block(Some(7), 540)).unwrap();
+        assert_eq!(renamed, "notes/2025-06-01/0540-7.md");
+        assert_eq!(files.load_notes(&renamed).unwrap(), "first");
+        assert!(!dir.path().join(&first).exists());
+    }
+    
+    #[test]
+    fn test_moving_a_block_renames_its_notes() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        
+        let original = files.save_notes(&block(Some(3), 540), "notes").unwrap();
+        let moved = files.rename_notes(&original, &block(Some(3), 600)).unwrap();
+        
+        assert_eq!(moved, "notes/2025-06-01/0600-3.md");
+        assert_eq!(files.load_notes(&moved).unwrap(), "notes");
+        assert!(!dir.path().join(&original).exists());
+    }
+    
+    #[test]
+    fn test_note_sections_get_their_own_files() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        
+        let agenda = files.save_note_section(&block(Some(3), 540), "Agenda!", None, "one").unwrap();
+        assert_eq!(agenda, "notes/2025-06-01/0540-3-agenda.md");
+        // A different name with the same slug doesn't overwrite it
+        let other = files.save_note_section(&block(Some(3), 540), "agenda", None, "two").unwrap();
+        assert_eq!(other, "notes/2025-06-01/0540-3-agenda-2.md");
+        
+        files.save_note_section(&block(Some(3), 540), "Agenda!", Some(&agenda), "updated").unwrap();
+        assert_eq!(files.load_notes(&agenda).unwrap(), "updated");
+        assert!(files.save_note_section(&block(None, 540), "agenda", None, "x").is_err());
+    }
+    
+    #[test]
+    fn test_paths_outside_data_dir_are_rejected() {
+        let root = tempdir().unwrap();
+        let data_dir = root.path().join("data");
+        let files = FileService::new(data_dir.clone()).unwrap();
+        
+        let secret = root.path().join("secret.txt");
+        fs::write(&secret, "secret").unwrap();
+        
+        for path in ["../secret.txt", "notes/../../secret.txt", "attachments/2025-06-01/../../../secret.txt"] {
+            assert!(files.load_notes(path).is_err(), "{}", path);
+            assert!(files.load_attachment(path).is_err(), "{}", path);
+            assert!(files.delete_notes(path).is_err(), "{}", path);
+            assert!(files.delete_attachment(path).is_err(), "{}", path);
+        }
+        assert!(files.load_notes(secret.to_str().unwrap()).is_err());
+        assert!(files.delete_attachment(secret.to_str().unwrap()).is_err());
+        assert!(secret.exists());
+        
+        // Ordinary relative paths still work
+        let stored = files.save_attachment(1, "2025-06-01", b"data", "a.pdf").unwrap();
+        assert_eq!(files.load_attachment(&stored).unwrap(), b"data");
+        assert_eq!(files.load_notes("notes/2025-06-01/missing.md").unwrap(), "");
+    }
+    
+    #[test]
+    fn test_rename_attachment_suffixes_collisions() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        
+        let first = files.save_attachment(1, "2025-06-01", b"one", "a.pdf").unwrap();
+        let second = files.save_attachment(1, "2025-06-01", b"two", "b.pdf").unwrap();
+        files.save_attachment(1, "2025-06-01", b"three", "report.pdf").unwrap();
+        
+        let renamed = files.rename_attachment(&first, 1, "report.pdf").unwrap();
+        assert_eq!(renamed, "attachments/2025-06-01/1_report (2).pdf");
+        assert_eq!(fs::read(dir.path().join(&renamed)).unwrap(), b"one");
+        assert!(!dir.path().join(&first).exists());
+        
+        let renamed = files.rename_attachment(&second, 1, "report.pdf").unwrap();
+        assert_eq!(renamed, "attachments/2025-06-01/1_report (3).pdf");
+        
+        // Renaming to the current name is a no-op
+        assert_eq!(files.rename_attachment(&renamed, 1, "report (3).pdf").unwrap(), renamed);
+        assert!(files.rename_attachment(&renamed, 1, "../escape.pdf").is_err());
+    }
+    
+    #[test]
+    fn test_reassign_attachment_to_another_block() {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        
+        let original = files.save_attachment(1, "2025-06-01", b"one", "photo_1.png").unwrap();
+        files.save_attachment(2, "2025-06-02", b"two", "photo_1.png").unwrap();
+        
+        let moved = files.reassign_attachment(&original, 2, "2025-06-02").unwrap();
+        assert_eq!(moved, "attachments/2025-06-02/2_photo_1 (2).png");
+        assert_eq!(fs::read(dir.path().join(&moved)).unwrap(), b"one");
+        assert!(!dir.path().join(&original).exists());
+        
+        // A date that isn't one never becomes a directory
+        assert!(files.reassign_attachment(&moved, 2, "../outside").is_err());
+        assert!(!dir.path().join("outside").exists());
+        assert!(dir.path().join(&moved).exists());
+    }
 }
\ No newline at end of file
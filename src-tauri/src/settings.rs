@@ -0,0 +1,184 @@
+use rusqlite::Connection;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::error::{AppError, AppResult};
+use crate::models::{parse_hex_color, PaletteColor};
+
+// Settings carried over in a profile. The encryption key location is left out:
+// it describes this machine, not the user's preferences.
+const PORTABLE_SETTINGS: &[&str] = &[
+    "default_time_interval",
+    "snap_minutes",
+    "available_intervals",
+    "duration_presets",
+    "work_hours_start",
+    "work_hours_end",
+    "week_start",
+    "tag_colors",
+    "event_colors",
+    "time_format",
+    "default_block_title",
+    "max_attachment_size_mb",
+    "calendar_sync_interval",
+    "calendar_max_retries",
+    "calendar_sync_parallelism",
+    "calendar_event_retention_days",
+    "color_palettes",
+];
+
+// Portable settings only exported when asked for, e.g. credentials. None yet.
+const SENSITIVE_SETTINGS: &[&str] = &[];
+
+const MINUTES_PER_DAY: i64 = 24 * 60;
+
+// Check a value before it's stored. Keys without rules (e.g. frontend preferences) pass.
+pub fn validate_setting(key: &str, value: &str) -> AppResult<()> {
+    let value = value.trim();
+    match key {
+        "snap_minutes" => {
+            if !value.parse().is_ok_and(crate::scheduling::is_valid_snap) {
+                return Err(AppError::Validation(format!("snap_minutes must divide evenly into 60, got '{}'", value)));
+            }
+        }
+        "default_time_interval" => integer_in(key, value, 1, MINUTES_PER_DAY)?,
+        "work_hours_start" | "work_hours_end" => integer_in(key, value, 0, MINUTES_PER_DAY)?,
+        "max_attachment_size_mb" => integer_in(key, value, 0, 10 * 1024)?,  // 0 turns the limit off
+        "calendar_sync_interval" => integer_in(key, value, 1, MINUTES_PER_DAY)?,
+        "calendar_max_retries" => integer_in(key, value, 0, 20)?,
+        "calendar_sync_parallelism" => integer_in(key, value, 1, 16)?,
+        "calendar_event_retention_days" => integer_in(key, value, 0, 3650)?,  // 0 keeps every event
+        "available_intervals" | "duration_presets" => {
+            let minutes: Vec<i64> = serde_json::from_str(value)
+                .map_err(|_| AppError::Validation(format!("{} must be a JSON array of minutes, got '{}'", key, value)))?;
+            if minutes.is_empty() || minutes.iter().any(|m| !(1..=MINUTES_PER_DAY).contains(m)) {
+                return Err(AppError::Validation(format!("{} must list minutes between 1 and {}", key, MINUTES_PER_DAY)));
+            }
+        }
+        "week_start" => one_of(key, value, &["monday", "sunday"])?,
+        "time_format" => one_of(key, value, &["12h", "24h"])?,
+        "encryption_key_storage" => one_of(key, value, &["file", "keychain"])?,
+        "tag_colors" => {
+            crate::commands::parse_tag_colors(value)?;
+        }
+        "event_colors" => {
+            crate::commands::parse_event_colors(value)?;
+        }
+        "color_palettes" => {
+            let palette: Vec<PaletteColor> = serde_json::from_str(value)
+                .map_err(|e| AppError::Validation(format!("color_palettes must be a list of named colors: {}", e)))?;
+            if let Some(color) = palette.iter().find(|color| parse_hex_color(&color.hex).is_none()) {
+                return Err(AppError::Validation(format!("Invalid hex color for '{}': {}", color.name, color.hex)));
+            }
+        }
+        "default_block_title" => {
+            if value.is_empty() {
+                return Err(AppError::Validation("default_block_title can't be empty".to_string()));
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+fn integer_in(key: &str, value: &str, min: i64, max: i64) -> AppResult<()> {
+    match value.parse::<i64>() {
+        Ok(number) if (min..=max).contains(&number) => Ok(()),
+        _ => Err(AppError::Validation(format!("{} must be a whole number from {} to {}, got '{}'", key, min, max, value))),
+    }
+}
+
+fn one_of(key: &str, value: &str, allowed: &[&str]) -> AppResult<()> {
+    if allowed.contains(&value) {
+        Ok(())
+    } else {
+        Err(AppError::Validation(format!("{} must be one of {}, got '{}'", key, allowed.join(", "), value)))
+    }
+}
+
+// The portable settings as a JSON object of key -> stored value, sorted by key
+pub fn export_profile(conn: &Connection, include_sensitive: bool) -> AppResult<String> {
+    let mut stmt = conn.prepare("SELECT key, value FROM settings")?;
+    let settings: BTreeMap<String, String> = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<rusqlite::Result<Vec<(String, String)>>>()?
+        .into_iter()
+        .filter(|(key, _)| PORTABLE_SETTINGS.contains(&key.as_str()))
+        .filter(|(key, _)| include_sensitive || !SENSITIVE_SETTINGS.contains(&key.as_str()))
+        .collect();
+
+    Ok(serde_json::to_string_pretty(&settings)?)
+}
+
+// Apply a profile from export_profile. Every entry is checked first, so one bad or
+// unknown key rejects the whole profile and nothing is changed. Returns how many
+// settings were applied.
+pub fn import_profile(conn: &mut Connection, json: &str) -> AppResult<usize> {
+    let profile: serde_json::Map<String, Value> = serde_json::from_str(json)
+        .map_err(|e| AppError::Validation(format!("Settings profile must be a JSON object: {}", e)))?;
+
+    let mut settings = Vec::with_capacity(profile.len());
+    for (key, value) in profile {
+        if !PORTABLE_SETTINGS.contains(&key.as_str()) {
+            return Err(AppError::Validation(format!("Unknown setting in profile: {}", key)));
+        }
+        // Values are stored as text; accept hand-written numbers and arrays too
+        let value = match value {
+            Value::String(value) => value,
+            other => other.to_string(),
+        };
+        validate_setting(&key, &value)?;
+        settings.push((key, value));
+    }
+
+    let tx = conn.transaction()?;
+    for (key, value) in &settings {
+        tx.execute("INSERT OR REPLACE INTO settings (key, value) VALUES (?1, ?2)", (key, value))?;
+    }
+    tx.commit()?;
+
+    Ok(settings.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+        conn
+    }
+
+    #[test]
+    fn test_profile_round_trip() {
+        let mut conn = test_db();
+        conn.execute("UPDATE settings SET value = 'sunday' WHERE key = 'week_start'", []).unwrap();
+        conn.execute("INSERT INTO settings (key, value) VALUES ('sidebar_collapsed', 'true')", []).unwrap();
+
+        let profile = export_profile(&conn, false).unwrap();
+        let exported: BTreeMap<String, String> = serde_json::from_str(&profile).unwrap();
+        assert_eq!(exported["week_start"], "sunday");
+        assert!(!exported.contains_key("encryption_key_storage"));
+        assert!(!exported.contains_key("sidebar_collapsed"));
+
+        let mut other = test_db();
+        assert_eq!(import_profile(&mut other, &profile).unwrap(), exported.len());
+        assert_eq!(export_profile(&other, false).unwrap(), profile);
+
+        assert_eq!(import_profile(&mut other, r#"{"snap_minutes": 15, "available_intervals": [15, 30]}"#).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_import_rejects_invalid_profile_without_changes() {
+        let mut conn = test_db();
+        let before = export_profile(&conn, false).unwrap();
+
+        let error = import_profile(&mut conn, r#"{"week_start": "sunday", "snap_minutes": "7"}"#).unwrap_err();
+        assert_eq!(error.code(), "validation");
+        assert!(import_profile(&mut conn, r#"{"week_start": "sunday", "theme": "dark"}"#).unwrap_err().message().contains("theme"));
+        assert!(import_profile(&mut conn, r#"{"work_hours_end": "2000"}"#).is_err());
+        assert!(import_profile(&mut conn, "[1, 2]").is_err());
+
+        assert_eq!(export_profile(&conn, false).unwrap(), before);
+    }
+}
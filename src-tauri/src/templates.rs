@@ -0,0 +1,226 @@
+use chrono::{Datelike, NaiveDate};
+use rusqlite::{Connection, OptionalExtension};
+
+use crate::commands::{find_matching_block, normalize_date, write_time_block, MINUTES_PER_DAY, WEEKDAYS};
+use crate::error::{AppError, AppResult};
+use crate::models::{normalize_color, AppliedTemplate, TimeBlock, WeekTemplate, WeekTemplateBlock};
+use crate::services::FileService;
+
+fn validate(template: &WeekTemplate) -> AppResult<()> {
+    if template.name.trim().is_empty() {
+        return Err(AppError::Validation("Template name can't be empty".to_string()));
+    }
+    for block in &template.blocks {
+        if !WEEKDAYS.contains(&block.weekday.as_str()) {
+            return Err(AppError::Validation(format!("Weekday must be one of {}, got '{}'", WEEKDAYS.join(", "), block.weekday)));
+        }
+        if !(0..MINUTES_PER_DAY).contains(&block.start_minutes)
+            || block.duration_minutes <= 0
+            || block.start_minutes + block.duration_minutes > MINUTES_PER_DAY
+        {
+            return Err(AppError::Validation(format!("'{}' on {} doesn't fit in the day", block.title, block.weekday)));
+        }
+    }
+    Ok(())
+}
+
+// Create a template, or replace an existing one's name and blocks. Returns its id.
+pub fn save_template(conn: &mut Connection, template: &WeekTemplate) -> AppResult<i64> {
+    let mut template = template.clone();
+    for block in &mut template.blocks {
+        block.weekday = block.weekday.trim().to_lowercase();
+    }
+    validate(&template)?;
+
+    let tx = conn.transaction()?;
+    let id = match template.id {
+        Some(id) => {
+            if tx.execute("UPDATE week_templates SET name = ?1 WHERE id = ?2", (template.name.trim(), id))? == 0 {
+                return Err(AppError::NotFound(format!("Week template not found: {}", id)));
+            }
+            tx.execute("DELETE FROM week_template_blocks WHERE template_id = ?1", [id])?;
+            id
+        }
+        None => {
+            tx.execute("INSERT INTO week_templates (name) VALUES (?1)", [template.name.trim()])?;
+            tx.last_insert_rowid()
+        }
+    };
+    for block in &template.blocks {
+        tx.execute(
+            "INSERT INTO week_template_blocks (template_id, weekday, start_minutes, duration_minutes, title, color, tags, reminder_minutes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            (id, &block.weekday, block.start_minutes, block.duration_minutes, &block.title,
+             normalize_color(&block.color), serde_json::to_string(&block.tags)?, block.reminder_minutes),
+        )?;
+    }
+    tx.commit()?;
+    Ok(id)
+}
+
+fn template_blocks(conn: &Connection, template_id: i64) -> rusqlite::Result<Vec<WeekTemplateBlock>> {
+    let mut stmt = conn.prepare(
+        "SELECT weekday, start_minutes, duration_minutes, title, color, tags, reminder_minutes
+         FROM week_template_blocks WHERE template_id = ?1 ORDER BY start_minutes, id"
+    )?;
+    let blocks = stmt.query_map([template_id], |row| {
+        let tags: Option<String> = row.get(5)?;
+        Ok(WeekTemplateBlock {
+            weekday: row.get(0)?,
+            start_minutes: row.get(1)?,
+            duration_minutes: row.get(2)?,
+            title: row.get(3)?,
+            color: normalize_color(&row.get::<_, Option<String>>(4)?.unwrap_or_default()),
+            tags: tags.and_then(|tags| serde_json::from_str(&tags).ok()).unwrap_or_default(),
+            reminder_minutes: row.get(6)?,
+        })
+    })?.collect::<rusqlite::Result<Vec<_>>>();
+    blocks
+}
+
+pub fn load_template(conn: &Connection, id: i64) -> AppResult<WeekTemplate> {
+    let name: String = conn.query_row("SELECT name FROM week_templates WHERE id = ?1", [id], |row| row.get(0))
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("Week template not found: {}", id)))?;
+    Ok(WeekTemplate { id: Some(id), name, blocks: template_blocks(conn, id)? })
+}
+
+pub fn list_templates(conn: &Connection) -> AppResult<Vec<WeekTemplate>> {
+    let mut stmt = conn.prepare("SELECT id FROM week_templates ORDER BY name COLLATE NOCASE, id")?;
+    let ids = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<Vec<i64>>>()?;
+    ids.into_iter().map(|id| load_template(conn, id)).collect()
+}
+
+// Blocks are deleted explicitly rather than relying on ON DELETE CASCADE alone
+pub fn delete_template(conn: &Connection, id: i64) -> AppResult<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM week_template_blocks WHERE template_id = ?1", [id])?;
+    if tx.execute("DELETE FROM week_templates WHERE id = ?1", [id])? == 0 {
+        return Err(AppError::NotFound(format!("Week template not found: {}", id)));
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+pub struct TemplateApplication {
+    pub result: AppliedTemplate,
+    pub to_index: Vec<(TimeBlock, String)>,  // Created blocks, for the search index
+}
+
+// Create the template's blocks on each day of the seven starting at `week_start_date`,
+// matched by weekday name, so it works for weeks starting on any day. A block already
+// on the calendar with the same start time and title is skipped, so applying a
+// template twice doesn't double up.
+pub fn apply_template(conn: &Connection, files: &FileService, template_id: i64, week_start_date: &str) -> AppResult<TemplateApplication> {
+    let template = load_template(conn, template_id)?;
+    let week_start = NaiveDate::parse_from_str(&normalize_date(week_start_date)?, "%Y-%m-%d")
+        .map_err(|_| AppError::Validation(format!("Invalid date: {}", week_start_date)))?;
+
+    let tx = conn.unchecked_transaction()?;
+    let mut applied = AppliedTemplate::default();
+    let mut to_index = Vec::new();
+    for day in week_start.iter_days().take(7) {
+        let weekday = WEEKDAYS[day.weekday().num_days_from_monday() as usize];
+        let date = day.format("%Y-%m-%d").to_string();
+        for template_block in template.blocks.iter().filter(|block| block.weekday == weekday) {
+            let block = TimeBlock {
+                id: None,
+                date: date.clone(),
+                start_minutes: template_block.start_minutes,
+                duration_minutes: template_block.duration_minutes,
+                title: template_block.title.clone(),
+                notes_file: None,
+                color: template_block.color.clone(),
+                tags: template_block.tags.clone(),
+                created_at: None,
+                updated_at: None,
+                reminder_minutes: template_block.reminder_minutes,
+                completed: false,
+                actual_duration_minutes: None,
+                notes_content: None,
+                is_placeholder: false,
+            };
+            if find_matching_block(&tx, &block)?.is_some() {
+                applied.skipped += 1;
+                continue;
+            }
+            let saved = write_time_block(&tx, files, &block, None)?;
+            applied.ids.extend(saved.id);
+            to_index.push((saved, String::new()));
+        }
+    }
+    tx.commit()?;
+    Ok(TemplateApplication { result: applied, to_index })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+        conn
+    }
+
+    fn block(weekday: &str, start_minutes: i32, title: &str) -> WeekTemplateBlock {
+        WeekTemplateBlock {
+            weekday: weekday.to_string(),
+            start_minutes,
+            duration_minutes: 60,
+            title: title.to_string(),
+            color: String::new(),
+            tags: vec!["routine".to_string()],
+            reminder_minutes: None,
+        }
+    }
+
+    #[test]
+    fn test_apply_week_template_skips_existing_blocks() {
+        let mut conn = test_db();
+        let dir = tempfile::tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        let template = WeekTemplate {
+            id: None,
+            name: "Default week".to_string(),
+            blocks: vec![block("Monday", 540, "Plan the week"), block("wednesday", 600, "Deep work"), block("sunday", 1080, "Review")],
+        };
+        let id = save_template(&mut conn, &template).unwrap();
+        assert_eq!(load_template(&conn, id).unwrap().blocks[0].weekday, "monday");
+
+        // 2025-06-01 is a Sunday, so the week runs Sunday to Saturday
+        let applied = apply_template(&conn, &files, id, "2025-6-1").unwrap();
+        assert_eq!((applied.result.ids.len(), applied.result.skipped), (3, 0));
+        assert_eq!(applied.to_index.iter().filter_map(|(block, _)| block.id).collect::<Vec<_>>(), applied.result.ids);
+        let sunday = crate::commands::load_time_blocks(&conn, "2025-06-01").unwrap();
+        assert_eq!((sunday[0].title.as_str(), sunday[0].start_minutes), ("Review", 1080));
+        assert_eq!(sunday[0].tags, vec!["routine"]);
+        assert_eq!(crate::commands::load_time_blocks(&conn, "2025-06-02").unwrap()[0].title, "Plan the week");
+        assert_eq!(crate::commands::load_time_blocks(&conn, "2025-06-04").unwrap()[0].title, "Deep work");
+
+        let again = apply_template(&conn, &files, id, "2025-06-01").unwrap();
+        assert_eq!(again.result, AppliedTemplate { ids: Vec::new(), skipped: 3 });
+        assert!(again.to_index.is_empty());
+    }
+
+    #[test]
+    fn test_save_and_delete_templates() {
+        let mut conn = test_db();
+        let mut template = WeekTemplate { id: None, name: "Focus".to_string(), blocks: vec![block("friday", 540, "Admin")] };
+        template.id = Some(save_template(&mut conn, &template).unwrap());
+
+        template.blocks = vec![block("friday", 600, "Admin"), block("friday", 480, "Email")];
+        save_template(&mut conn, &template).unwrap();
+        let saved = list_templates(&conn).unwrap();
+        assert_eq!(saved.len(), 1);
+        assert_eq!(saved[0].blocks.iter().map(|b| b.title.as_str()).collect::<Vec<_>>(), vec!["Email", "Admin"]);
+
+        assert!(save_template(&mut conn, &WeekTemplate { id: None, name: "Bad".to_string(), blocks: vec![block("someday", 540, "x")] }).is_err());
+        assert!(save_template(&mut conn, &WeekTemplate { id: None, name: "Late".to_string(), blocks: vec![block("monday", 1400, "x")] }).is_err());
+        assert!(save_template(&mut conn, &WeekTemplate { id: None, name: " ".to_string(), blocks: Vec::new() }).is_err());
+
+        delete_template(&conn, template.id.unwrap()).unwrap();
+        assert!(list_templates(&conn).unwrap().is_empty());
+        assert_eq!(delete_template(&conn, 1).unwrap_err().code(), "not_found");
+    }
+}
@@ -0,0 +1,200 @@
+use anyhow::{anyhow, Result};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+
+use crate::commands::{load_time_block, relocate_attachments, sync_block_tags};
+use crate::models::TimeBlock;
+use crate::services::FileService;
+
+// Only the most recent operations are kept
+const MAX_LOGGED_OPERATIONS: i64 = 50;
+
+/// A reversible operation, stored as JSON in operation_log.payload.
+/// New kinds of operations get a variant here and a branch in `undo_last`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "operation", rename_all = "snake_case")]
+pub enum LoggedOperation {
+    DeleteTimeBlock { block: TimeBlock, notes: Option<String> },
+    UpdateTimeBlock { previous: TimeBlock },
+}
+
+impl LoggedOperation {
+    pub fn scope(&self) -> &'static str {
+        match self {
+            LoggedOperation::DeleteTimeBlock { .. } | LoggedOperation::UpdateTimeBlock { .. } => "time_block",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            LoggedOperation::DeleteTimeBlock { .. } => "delete_time_block",
+            LoggedOperation::UpdateTimeBlock { .. } => "update_time_block",
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UndoResult {
+    pub scope: String,
+    pub operation: String,
+    pub block: TimeBlock,  // The block as restored
+}
+
+pub fn record(conn: &Connection, operation: &LoggedOperation) -> Result<()> {
+    conn.execute(
+        "INSERT INTO operation_log (scope, operation, payload) VALUES (?1, ?2, ?3)",
+        (operation.scope(), operation.name(), serde_json::to_string(operation)?),
+    )?;
+
+    conn.execute(
+        "DELETE FROM operation_log WHERE id NOT IN (SELECT id FROM operation_log ORDER BY id DESC LIMIT ?1)",
+        [MAX_LOGGED_OPERATIONS],
+    )?;
+
+    Ok(())
+}
+
+// Reverse the most recent logged operation (optionally within a scope) and drop it from the log
+pub fn undo_last(conn: &Connection, files: &FileService, scope: Option<&str>) -> Result<Option<UndoResult>> {
+    let entry: Option<(i64, String)> = conn.query_row(
+        "SELECT id, payload FROM operation_log WHERE ?1 IS NULL OR scope = ?1 ORDER BY id DESC LIMIT 1",
+        [scope],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    ).optional()?;
+
+    let (log_id, payload) = match entry {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    let operation: LoggedOperation = serde_json::from_str(&payload)?;
+
+    let tx = conn.unchecked_transaction()?;
+    let restored = match &operation {
+        LoggedOperation::DeleteTimeBlock { block, notes } => {
+            let id = block.id.ok_or_else(|| anyhow!("Logged block has no id"))?;
+            let mut restored = block.clone();
+            restored.notes_file = match notes {
+                Some(content) => Some(files.save_notes(&restored, content)?),
+                None => None,
+            };
+
+            tx.execute(
+                "INSERT INTO time_blocks (id, date, start_minutes, duration_minutes, title, notes_file, color, tags, reminder_minutes,
+                 completed, actual_duration_minutes, created_at, is_placeholder)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, COALESCE(?12, CURRENT_TIMESTAMP), ?13)",
+                (id, &restored.date, restored.start_minutes, restored.duration_minutes, &restored.title,
+                 &restored.notes_file, &restored.color, serde_json::to_string(&restored.tags)?,
+                 restored.reminder_minutes, restored.completed, restored.actual_duration_minutes, &restored.created_at,
+                 restored.is_placeholder),
+            )?;
+            sync_block_tags(&tx, id, &restored.tags)?;
+            restored
+        }
+        LoggedOperation::UpdateTimeBlock { previous } => {
+            let id = previous.id.ok_or_else(|| anyhow!("Logged block has no id"))?;
+            let current = load_time_block(&tx, id)?
+                .ok_or_else(|| anyhow!("Block {} no longer exists", id))?;
+
+            // Files are named after the block's date and start time, so bring them back too
+            let mut previous = previous.clone();
+            relocate_attachments(&tx, files, id, &current.date, &previous.date)?;
+            if let Some(notes_file) = &current.notes_file {
+                previous.notes_file = Some(files.rename_notes(notes_file, &previous)?);
+            }
+
+            tx.execute(
+                "UPDATE time_blocks SET date = ?1, start_minutes = ?2, duration_minutes = ?3, title = ?4,
+                 notes_file = ?5, color = ?6, tags = ?7, reminder_minutes = ?8, is_placeholder = ?9, updated_at = CURRENT_TIMESTAMP
+                 WHERE id = ?10",
+                (&previous.date, previous.start_minutes, previous.duration_minutes, &previous.title,
+                 &previous.notes_file, &previous.color, serde_json::to_string(&previous.tags)?,
+                 previous.reminder_minutes, previous.is_placeholder, id),
+            )?;
+            sync_block_tags(&tx, id, &previous.tags)?;
+            previous
+        }
+    };
+
+    tx.execute("DELETE FROM operation_log WHERE id = ?1", [log_id])?;
+    tx.commit()?;
+
+    Ok(Some(UndoResult {
+        scope: operation.scope().to_string(),
+        operation: operation.name().to_string(),
+        block: restored,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::load_time_blocks;
+    use tempfile::tempdir;
+
+    fn setup() -> (Connection, FileService, tempfile::TempDir) {
+        let dir = tempdir().unwrap();
+        let files = FileService::new(dir.path().to_path_buf()).unwrap();
+        let conn = Connection::open_in_memory().unwrap();
+        crate::init_database(&conn).unwrap();
+        (conn, files, dir)
+    }
+
+    fn insert_block(conn: &Connection, title: &str) -> TimeBlock {
+        conn.execute(
+            "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title, tags) VALUES ('2025-06-01', 540, 30, ?1, '[\"work\"]')",
+            [title],
+        ).unwrap();
+        load_time_blocks(conn, "2025-06-01").unwrap().pop().unwrap()
+    }
+
+    #[test]
+    fn test_undo_delete_restores_block_and_notes() {
+        let (conn, files, _dir) = setup();
+        let block = insert_block(&conn, "Focus");
+
+        record(&conn, &LoggedOperation::DeleteTimeBlock {
+            block: block.clone(),
+            notes: Some("Remember the outline".to_string()),
+        }).unwrap();
+        conn.execute("DELETE FROM time_blocks WHERE id = ?1", [block.id]).unwrap();
+
+        let result = undo_last(&conn, &files, Some("time_block")).unwrap().unwrap();
+        assert_eq!(result.operation, "delete_time_block");
+
+        let restored = load_time_blocks(&conn, "2025-06-01").unwrap();
+        assert_eq!(restored.len(), 1);
+        assert_eq!(restored[0].id, block.id);
+        assert_eq!(restored[0].tags, vec!["work"]);
+        let notes_file = restored[0].notes_file.as_ref().unwrap();
+        assert_eq!(files.load_notes(notes_file).unwrap(), "Remember the outline");
+
+        // The log entry is consumed
+        assert!(undo_last(&conn, &files, None).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_undo_update_reverts_title() {
+        let (conn, files, _dir) = setup();
+        let block = insert_block(&conn, "Original");
+
+        record(&conn, &LoggedOperation::UpdateTimeBlock { previous: block.clone() }).unwrap();
+        conn.execute("UPDATE time_blocks SET title = 'Edited' WHERE id = ?1", [block.id]).unwrap();
+
+        undo_last(&conn, &files, None).unwrap().unwrap();
+        assert_eq!(load_time_blocks(&conn, "2025-06-01").unwrap()[0].title, "Original");
+        assert!(undo_last(&conn, &files, Some("other_scope")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_log_is_bounded() {
+        let (conn, _files, _dir) = setup();
+        let block = insert_block(&conn, "Focus");
+
+        for _ in 0..(MAX_LOGGED_OPERATIONS + 10) {
+            record(&conn, &LoggedOperation::UpdateTimeBlock { previous: block.clone() }).unwrap();
+        }
+
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM operation_log", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, MAX_LOGGED_OPERATIONS);
+    }
+}
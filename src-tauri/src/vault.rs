@@ -0,0 +1,159 @@
+use anyhow::{anyhow, Result};
+use log::warn;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Lives in the default app data directory, since the database itself may move
+const CONFIG_FILE: &str = "vault.json";
+pub const DATABASE_FILE: &str = "timeblock.db";
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct VaultConfig {
+    data_directory: Option<PathBuf>,
+}
+
+fn read_config(default_dir: &Path) -> VaultConfig {
+    fs::read_to_string(default_dir.join(CONFIG_FILE))
+        .ok()
+        .and_then(|content| serde_json::from_str(&content).ok())
+        .unwrap_or_default()
+}
+
+/// The configured data directory, if any (whether or not it currently exists).
+pub fn configured_data_dir(default_dir: &Path) -> Option<PathBuf> {
+    read_config(default_dir).data_directory
+}
+
+/// Where the database, notes, attachments and search index live for this run.
+/// Falls back to the default directory if the configured one is missing,
+/// e.g. a synced folder on a drive that isn't mounted.
+pub fn resolve_data_dir(default_dir: &Path) -> PathBuf {
+    match configured_data_dir(default_dir) {
+        Some(dir) if dir.is_dir() => dir,
+        Some(dir) => {
+            warn!("Configured data directory {:?} is missing, using {:?}", dir, default_dir);
+            default_dir.to_path_buf()
+        }
+        None => default_dir.to_path_buf(),
+    }
+}
+
+fn ensure_writable(dir: &Path) -> Result<()> {
+    if !dir.is_absolute() {
+        return Err(anyhow!("Data directory must be an absolute path"));
+    }
+    fs::create_dir_all(dir)?;
+
+    let probe = dir.join(".timebloc-write-test");
+    fs::write(&probe, b"")
+        .map_err(|e| anyhow!("Data directory {:?} is not writable: {}", dir, e))?;
+    fs::remove_file(&probe)?;
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name_str = name.to_string_lossy();
+
+        // The database is copied consistently with VACUUM INTO instead
+        if name_str == CONFIG_FILE || name_str.starts_with(DATABASE_FILE) {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to.join(&name))?;
+        } else {
+            fs::copy(entry.path(), to.join(&name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Copy the current data (database, notes, attachments, search index and
+/// encryption key) into `target`. Refuses to overwrite an existing database.
+pub fn migrate_data(conn: &Connection, current_dir: &Path, target: &Path) -> Result<()> {
+    ensure_writable(target)?;
+    let target_db = target.join(DATABASE_FILE);
+    if target_db.exists() {
+        return Err(anyhow!("{:?} already contains a TimeBloc database", target));
+    }
+
+    copy_dir(current_dir, target)?;
+    conn.execute("VACUUM INTO ?1", [target_db.to_string_lossy().into_owned()])?;
+    Ok(())
+}
+
+/// Point the next startup at `target`, or back at the default directory when None.
+pub fn set_data_dir(default_dir: &Path, target: Option<&Path>) -> Result<()> {
+    if let Some(target) = target {
+        ensure_writable(target)?;
+    }
+
+    let config = VaultConfig {
+        data_directory: target.map(Path::to_path_buf),
+    };
+    fs::create_dir_all(default_dir)?;
+    fs::write(default_dir.join(CONFIG_FILE), serde_json::to_string_pretty(&config)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_resolve_falls_back_when_missing() {
+        let default_dir = tempdir().unwrap();
+        let vault = tempdir().unwrap();
+
+        assert_eq!(resolve_data_dir(default_dir.path()), default_dir.path());
+
+        set_data_dir(default_dir.path(), Some(vault.path())).unwrap();
+        assert_eq!(resolve_data_dir(default_dir.path()), vault.path());
+
+        let vault_path = vault.path().to_path_buf();
+        drop(vault);
+        assert_eq!(configured_data_dir(default_dir.path()), Some(vault_path));
+        assert_eq!(resolve_data_dir(default_dir.path()), default_dir.path());
+
+        set_data_dir(default_dir.path(), None).unwrap();
+        assert_eq!(configured_data_dir(default_dir.path()), None);
+    }
+
+    #[test]
+    fn test_rejects_relative_path() {
+        let default_dir = tempdir().unwrap();
+        assert!(set_data_dir(default_dir.path(), Some(Path::new("relative/vault"))).is_err());
+    }
+
+    #[test]
+    fn test_migrate_copies_database_and_files() {
+        let current = tempdir().unwrap();
+        let target = tempdir().unwrap();
+
+        let conn = Connection::open(current.path().join(DATABASE_FILE)).unwrap();
+        crate::init_database(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO time_blocks (date, start_minutes, duration_minutes, title) VALUES ('2025-06-01', 540, 30, 'Focus')",
+            [],
+        ).unwrap();
+        fs::create_dir_all(current.path().join("notes/2025-06-01")).unwrap();
+        fs::write(current.path().join("notes/2025-06-01/0540-1.md"), "notes").unwrap();
+
+        migrate_data(&conn, current.path(), target.path()).unwrap();
+
+        assert_eq!(fs::read_to_string(target.path().join("notes/2025-06-01/0540-1.md")).unwrap(), "notes");
+        let migrated = Connection::open(target.path().join(DATABASE_FILE)).unwrap();
+        let count: i64 = migrated.query_row("SELECT COUNT(*) FROM time_blocks", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+
+        // A second migration must not clobber the copy
+        assert!(migrate_data(&conn, current.path(), target.path()).is_err());
+    }
+}